@@ -0,0 +1,159 @@
+use chrono::prelude::*;
+use super::lib::*;
+use super::importe::*;
+use super::interes_legal::fecha_ley_5_2019;
+
+/// Forma en que se recalculan las cuotas tras una amortización
+/// anticipada parcial
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ModalidadAmortizacion {
+    /// Se mantiene el plazo y se reduce el importe de la cuota
+    ReducirCuota,
+    /// Se mantiene el importe de la cuota y se reduce el plazo
+    ReducirPlazo,
+}
+
+impl ModalidadAmortizacion {
+    /// Código de la modalidad usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            ModalidadAmortizacion::ReducirCuota => "REDUCIR_CUOTA",
+            ModalidadAmortizacion::ReducirPlazo => "REDUCIR_PLAZO",
+        }
+    }
+    /// Obtiene la modalidad a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        [ModalidadAmortizacion::ReducirCuota, ModalidadAmortizacion::ReducirPlazo].into_iter()
+            .find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+}
+
+/// Comisión por amortización anticipada pactada. Los porcentajes son los
+/// máximos legales según la fecha de la escritura (art. 23 de la Ley 5/2019
+/// o, en las anteriores, art. 8 de la Ley 41/2007) y se aplican sobre el
+/// capital reembolsado anticipadamente
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ComisionAmortizacion {
+    /// Sin comisión pactada
+    SinComision,
+    /// Tipo variable, comisión durante los 5 primeros años: 0,15%
+    VariableCincoAgnos,
+    /// Tipo variable, comisión durante los 3 primeros años: 0,25%
+    VariableTresAgnos,
+    /// Tipo fijo: 2% durante los 10 primeros años y 1,5% después
+    Fijo,
+}
+impl ComisionAmortizacion {
+    /// Todas las comisiones disponibles
+    pub fn todas() -> [ComisionAmortizacion; 4] {
+        [ComisionAmortizacion::SinComision, ComisionAmortizacion::VariableCincoAgnos,
+            ComisionAmortizacion::VariableTresAgnos, ComisionAmortizacion::Fijo]
+    }
+    /// Código de la comisión usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            ComisionAmortizacion::SinComision => "SIN_COMISION",
+            ComisionAmortizacion::VariableCincoAgnos => "VARIABLE_5",
+            ComisionAmortizacion::VariableTresAgnos => "VARIABLE_3",
+            ComisionAmortizacion::Fijo => "FIJO",
+        }
+    }
+    /// Obtiene la comisión a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        Self::todas().into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Porcentaje máximo (en tanto por uno) aplicable a una
+    /// amortización realizada en la fecha indicada
+    pub fn porcentaje(&self, fecha_escritura: Date<Utc>, fecha: Date<Utc>) -> f64 {
+        let agnos = agnos_completos(fecha_escritura, fecha);
+        if fecha_escritura < fecha_ley_5_2019() {
+            return self.porcentaje_ley_41_2007(agnos);
+        }
+        match self {
+            ComisionAmortizacion::SinComision => 0.0,
+            ComisionAmortizacion::VariableCincoAgnos => if agnos < 5 { 0.0015 } else { 0.0 },
+            ComisionAmortizacion::VariableTresAgnos => if agnos < 3 { 0.0025 } else { 0.0 },
+            ComisionAmortizacion::Fijo => if agnos < 10 { 0.02 } else { 0.015 },
+        }
+    }
+    /// Límites de las escrituras anteriores a la Ley 5/2019: en los préstamos
+    /// a tipo variable, 0,5% durante los 5 primeros años y 0,25% después.
+    /// La Ley 41/2007 no fija un máximo para la compensación por riesgo de
+    /// tipo de interés de los préstamos a tipo fijo, por lo que se mantienen
+    /// los porcentajes de la Ley 5/2019
+    fn porcentaje_ley_41_2007(&self, agnos: i32) -> f64 {
+        match self {
+            ComisionAmortizacion::SinComision => 0.0,
+            ComisionAmortizacion::VariableCincoAgnos | ComisionAmortizacion::VariableTresAgnos => 
+                if agnos < 5 { 0.005 } else { 0.0025 },
+            ComisionAmortizacion::Fijo => if agnos < 10 { 0.02 } else { 0.015 },
+        }
+    }
+    /// Importe de la comisión por amortizar anticipadamente
+    /// un capital en la fecha indicada
+    pub fn importe(&self, redondeo: Redondeo, fecha_escritura: Date<Utc>, fecha: Date<Utc>, 
+            capital: Importe) -> Importe {
+        redondeo.importe(capital * tipo(self.porcentaje(fecha_escritura, fecha)))
+    }
+}
+
+/// Amortización anticipada, parcial o total, del capital pendiente
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AmortizacionAnticipada {
+    /// Fecha en que se realiza la amortización. Si coincide con
+    /// un vencimiento se aplica después de pagar esa cuota
+    pub fecha: Date<Utc>,
+    /// Capital que se amortiza. Si es igual o mayor que el capital
+    /// pendiente, la amortización es total
+    pub importe: Importe,
+    /// Forma de recalcular las cuotas siguientes
+    pub modalidad: ModalidadAmortizacion,
+}
+impl AmortizacionAnticipada {
+    pub fn new(fecha: Date<Utc>, importe: Importe, modalidad: ModalidadAmortizacion) -> Self {
+        AmortizacionAnticipada { fecha, importe, modalidad }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    #[test]
+    fn test_porcentaje() {
+        let fecha = Utc.ymd(2020, 3, 17);
+        let c = ComisionAmortizacion::VariableCincoAgnos;
+        assert_eq!(0.0015, c.porcentaje(fecha, Utc.ymd(2025, 3, 16)));
+        assert_eq!(0.0, c.porcentaje(fecha, Utc.ymd(2025, 3, 17)));
+        let c = ComisionAmortizacion::VariableTresAgnos;
+        assert_eq!(0.0025, c.porcentaje(fecha, Utc.ymd(2022, 12, 1)));
+        assert_eq!(0.0, c.porcentaje(fecha, Utc.ymd(2023, 3, 17)));
+        let c = ComisionAmortizacion::Fijo;
+        assert_eq!(0.02, c.porcentaje(fecha, Utc.ymd(2030, 3, 16)));
+        assert_eq!(0.015, c.porcentaje(fecha, Utc.ymd(2030, 3, 17)));
+        assert_eq!(0.0, ComisionAmortizacion::SinComision.porcentaje(fecha, fecha));
+        // Escrituras anteriores a la Ley 5/2019: límites de la Ley 41/2007
+        let fecha = Utc.ymd(2004, 3, 17);
+        let c = ComisionAmortizacion::VariableTresAgnos;
+        assert_eq!(0.005, c.porcentaje(fecha, Utc.ymd(2009, 3, 16)));
+        assert_eq!(0.0025, c.porcentaje(fecha, Utc.ymd(2009, 3, 17)));
+        assert_eq!(0.0025, ComisionAmortizacion::VariableCincoAgnos.porcentaje(fecha, Utc.ymd(2020, 1, 1)));
+        assert_eq!(0.0, ComisionAmortizacion::SinComision.porcentaje(fecha, fecha));
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(ModalidadAmortizacion::ReducirPlazo), ModalidadAmortizacion::from_codigo("reducir_plazo"));
+        assert_eq!(None, ModalidadAmortizacion::from_codigo("TOTAL"));
+        assert_eq!(Some(ComisionAmortizacion::VariableTresAgnos), ComisionAmortizacion::from_codigo("VARIABLE_3"));
+        assert_eq!(None, ComisionAmortizacion::from_codigo("MIXTA"));
+    }
+    #[test]
+    fn test_importe() {
+        let c = ComisionAmortizacion::VariableTresAgnos;
+        assert_eq!(dec!(25), c.importe(Redondeo::MitadArriba, Utc.ymd(2020, 3, 17), Utc.ymd(2021, 6, 1), 
+            dec!(10000)));
+        // 0,25% de 1.234,56 es 3,0864
+        assert_eq!(dec!(3.08), c.importe(Redondeo::Truncamiento, Utc.ymd(2020, 3, 17), Utc.ymd(2021, 6, 1), 
+            dec!(1234.56)));
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use super::importe::*;
+use super::lib::last_day_of_month;
+use super::comparacion_tablas::ComparacionTablas;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Base de cálculo (convención de cómputo de días) con la que
+/// se calculan los intereses de cada periodo
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BaseCalculo {
+    /// Meses de 30 días y años de 360 (30E/360). Cada mes completo
+    /// devenga la doceava parte del tipo anual
+    #[default]
+    Treinta360,
+    /// Días naturales sobre años de 365 días
+    Real365,
+    /// Días naturales sobre años de 360 días. Devenga más intereses
+    /// que las demás bases con el mismo tipo nominal
+    Real360,
+    /// Días naturales sobre los días de cada año natural (365 o 366)
+    RealReal,
+}
+
+impl BaseCalculo {
+    /// Todas las bases de cálculo disponibles
+    pub fn todas() -> [BaseCalculo; 4] {
+        [BaseCalculo::Treinta360, BaseCalculo::Real365,
+            BaseCalculo::Real360, BaseCalculo::RealReal]
+    }
+    /// Código de la base usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            BaseCalculo::Treinta360 => "30/360",
+            BaseCalculo::Real365 => "ACT/365",
+            BaseCalculo::Real360 => "ACT/360",
+            BaseCalculo::RealReal => "ACT/ACT",
+        }
+    }
+    /// Obtiene la base a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        Self::todas().into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Nombre de la base, tal como se escribe en los informes
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            BaseCalculo::Treinta360 => "30/360",
+            BaseCalculo::Real365 => "Real/365",
+            BaseCalculo::Real360 => "Real/360",
+            BaseCalculo::RealReal => "Real/Real",
+        }
+    }
+    /// Días que se computan entre dos fechas. En la base 30/360 el
+    /// último día de cada mes cuenta como día 30
+    pub fn dias(&self, desde: Date<Utc>, hasta: Date<Utc>) -> i64 {
+        match self {
+            BaseCalculo::Treinta360 => {
+                let dia = |fecha: Date<Utc>| {
+                    let ultimo = last_day_of_month(fecha.year(), fecha.month()).unwrap_or(31);
+                    if fecha.day() == ultimo { 30 } else { std::cmp::min(fecha.day(), 30) as i64 }
+                };
+                (hasta.year() - desde.year()) as i64 * 360
+                    + (hasta.month() as i64 - desde.month() as i64) * 30
+                    + dia(hasta) - dia(desde)
+            },
+            _ => (hasta - desde).num_days(),
+        }
+    }
+    /// Fracción de año que transcurre entre dos fechas
+    pub fn fraccion_anual(&self, desde: Date<Utc>, hasta: Date<Utc>) -> Decimal {
+        self.periodos(desde, hasta).into_iter()
+            .map(|(dias, base)| Decimal::from(dias) / Decimal::from(base))
+            .sum()
+    }
+    /// Intereses de un capital entre dos fechas, redondeados a céntimos.
+    /// Se divide al final para que un mes completo en la base 30/360
+    /// dé exactamente los mismos intereses que el tipo anual entre 12
+    pub fn intereses(&self, redondeo: Redondeo, capital: Importe, tipo_interes_anual: f64,
+            desde: Date<Utc>, hasta: Date<Utc>) -> Importe {
+        let intereses: Decimal = self.periodos(desde, hasta).into_iter()
+            .map(|(dias, base)| capital * tipo(tipo_interes_anual) * Decimal::from(dias) / Decimal::from(base))
+            .sum();
+        redondeo.importe(intereses)
+    }
+    /// Días computables y días del año de cada tramo entre dos fechas.
+    /// En la base Real/Real hay un tramo por año natural
+    fn periodos(&self, desde: Date<Utc>, hasta: Date<Utc>) -> Vec<(i64, i64)> {
+        match self {
+            BaseCalculo::Treinta360 | BaseCalculo::Real360 => vec![(self.dias(desde, hasta), 360)],
+            BaseCalculo::Real365 => vec![(self.dias(desde, hasta), 365)],
+            BaseCalculo::RealReal => {
+                let mut periodos = Vec::new();
+                let mut inicio = desde;
+                while inicio < hasta {
+                    let inicio_agno_siguiente = Utc.ymd(inicio.year()+1, 1, 1);
+                    let fin = std::cmp::min(hasta, inicio_agno_siguiente);
+                    let dias_agno = (inicio_agno_siguiente - Utc.ymd(inicio.year(), 1, 1)).num_days();
+                    periodos.push(((fin - inicio).num_days(), dias_agno));
+                    inicio = fin;
+                }
+                periodos
+            },
+        }
+    }
+}
+
+/// Comparación de las cuotas calculadas por la entidad con su base
+/// de cálculo y las que resultan de la base pactada en la escritura
+pub struct ComparacionBaseCalculo {
+    /// Base de cálculo aplicada por la entidad
+    pub base_entidad: BaseCalculo,
+    /// Base de cálculo pactada en la escritura
+    pub base_contractual: BaseCalculo,
+    /// Tabla de amortización con la base de la entidad
+    pub tabla: TablaAmortizacion,
+    /// Diferencias entre las cuotas con la base de la entidad y con la pactada
+    pub comparacion: ComparacionTablas,
+}
+
+impl ComparacionBaseCalculo {
+    /// Total cobrado de más por aplicar la base de la entidad
+    pub fn total_cobrado_de_mas(&self) -> Importe {
+        self.comparacion.total_diferencias()
+    }
+    pub fn disp(&self) {
+        println!("Base de la entidad {} frente a base pactada {}", 
+            self.base_entidad.nombre(), self.base_contractual.nombre());
+        self.comparacion.disp();
+    }
+    /// Escribe la tabla con la base de la entidad en <nombre>_base_entidad.txt
+    /// y las diferencias por cuota en <nombre>_base_calculo.txt
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        self.tabla.print(&(String::from(nombre) + "_base_entidad"))?;
+        self.comparacion.print(&(String::from(nombre) + "_base_calculo"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_dias() {
+        let b = BaseCalculo::Treinta360;
+        assert_eq!(30, b.dias(Utc.ymd(2004, 3, 17), Utc.ymd(2004, 4, 17)));
+        assert_eq!(30, b.dias(Utc.ymd(2005, 1, 31), Utc.ymd(2005, 2, 28)));
+        assert_eq!(30, b.dias(Utc.ymd(2005, 2, 28), Utc.ymd(2005, 3, 30)));
+        assert_eq!(44, b.dias(Utc.ymd(2004, 3, 17), Utc.ymd(2004, 5, 1)));
+        assert_eq!(28, BaseCalculo::Real365.dias(Utc.ymd(2005, 2, 1), Utc.ymd(2005, 3, 1)));
+    }
+    #[test]
+    fn test_intereses() {
+        let desde = Utc.ymd(2004, 3, 17);
+        let hasta = Utc.ymd(2004, 4, 17);
+        let r = Redondeo::MitadArriba;
+        assert_eq!(dec!(280.47), BaseCalculo::Treinta360.intereses(r, dec!(84140), 0.04, desde, hasta));
+        assert_eq!(dec!(285.85), BaseCalculo::Real365.intereses(r, dec!(84140), 0.04, desde, hasta));
+        assert_eq!(dec!(289.82), BaseCalculo::Real360.intereses(r, dec!(84140), 0.04, desde, hasta));
+        assert_eq!(dec!(285.06), BaseCalculo::RealReal.intereses(r, dec!(84140), 0.04, desde, hasta));
+        // Periodo que abarca dos años naturales
+        assert_eq!(dec!(1), BaseCalculo::RealReal.fraccion_anual(Utc.ymd(2003, 7, 1), Utc.ymd(2004, 7, 1))
+            .round_dp(2));
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(BaseCalculo::Real360), BaseCalculo::from_codigo("act/360"));
+        assert_eq!(Some(BaseCalculo::Treinta360), BaseCalculo::from_codigo(" 30/360 "));
+        assert_eq!(None, BaseCalculo::from_codigo("30/365"));
+    }
+}
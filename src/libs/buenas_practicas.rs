@@ -0,0 +1,165 @@
+use chrono::prelude::*;
+use super::lib::*;
+use super::importe::*;
+use super::comparacion_tablas::ComparacionTablas;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Cuotas de carencia de capital de la reestructuración
+pub const MESES_CARENCIA_BUENAS_PRACTICAS: i32 = 60;
+/// Diferencial sobre el euribor durante la carencia
+pub const DIFERENCIAL_BUENAS_PRACTICAS: f64 = 0.0025;
+/// Plazo total máximo, contado desde la concesión del préstamo
+pub const PLAZO_BUENAS_PRACTICAS: i32 = 480;
+
+/// Límite del precio de adquisición de la vivienda según la población
+/// del municipio, en la redacción original del art. 5.2 del RDL 6/2012
+pub fn limite_precio_rdl_6_2012(habitantes: u32) -> Importe {
+    match habitantes {
+        h if h > 1_000_000 => Importe::from(200_000),
+        h if h > 500_000 => Importe::from(180_000),
+        h if h > 100_000 => Importe::from(150_000),
+        _ => Importe::from(120_000),
+    }
+}
+
+/// Datos del deudor para comprobar si está en el umbral de exclusión
+/// del Código de Buenas Prácticas (art. 3 RDL 6/2012)
+pub struct SolicitudBuenasPracticas {
+    /// Ingresos netos mensuales de la unidad familiar
+    pub ingresos_mensuales: Importe,
+    /// IPREM mensual vigente
+    pub iprem_mensual: Importe,
+    /// Veces el IPREM que no pueden superar los ingresos: tres con
+    /// carácter general, cuatro o cinco en los supuestos especiales
+    pub veces_iprem: Importe,
+    /// Proporción mínima (en tanto por uno) de la cuota sobre los
+    /// ingresos: el 50%, o el 40% en los supuestos especiales
+    pub esfuerzo_minimo: f64,
+    /// Precio de adquisición de la vivienda hipotecada
+    pub precio_adquisicion: Importe,
+    /// Precio máximo de adquisición aplicable a la vivienda
+    pub limite_precio: Importe,
+}
+
+impl SolicitudBuenasPracticas {
+    /// Solicitud con los umbrales generales: tres veces el IPREM y
+    /// cuota de al menos la mitad de los ingresos
+    pub fn new(ingresos_mensuales: Importe, iprem_mensual: Importe,
+            precio_adquisicion: Importe, limite_precio: Importe) -> Self {
+        SolicitudBuenasPracticas { ingresos_mensuales, iprem_mensual, veces_iprem: Importe::from(3),
+            esfuerzo_minimo: 0.5, precio_adquisicion, limite_precio }
+    }
+    /// Comprueba los umbrales con la cuota vigente antes de la reestructuración
+    pub fn evalua(&self, cuota: Importe) -> Elegibilidad {
+        let umbral_ingresos = redondea_dos_decimales(self.iprem_mensual * self.veces_iprem);
+        let esfuerzo = if self.ingresos_mensuales > Importe::ZERO {
+            redondea_cinco_decimales(a_f64(cuota / self.ingresos_mensuales))
+        } else {
+            1.0
+        };
+        Elegibilidad { umbral_ingresos, ingresos_bajo_umbral: self.ingresos_mensuales <= umbral_ingresos,
+            esfuerzo, esfuerzo_suficiente: esfuerzo >= self.esfuerzo_minimo,
+            precio_dentro_limite: self.precio_adquisicion <= self.limite_precio }
+    }
+}
+
+/// Resultado de la comprobación de los umbrales del Código de Buenas Prácticas
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Elegibilidad {
+    /// Ingresos máximos de la unidad familiar
+    pub umbral_ingresos: Importe,
+    pub ingresos_bajo_umbral: bool,
+    /// Proporción de la cuota sobre los ingresos netos
+    pub esfuerzo: f64,
+    pub esfuerzo_suficiente: bool,
+    pub precio_dentro_limite: bool,
+}
+
+impl Elegibilidad {
+    pub fn es_elegible(&self) -> bool {
+        self.ingresos_bajo_umbral && self.esfuerzo_suficiente && self.precio_dentro_limite
+    }
+    /// Umbrales que no se cumplen
+    pub fn incumplimientos(&self) -> Vec<&'static str> {
+        [(self.ingresos_bajo_umbral, "Ingresos superiores al umbral"),
+            (self.esfuerzo_suficiente, "Cuota inferior al porcentaje mínimo de los ingresos"),
+            (self.precio_dentro_limite, "Precio de adquisición superior al límite")]
+            .into_iter().filter(|x| !x.0).map(|x| x.1).collect()
+    }
+}
+
+/// Simulación de la reestructuración del Código de Buenas Prácticas:
+/// cinco años de carencia de capital a euribor más 0,25 y ampliación
+/// del plazo hasta cuarenta años desde la concesión
+pub struct ReestructuracionBuenasPracticas {
+    /// Fecha desde la que surte efecto la reestructuración
+    pub fecha: Date<Utc>,
+    pub elegibilidad: Elegibilidad,
+    /// Cuota que se pagaría sin la reestructuración
+    pub cuota_anterior: Importe,
+    /// Primera cuota de la carencia
+    pub cuota_carencia: Importe,
+    /// Primera cuota después de la carencia
+    pub cuota_tras_carencia: Importe,
+    /// Total pendiente de pagar desde la fecha sin la reestructuración
+    pub total_anterior: Importe,
+    /// Tabla de amortización reestructurada
+    pub tabla: TablaAmortizacion,
+    /// Diferencias con las cuotas de la tabla sin reestructurar
+    pub comparacion: ComparacionTablas,
+}
+
+impl ReestructuracionBuenasPracticas {
+    /// Reducción de la cuota durante la carencia
+    pub fn reduccion_cuota(&self) -> Importe {
+        redondea_dos_decimales(self.cuota_anterior - self.cuota_carencia)
+    }
+    /// Total pendiente de pagar desde la fecha con la reestructuración
+    pub fn total_reestructurado(&self) -> Importe {
+        redondea_dos_decimales(self.tabla.cuotas.iter()
+            .filter(|x| x.fecha >= self.fecha).map(|x| x.cuota_total).sum())
+    }
+    /// Coste total de la reestructuración: lo que se paga de más
+    /// por alargar el plazo
+    pub fn coste_total(&self) -> Importe {
+        redondea_dos_decimales(self.total_reestructurado() - self.total_anterior)
+    }
+    pub fn disp(&self) {
+        println!("Reestructuración del Código de Buenas Prácticas desde {}", self.fecha);
+        for incumplimiento in self.elegibilidad.incumplimientos() {
+            println!("{}", incumplimiento);
+        }
+        println!("Cuota anterior: {:.2} Cuota en carencia: {:.2} Reducción: {:.2} Cuota tras la carencia: {:.2}",
+            self.cuota_anterior, self.cuota_carencia, self.reduccion_cuota(), self.cuota_tras_carencia);
+        println!("Total pendiente: {:.2} Total reestructurado: {:.2} Coste: {:.2}",
+            self.total_anterior, self.total_reestructurado(), self.coste_total());
+    }
+    /// Escribe la tabla reestructurada en <nombre>_buenas_practicas.txt y
+    /// las diferencias por cuota en <nombre>_diferencias_buenas_practicas.txt
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        self.tabla.print(&(String::from(nombre) + "_buenas_practicas"))?;
+        self.comparacion.print(&(String::from(nombre) + "_diferencias_buenas_practicas"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_evalua() {
+        let s = SolicitudBuenasPracticas::new(dec!(1200), dec!(600), dec!(140000),
+            limite_precio_rdl_6_2012(300_000));
+        let e = s.evalua(dec!(650));
+        assert_eq!(dec!(1800), e.umbral_ingresos);
+        assert_eq!(0.54167, e.esfuerzo);
+        assert!(e.es_elegible());
+        let e = s.evalua(dec!(500));
+        assert!(!e.es_elegible());
+        assert_eq!(vec!["Cuota inferior al porcentaje mínimo de los ingresos"], e.incumplimientos());
+        let s = SolicitudBuenasPracticas::new(dec!(2000), dec!(600), dec!(190000),
+            limite_precio_rdl_6_2012(50_000));
+        assert_eq!(2, s.evalua(dec!(1200)).incumplimientos().len());
+    }
+}
@@ -0,0 +1,162 @@
+use chrono::prelude::*;
+
+/// Ajuste de las fechas de vencimiento que caen en día inhábil
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum AjusteDiaHabil {
+    /// El vencimiento se mantiene aunque sea inhábil
+    #[default]
+    SinAjuste,
+    /// El vencimiento pasa al siguiente día hábil
+    Siguiente,
+    /// El vencimiento pasa al siguiente día hábil salvo que sea de otro
+    /// mes; en ese caso pasa al día hábil anterior
+    SiguienteModificado,
+}
+
+impl AjusteDiaHabil {
+    /// Código del ajuste usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            AjusteDiaHabil::SinAjuste => "NINGUNO",
+            AjusteDiaHabil::Siguiente => "SIGUIENTE",
+            AjusteDiaHabil::SiguienteModificado => "SIGUIENTE_MODIFICADO",
+        }
+    }
+    /// Obtiene el ajuste a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        [AjusteDiaHabil::SinAjuste, AjusteDiaHabil::Siguiente, AjusteDiaHabil::SiguienteModificado]
+            .into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+}
+
+/// Calendario de días inhábiles para ajustar los vencimientos. Los
+/// sábados y domingos son siempre inhábiles; además se pueden incluir
+/// los festivos del sistema TARGET2, los festivos nacionales de España
+/// y cualquier otro festivo (autonómico o local)
+#[derive(Clone, PartialEq, Debug)]
+pub struct CalendarioFestivos {
+    /// Incluye los festivos de TARGET2: 1 de enero, Viernes Santo,
+    /// Lunes de Pascua, 1 de mayo y 25 y 26 de diciembre
+    pub target2: bool,
+    /// Incluye las fiestas nacionales de España: 1 y 6 de enero,
+    /// Viernes Santo, 1 de mayo, 15 de agosto, 12 de octubre,
+    /// 1 de noviembre y 6, 8 y 25 de diciembre
+    pub nacional: bool,
+    /// Otros festivos
+    pub festivos: Vec<Date<Utc>>,
+}
+
+impl CalendarioFestivos {
+    /// Calendario sin festivos: solo son inhábiles los fines de semana
+    pub fn new() -> Self {
+        CalendarioFestivos { target2: false, nacional: false, festivos: Vec::new() }
+    }
+    /// Calendario con los festivos de TARGET2 y los nacionales de España
+    pub fn target2_y_nacional() -> Self {
+        CalendarioFestivos { target2: true, nacional: true, festivos: Vec::new() }
+    }
+    /// Añade un festivo al calendario
+    pub fn add_festivo(&mut self, fecha: Date<Utc>) {
+        self.festivos.push(fecha);
+    }
+    /// Indica si la fecha es festiva en el calendario
+    pub fn es_festivo(&self, fecha: Date<Utc>) -> bool {
+        let pascua = domingo_de_pascua(fecha.year());
+        let viernes_santo = pascua - chrono::Duration::days(2);
+        let lunes_pascua = pascua + chrono::Duration::days(1);
+        let (mes, dia) = (fecha.month(), fecha.day());
+        let target2 = fecha == viernes_santo || fecha == lunes_pascua
+            || matches!((mes, dia), (1, 1) | (5, 1) | (12, 25) | (12, 26));
+        let nacional = fecha == viernes_santo
+            || matches!((mes, dia), (1, 1) | (1, 6) | (5, 1) | (8, 15) | (10, 12)
+                | (11, 1) | (12, 6) | (12, 8) | (12, 25));
+        (self.target2 && target2) || (self.nacional && nacional) || self.festivos.contains(&fecha)
+    }
+    /// Indica si la fecha es día hábil: ni fin de semana ni festivo
+    pub fn es_habil(&self, fecha: Date<Utc>) -> bool {
+        !matches!(fecha.weekday(), Weekday::Sat | Weekday::Sun) && !self.es_festivo(fecha)
+    }
+    /// Ajusta una fecha de vencimiento que cae en día inhábil
+    pub fn ajusta(&self, fecha: Date<Utc>, ajuste: AjusteDiaHabil) -> Date<Utc> {
+        if ajuste == AjusteDiaHabil::SinAjuste {
+            return fecha;
+        }
+        let mut siguiente = fecha;
+        while !self.es_habil(siguiente) {
+            siguiente = siguiente.succ();
+        }
+        if ajuste == AjusteDiaHabil::SiguienteModificado && siguiente.month() != fecha.month() {
+            let mut anterior = fecha;
+            while !self.es_habil(anterior) {
+                anterior = anterior.pred();
+            }
+            return anterior;
+        }
+        siguiente
+    }
+}
+impl Default for CalendarioFestivos {
+    fn default() -> Self {
+        Self::target2_y_nacional()
+    }
+}
+
+/// Domingo de Pascua del año indicado (calendario gregoriano)
+pub fn domingo_de_pascua(agno: i32) -> Date<Utc> {
+    let a = agno % 19;
+    let b = agno / 100;
+    let c = agno % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let mes = (h + l - 7 * m + 114) / 31;
+    let dia = (h + l - 7 * m + 114) % 31 + 1;
+    Utc.ymd(agno, mes as u32, dia as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_domingo_de_pascua() {
+        assert_eq!(Utc.ymd(2004, 4, 11), domingo_de_pascua(2004));
+        assert_eq!(Utc.ymd(2019, 4, 21), domingo_de_pascua(2019));
+        assert_eq!(Utc.ymd(2024, 3, 31), domingo_de_pascua(2024));
+    }
+    #[test]
+    fn test_es_habil() {
+        let c = CalendarioFestivos::target2_y_nacional();
+        assert!(c.es_habil(Utc.ymd(2022, 10, 11)));
+        assert!(!c.es_habil(Utc.ymd(2022, 10, 12)));
+        assert!(!c.es_habil(Utc.ymd(2022, 10, 15)));
+        // Lunes de Pascua solo en TARGET2
+        assert!(!c.es_habil(Utc.ymd(2022, 4, 18)));
+        assert!(CalendarioFestivos::new().es_habil(Utc.ymd(2022, 4, 18)));
+        let mut c = CalendarioFestivos::new();
+        c.add_festivo(Utc.ymd(2022, 5, 2));
+        assert!(!c.es_habil(Utc.ymd(2022, 5, 2)));
+    }
+    #[test]
+    fn test_ajusta() {
+        let c = CalendarioFestivos::target2_y_nacional();
+        // Sábado 30 de abril de 2022; el lunes 2 de mayo es hábil
+        let fecha = Utc.ymd(2022, 4, 30);
+        assert_eq!(fecha, c.ajusta(fecha, AjusteDiaHabil::SinAjuste));
+        assert_eq!(Utc.ymd(2022, 5, 2), c.ajusta(fecha, AjusteDiaHabil::Siguiente));
+        assert_eq!(Utc.ymd(2022, 4, 29), c.ajusta(fecha, AjusteDiaHabil::SiguienteModificado));
+        // Miércoles 12 de octubre de 2022
+        assert_eq!(Utc.ymd(2022, 10, 13), c.ajusta(Utc.ymd(2022, 10, 12), AjusteDiaHabil::SiguienteModificado));
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(AjusteDiaHabil::SiguienteModificado), AjusteDiaHabil::from_codigo("siguiente_modificado"));
+        assert_eq!(None, AjusteDiaHabil::from_codigo("ANTERIOR"));
+    }
+}
@@ -0,0 +1,78 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+
+/// Liquidación para cancelar el préstamo en una fecha cualquiera:
+/// capital pendiente tras el último vencimiento, intereses corridos
+/// desde ese vencimiento, cantidades vencidas y no pagadas y comisión
+/// por amortización anticipada
+pub struct Cancelacion {
+    /// Fecha de la cancelación
+    pub fecha: Date<Utc>,
+    /// Último vencimiento anterior o igual a la fecha de la cancelación,
+    /// o la fecha de la escritura si aún no ha vencido ninguna cuota
+    pub fecha_ultimo_vencimiento: Date<Utc>,
+    /// Tipo de interés del periodo en curso
+    pub tipo_interes: f64,
+    /// Capital pendiente después del último vencimiento. Tras la
+    /// resolución todo el capital está vencido y se incluye en la
+    /// deuda vencida
+    pub capital_pendiente: Importe,
+    /// Intereses ordinarios devengados desde el último vencimiento
+    pub intereses_corridos: Importe,
+    /// Cuotas impagadas, intereses de demora y gastos pendientes,
+    /// descontados los pagos realizados
+    pub deuda_vencida: Importe,
+    /// Comisión por amortización anticipada del capital pendiente
+    pub comision: Importe,
+}
+
+impl Cancelacion {
+    /// Días transcurridos desde el último vencimiento
+    pub fn dias(&self) -> i64 {
+        (self.fecha - self.fecha_ultimo_vencimiento).num_days()
+    }
+    /// Importe total necesario para cancelar el préstamo
+    pub fn total(&self) -> Importe {
+        redondea_dos_decimales(self.capital_pendiente + self.intereses_corridos
+            + self.deuda_vencida + self.comision)
+    }
+    pub fn disp(&self) {
+        println!("Cancelación a {}: capital pendiente {:.2}, intereses corridos {:.2} ({} días al {}), \
+            deuda vencida {:.2}, comisión {:.2}. Total: {:.2}",
+            self.fecha, self.capital_pendiente, self.intereses_corridos, self.dias(),
+            self.tipo_interes, self.deuda_vencida, self.comision, self.total());
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let fecha = |f: Date<Utc>| format!("{}/{}/{}", f.day(), f.month(), f.year());
+        let importe = |i: Importe| i.to_string().replace(".", ",");
+        writeln!(&file, "Fecha de cancelación; {}", fecha(self.fecha))?;
+        writeln!(&file, "Último vencimiento; {}", fecha(self.fecha_ultimo_vencimiento))?;
+        writeln!(&file, "Capital pendiente; {}", importe(self.capital_pendiente))?;
+        writeln!(&file, "Intereses corridos; {}; {} días; {}", importe(self.intereses_corridos),
+            self.dias(), self.tipo_interes.to_string().replace(".", ","))?;
+        writeln!(&file, "Deuda vencida; {}", importe(self.deuda_vencida))?;
+        writeln!(&file, "Comisión de amortización; {}", importe(self.comision))?;
+        writeln!(&file, "Total; {}", importe(self.total()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_total() {
+        let c = Cancelacion { fecha: Utc.ymd(2010, 2, 1), fecha_ultimo_vencimiento: Utc.ymd(2010, 1, 17),
+            tipo_interes: 0.02, capital_pendiente: dec!(50000), intereses_corridos: dec!(38.89),
+            deuda_vencida: dec!(0), comision: dec!(75) };
+        assert_eq!(15, c.dias());
+        assert_eq!(dec!(50113.89), c.total());
+    }
+}
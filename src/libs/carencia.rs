@@ -0,0 +1,59 @@
+use chrono::prelude::*;
+
+/// Tipo de periodo de carencia
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TipoCarencia {
+    /// Carencia de capital: las cuotas solo pagan los intereses
+    Capital,
+    /// Carencia total: no se paga nada y los intereses se suman
+    /// al capital pendiente
+    Total,
+}
+
+impl TipoCarencia {
+    /// Código del tipo de carencia usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            TipoCarencia::Capital => "CAPITAL",
+            TipoCarencia::Total => "TOTAL",
+        }
+    }
+    /// Obtiene el tipo de carencia a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        [TipoCarencia::Capital, TipoCarencia::Total]
+            .into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+}
+
+/// Periodo de carencia inicial o intermedio. Los meses de carencia
+/// forman parte del plazo del préstamo: al terminar, la cuota se
+/// vuelve a calcular para amortizar el capital pendiente en los
+/// meses que quedan
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Carencia {
+    /// Fecha desde la que se aplica la carencia. La primera cuota
+    /// afectada es la que vence en esa fecha o después
+    pub fecha: Date<Utc>,
+    /// Número de cuotas en carencia
+    pub meses: i32,
+    /// Carencia de capital o total
+    pub tipo: TipoCarencia,
+}
+
+impl Carencia {
+    pub fn new(fecha: Date<Utc>, meses: i32, tipo: TipoCarencia) -> Self {
+        Carencia { fecha, meses, tipo }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(TipoCarencia::Capital), TipoCarencia::from_codigo("capital"));
+        assert_eq!(Some(TipoCarencia::Total), TipoCarencia::from_codigo(" TOTAL "));
+        assert_eq!(None, TipoCarencia::from_codigo("PARCIAL"));
+    }
+}
@@ -0,0 +1,193 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+use super::tabla_amort::TablaAmortizacion;
+use super::interes_legal::InteresLegal;
+
+/// Línea de la comparación de dos tablas de amortización:
+/// la cuota cobrada y la que resulta con otras condiciones
+pub struct LineaComparacion {
+    /// Fecha de vencimiento de la cuota
+    pub fecha: Date<Utc>,
+    /// Tipo de interés aplicado en la cuota cobrada
+    pub tipo_cobrado: f64,
+    /// Tipo de interés con las condiciones alternativas
+    pub tipo_alternativo: f64,
+    /// Importe de la cuota cobrada
+    pub cuota_cobrada: Importe,
+    /// Importe de la cuota con las condiciones alternativas
+    pub cuota_alternativa: Importe,
+    /// Intereses legales de la diferencia desde el vencimiento hasta
+    /// la fecha de liquidación, si se han calculado
+    pub intereses_legales: Importe,
+}
+impl LineaComparacion {
+    /// Importe cobrado de más (o de menos, si es negativo) en la cuota
+    pub fn diferencia(&self) -> Importe {
+        redondea_dos_decimales(self.cuota_cobrada - self.cuota_alternativa)
+    }
+    pub fn to_csv_string(&self) -> String {
+        format!("{}/{}/{}; {}; {}; {}; {}; {}",
+            self.fecha.day(), self.fecha.month(), self.fecha.year(),
+            self.tipo_cobrado.to_string().replace(".", ","),
+            self.tipo_alternativo.to_string().replace(".", ","),
+            self.cuota_cobrada.to_string().replace(".", ","),
+            self.cuota_alternativa.to_string().replace(".", ","),
+            self.diferencia().to_string().replace(".", ","))
+    }
+}
+
+/// Comparación cuota a cuota de dos tablas de amortización
+pub struct ComparacionTablas {
+    /// Fecha hasta la que se calculan los intereses legales de las
+    /// diferencias, si se reclaman con intereses
+    pub fecha_liquidacion: Option<Date<Utc>>,
+    pub lineas: Vec<LineaComparacion>,
+}
+
+impl ComparacionTablas {
+    pub fn new() -> Self {
+        ComparacionTablas { fecha_liquidacion: None, lineas: Vec::<LineaComparacion>::new() }
+    }
+    /// Compara las cuotas mensuales de las dos tablas que vencen
+    /// en la misma fecha, hasta la fecha indicada incluida
+    pub fn compara(cobrada: &TablaAmortizacion, alternativa: &TablaAmortizacion,
+            hasta: Date<Utc>) -> Self {
+        let mut comparacion = ComparacionTablas::new();
+        let cuotas = cobrada.cuotas.iter()
+            .filter(|x| !x.amortizacion_anticipada && x.fecha <= hasta);
+        for cuota in cuotas {
+            let cuota_alternativa = alternativa.cuotas.iter()
+                .find(|x| x.fecha == cuota.fecha && !x.amortizacion_anticipada);
+            if let Some(cuota_alternativa) = cuota_alternativa {
+                comparacion.push(LineaComparacion {
+                    fecha: cuota.fecha,
+                    tipo_cobrado: cuota.i,
+                    tipo_alternativo: cuota_alternativa.i,
+                    cuota_cobrada: cuota.cuota_total,
+                    cuota_alternativa: cuota_alternativa.cuota_total,
+                    intereses_legales: Importe::ZERO,
+                });
+            }
+        }
+        comparacion
+    }
+    pub fn push(&mut self, linea: LineaComparacion) {
+        self.lineas.push(linea);
+    }
+    pub fn total_cobrado(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.cuota_cobrada).sum())
+    }
+    pub fn total_alternativo(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.cuota_alternativa).sum())
+    }
+    /// Total cobrado de más respecto a las condiciones alternativas
+    pub fn total_diferencias(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.diferencia()).sum())
+    }
+    /// Calcula los intereses legales de la diferencia de cada línea desde
+    /// su vencimiento hasta la fecha de liquidación
+    pub fn calcula_intereses_legales(&mut self, il: &InteresLegal, fecha_liquidacion: Date<Utc>) {
+        for linea in self.lineas.iter_mut() {
+            linea.intereses_legales = il.intereses(linea.diferencia(), linea.fecha, fecha_liquidacion);
+        }
+        self.fecha_liquidacion = Some(fecha_liquidacion);
+    }
+    pub fn total_intereses_legales(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.intereses_legales).sum())
+    }
+    /// Importe total a devolver: las diferencias más sus intereses legales
+    pub fn total_a_devolver(&self) -> Importe {
+        redondea_dos_decimales(self.total_diferencias() + self.total_intereses_legales())
+    }
+    /// Línea del informe, con la columna de intereses legales si se han calculado
+    fn linea_csv(&self, linea: &LineaComparacion) -> String {
+        match self.fecha_liquidacion {
+            Some(_) => format!("{}; {}", linea.to_csv_string(), linea.intereses_legales.to_string().replace(".", ",")),
+            None => linea.to_csv_string(),
+        }
+    }
+    pub fn disp(&self) {
+        for linea in self.lineas.iter() {
+            println!("{}", self.linea_csv(linea));
+        }
+        println!("Cobrado: {:.2} Alternativo: {:.2} Diferencia: {:.2}",
+            self.total_cobrado(), self.total_alternativo(), self.total_diferencias());
+        if self.fecha_liquidacion.is_some() {
+            println!("Intereses legales: {:.2} Total a devolver: {:.2}",
+                self.total_intereses_legales(), self.total_a_devolver());
+        }
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let intereses = self.fecha_liquidacion.is_some();
+        writeln!(&file, "Fecha; i_cobrado; i_alternativo; Cuota_cobrada; Cuota_alternativa; Diferencia{}",
+            if intereses { "; Intereses_legales" } else { "" })?;
+        for linea in self.lineas.iter() {
+            writeln!(&file, "{}", self.linea_csv(linea))?;
+        }
+        writeln!(&file, "Total; ; ; {}; {}; {}{}",
+            self.total_cobrado().to_string().replace(".", ","),
+            self.total_alternativo().to_string().replace(".", ","),
+            self.total_diferencias().to_string().replace(".", ","),
+            if intereses { format!("; {}", self.total_intereses_legales().to_string().replace(".", ",")) } 
+            else { String::new() })?;
+        if intereses {
+            writeln!(&file, "Total a devolver; {}", self.total_a_devolver().to_string().replace(".", ","))?;
+        }
+        Ok(())
+    }
+}
+impl Default for ComparacionTablas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    use super::super::cuota::Cuota;
+
+    #[test]
+    fn test_compara() {
+        let mut cobrada = TablaAmortizacion::new();
+        let mut alternativa = TablaAmortizacion::new();
+        cobrada.push(Cuota::new(Utc.ymd(2015, 1, 17), 0.04, 100, dec!(30000), dec!(400), dec!(300), dec!(100)));
+        cobrada.push(Cuota::new(Utc.ymd(2015, 2, 17), 0.04, 99, dec!(29700), dec!(400), dec!(301), dec!(99)));
+        cobrada.push(Cuota::new(Utc.ymd(2015, 3, 17), 0.04, 98, dec!(29399), dec!(400), dec!(302), dec!(98)));
+        alternativa.push(Cuota::new(Utc.ymd(2015, 1, 17), 0.02, 100, dec!(30000), dec!(350.5), dec!(300.5), dec!(50)));
+        alternativa.push(Cuota::new(Utc.ymd(2015, 2, 17), 0.02, 99, dec!(29699.5), dec!(350.5), dec!(301), dec!(49.5)));
+        alternativa.push(Cuota::new(Utc.ymd(2015, 3, 17), 0.02, 98, dec!(29398.5), dec!(350.5), dec!(301.5), dec!(49)));
+        let comparacion = ComparacionTablas::compara(&cobrada, &alternativa, Utc.ymd(2015, 2, 17));
+        assert_eq!(2, comparacion.lineas.len());
+        assert_eq!(dec!(49.5), comparacion.lineas[0].diferencia());
+        assert_eq!(dec!(800), comparacion.total_cobrado());
+        assert_eq!(dec!(701), comparacion.total_alternativo());
+        assert_eq!(dec!(99), comparacion.total_diferencias());
+        assert_eq!(dec!(0), comparacion.total_intereses_legales());
+    }
+    #[test]
+    fn test_intereses_legales() {
+        let il = InteresLegal::new();
+        let mut comparacion = ComparacionTablas::new();
+        for fecha in [Utc.ymd(2015, 10, 17), Utc.ymd(2015, 11, 17)] {
+            comparacion.push(LineaComparacion { fecha, tipo_cobrado: 0.04, tipo_alternativo: 0.01154,
+                cuota_cobrada: dec!(451.35), cuota_alternativa: dec!(352.40), intereses_legales: dec!(0) });
+        }
+        let liquidacion = Utc.ymd(2017, 1, 1);
+        comparacion.calcula_intereses_legales(&il, liquidacion);
+        assert_eq!(Some(liquidacion), comparacion.fecha_liquidacion);
+        assert_eq!(dec!(98.95), comparacion.lineas[0].diferencia());
+        assert_eq!(il.intereses(dec!(98.95), Utc.ymd(2015, 10, 17), liquidacion), 
+            comparacion.lineas[0].intereses_legales);
+        assert!(comparacion.lineas[0].intereses_legales > comparacion.lineas[1].intereses_legales);
+        assert_eq!(dec!(197.9), comparacion.total_diferencias());
+        assert_eq!(redondea_dos_decimales(comparacion.total_diferencias() 
+            + comparacion.total_intereses_legales()), comparacion.total_a_devolver());
+    }
+}
@@ -0,0 +1,327 @@
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::Path;
+use chrono::prelude::*;
+use umya_spreadsheet::helper::date::{excel_to_date_time_object, CALENDAR_WINDOWS_1900};
+use super::lib::*;
+use super::importe::*;
+use super::error::*;
+use super::cuota::Cuota;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Días de diferencia máximos entre la fecha de un recibo y la del
+/// vencimiento de la cuota con la que se empareja
+pub const MAX_DIAS_DESFASE: i64 = 15;
+
+/// Recibo cobrado por la entidad, tal como aparece en su histórico
+/// de movimientos del préstamo
+#[derive(Clone, PartialEq, Debug)]
+pub struct ReciboBanco {
+    /// Fecha de cargo del recibo
+    pub fecha: Date<Utc>,
+    /// Importe total cobrado
+    pub importe: Importe,
+    /// Intereses cobrados
+    pub intereses: Importe,
+    /// Capital amortizado
+    pub capital: Importe,
+    /// Tipo de interés aplicado, en tanto por uno, si figura en el histórico
+    pub tipo: Option<f64>,
+}
+
+impl ReciboBanco {
+    /// Interpreta los campos de una línea del histórico: fecha, importe,
+    /// intereses, capital y, opcionalmente, el tipo aplicado en tanto por
+    /// ciento. Devuelve `None` si la línea no tiene ese formato, como las
+    /// cabeceras
+    fn from_campos(campos: &[String]) -> Option<Self> {
+        if campos.len() < 4 {
+            return None;
+        }
+        let fecha = parse_fecha_recibo(&campos[0])?;
+        let importe = parse_importe(&campos[1])?;
+        let intereses = parse_importe(&campos[2])?;
+        let capital = parse_importe(&campos[3])?;
+        let tipo = match campos.get(4).filter(|x| !x.is_empty()) {
+            Some(x) => Some(redondea_cinco_decimales(a_f64(parse_importe(x.trim_end_matches('%'))?) / 100.0)),
+            None => None,
+        };
+        Some(ReciboBanco { fecha, importe, intereses, capital, tipo })
+    }
+}
+
+/// Interpreta las filas del histórico. Las filas anteriores al primer
+/// recibo se toman como cabeceras y se ignoran, igual que las vacías;
+/// cualquier otra fila que no sea un recibo es un error que indica su
+/// número de línea
+fn recibos_de_filas(filas: impl Iterator<Item = Vec<String>>) -> io::Result<Vec<ReciboBanco>> {
+    let mut recibos = Vec::new();
+    for (n, campos) in filas.enumerate() {
+        if campos.iter().all(|x| x.is_empty()) {
+            continue;
+        }
+        match ReciboBanco::from_campos(&campos) {
+            Some(recibo) => recibos.push(recibo),
+            None if recibos.is_empty() => continue,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "la línea {} no es un recibo (fecha, importe, intereses, capital y tipo): {}", 
+                n + 1, campos.join("; ")))),
+        }
+    }
+    Ok(recibos)
+}
+
+/// Carga el histórico de recibos de la entidad desde un fichero XLSX
+/// (primera hoja) o CSV, según su extensión. Cada fila tiene la fecha,
+/// el importe, los intereses, el capital y, opcionalmente, el tipo
+/// aplicado en tanto por ciento. Solo se admiten cabeceras al principio
+pub fn carga_recibos(path: &Path) -> Result<Vec<ReciboBanco>> {
+    let error = |motivo: String| HipotecaError::Fichero { ruta: path.display().to_string(), motivo };
+    let es_xlsx = path.extension().is_some_and(|x| x.eq_ignore_ascii_case("xlsx"));
+    if !es_xlsx {
+        let contenido = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        return carga_recibos_csv_str(&contenido).map_err(|e| error(e.to_string()));
+    }
+    let book = umya_spreadsheet::reader::xlsx::read(path).map_err(|e| error(format!("{:?}", e)))?;
+    let worksheet = book.get_sheet(&0).map_err(|e| error(e.to_owned()))?;
+    let filas = (1..=worksheet.get_highest_row()).map(|fila| (1..=5)
+        .map(|col| worksheet.get_cell_value_by_column_and_row(&col, &fila).get_value().deref().trim().to_owned())
+        .collect());
+    recibos_de_filas(filas).map_err(|e| error(e.to_string()))
+}
+
+/// Interpreta el histórico de recibos en formato CSV, con separador punto
+/// y coma (y coma decimal) o coma
+pub fn carga_recibos_csv_str(contenido: &str) -> io::Result<Vec<ReciboBanco>> {
+    recibos_de_filas(contenido.lines().map(|linea| {
+        let separador = if linea.contains(';') { ';' } else { ',' };
+        linea.split(separador)
+            .map(|x| x.trim().trim_matches('"').trim().to_owned())
+            .collect()
+    }))
+}
+
+/// Interpreta la fecha de un recibo: dd/mm/aaaa, dd-mm-aaaa, aaaa-mm-dd
+/// o un número de serie de fecha de Excel, como los de las hojas XLSX
+pub(crate) fn parse_fecha_recibo(cad: &str) -> Option<Date<Utc>> {
+    if let Ok(serie) = cad.parse::<f64>() {
+        let fecha = excel_to_date_time_object(&serie, Some(CALENDAR_WINDOWS_1900.to_owned()));
+        return Some(Utc.ymd(fecha.year(), fecha.month(), fecha.day()));
+    }
+    parse_fecha(cad).or_else(|| parse_fecha(&cad.replace('-', "/")))
+}
+
+/// Interpreta un importe con coma decimal y punto de miles (1.234,56)
+/// o con punto decimal (1234.56). Se ignora el símbolo del euro
+pub(crate) fn parse_importe(cad: &str) -> Option<Importe> {
+    let cad = cad.replace(['€', ' '], "");
+    let cad = if cad.contains(',') { cad.replace('.', "").replace(',', ".") } else { cad };
+    cad.parse().ok()
+}
+
+/// Línea de la conciliación: un recibo de la entidad y la cuota
+/// calculada con la que se empareja, o solo uno de ellos si no
+/// se ha encontrado pareja
+pub struct LineaConciliacion {
+    /// Recibo cobrado por la entidad
+    pub recibo: Option<ReciboBanco>,
+    /// Cuota calculada de la tabla con actualización del índice
+    pub cuota: Option<Cuota>,
+}
+
+impl LineaConciliacion {
+    /// Fecha del recibo o, si no lo hay, de la cuota
+    pub fn fecha(&self) -> Option<Date<Utc>> {
+        self.recibo.as_ref().map(|x| x.fecha).or_else(|| self.cuota.as_ref().map(|x| x.fecha))
+    }
+    /// Importe cobrado de más (o de menos, si es negativo)
+    pub fn diferencia(&self) -> Importe {
+        redondea_dos_decimales(self.recibo.as_ref().map_or(Importe::ZERO, |x| x.importe)
+            - self.cuota.as_ref().map_or(Importe::ZERO, |x| x.cuota_total))
+    }
+    /// Intereses cobrados de más (o de menos, si es negativo)
+    pub fn diferencia_intereses(&self) -> Importe {
+        redondea_dos_decimales(self.recibo.as_ref().map_or(Importe::ZERO, |x| x.intereses)
+            - self.cuota.as_ref().map_or(Importe::ZERO, |x| x.cuota_interes))
+    }
+    /// Capital amortizado de más (o de menos, si es negativo)
+    pub fn diferencia_capital(&self) -> Importe {
+        redondea_dos_decimales(self.recibo.as_ref().map_or(Importe::ZERO, |x| x.capital)
+            - self.cuota.as_ref().map_or(Importe::ZERO, |x| x.cuota_capital))
+    }
+    /// Indica si el tipo aplicado por la entidad, si figura en el
+    /// recibo, es distinto del calculado
+    pub fn difiere_tipo(&self) -> bool {
+        match (&self.recibo, &self.cuota) {
+            (Some(recibo), Some(cuota)) => recibo.tipo
+                .is_some_and(|tipo| redondea_cinco_decimales(tipo - cuota.i) != 0.0),
+            _ => false,
+        }
+    }
+    /// Indica si hay alguna discrepancia entre el recibo y la cuota
+    pub fn tiene_discrepancias(&self) -> bool {
+        !self.observaciones().is_empty()
+    }
+    /// Texto con las discrepancias de la línea
+    pub fn observaciones(&self) -> String {
+        let mut obs: Vec<&str> = Vec::new();
+        if self.cuota.is_none() {
+            obs.push("Recibo sin cuota");
+        }
+        if self.recibo.is_none() {
+            obs.push("Cuota sin recibo");
+        }
+        if self.recibo.is_some() && self.cuota.is_some() {
+            if self.difiere_tipo() {
+                obs.push("Tipo distinto");
+            }
+            if !self.diferencia_intereses().is_zero() {
+                obs.push("Intereses distintos");
+            }
+            if !self.diferencia_capital().is_zero() {
+                obs.push("Capital distinto");
+            }
+        }
+        obs.join(", ")
+    }
+    pub fn to_csv_string(&self, acumulado: Importe) -> String {
+        let fecha = |x: Option<Date<Utc>>| x.map_or(String::new(), |f| format!("{}/{}/{}", f.day(), f.month(), f.year()));
+        let tipo = |x: Option<f64>| x.map_or(String::new(), |t| t.to_string().replace(".", ","));
+        let importe = |x: Option<Importe>| x.map_or(String::new(), |i| i.to_string().replace(".", ","));
+        let recibo = self.recibo.as_ref();
+        let cuota = self.cuota.as_ref();
+        format!("{}; {}; {}; {}; {}; {}; {}; {}; {}; {}; {}; {}",
+            fecha(recibo.map(|x| x.fecha)), fecha(cuota.map(|x| x.fecha)),
+            tipo(recibo.and_then(|x| x.tipo)), tipo(cuota.map(|x| x.i)),
+            importe(recibo.map(|x| x.importe)), importe(cuota.map(|x| x.cuota_total)),
+            importe(recibo.map(|x| x.intereses)), importe(cuota.map(|x| x.cuota_interes)),
+            importe(recibo.map(|x| x.capital)), importe(cuota.map(|x| x.cuota_capital)),
+            acumulado.to_string().replace(".", ","), self.observaciones())
+    }
+}
+
+/// Conciliación de los recibos cobrados por la entidad con las cuotas
+/// calculadas
+pub struct Conciliacion {
+    pub lineas: Vec<LineaConciliacion>,
+}
+
+impl Conciliacion {
+    /// Empareja cada recibo con la cuota mensual de la tabla que vence en
+    /// la fecha más próxima, con un desfase máximo de `MAX_DIAS_DESFASE`
+    /// días. Las cuotas que vencen entre el primer y el último recibo y no
+    /// tienen recibo también se incluyen
+    pub fn concilia(tabla: &TablaAmortizacion, recibos: &[ReciboBanco]) -> Self {
+        let mut recibos: Vec<&ReciboBanco> = recibos.iter().collect();
+        recibos.sort_by_key(|x| x.fecha);
+        let cuotas: Vec<&Cuota> = tabla.cuotas.iter().filter(|x| !x.amortizacion_anticipada).collect();
+        let mut usadas = vec![false; cuotas.len()];
+        let mut lineas: Vec<LineaConciliacion> = Vec::new();
+        for recibo in recibos.iter() {
+            let pareja = cuotas.iter().enumerate()
+                .filter(|(i, cuota)| !usadas[*i] && (cuota.fecha - recibo.fecha).num_days().abs() <= MAX_DIAS_DESFASE)
+                .min_by_key(|(_, cuota)| (cuota.fecha - recibo.fecha).num_days().abs())
+                .map(|(i, _)| i);
+            if let Some(i) = pareja {
+                usadas[i] = true;
+            }
+            lineas.push(LineaConciliacion { recibo: Some((*recibo).clone()), cuota: pareja.map(|i| cuotas[i].clone()) });
+        }
+        if let (Some(primero), Some(ultimo)) = (recibos.first(), recibos.last()) {
+            for (i, cuota) in cuotas.iter().enumerate() {
+                if !usadas[i] && cuota.fecha >= primero.fecha && cuota.fecha <= ultimo.fecha {
+                    lineas.push(LineaConciliacion { recibo: None, cuota: Some((*cuota).clone()) });
+                }
+            }
+        }
+        lineas.sort_by_key(|x| x.fecha());
+        Conciliacion { lineas }
+    }
+    /// Total cobrado de más (o de menos, si es negativo) en todos los recibos
+    pub fn total_diferencias(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.diferencia()).sum())
+    }
+    /// Líneas con alguna discrepancia
+    pub fn lineas_con_discrepancias(&self) -> Vec<&LineaConciliacion> {
+        self.lineas.iter().filter(|x| x.tiene_discrepancias()).collect()
+    }
+    /// Muestra las líneas con discrepancias, con la diferencia acumulada
+    /// de todas las líneas hasta cada una de ellas
+    pub fn disp(&self) {
+        let mut acumulado = Importe::ZERO;
+        for linea in self.lineas.iter() {
+            acumulado = redondea_dos_decimales(acumulado + linea.diferencia());
+            if linea.tiene_discrepancias() {
+                println!("{}", linea.to_csv_string(acumulado));
+            }
+        }
+        println!("{} recibos con discrepancias; total cobrado de más: {}",
+            self.lineas_con_discrepancias().len(), self.total_diferencias());
+    }
+    /// Escribe el informe de discrepancias en <nombre>.txt, con la
+    /// diferencia acumulada hasta cada línea
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        writeln!(&file, "Fecha_recibo; Fecha_cuota; i_cobrado; i_calculado; Cobrado; Calculado; Intereses_cobrados; Intereses_calculados; Capital_cobrado; Capital_calculado; Diferencia_acumulada; Observaciones")?;
+        let mut acumulado = Importe::ZERO;
+        for linea in self.lineas.iter() {
+            acumulado = redondea_dos_decimales(acumulado + linea.diferencia());
+            writeln!(&file, "{}", linea.to_csv_string(acumulado))?;
+        }
+        writeln!(&file, "Total cobrado de más; {}", self.total_diferencias().to_string().replace(".", ","))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_carga_recibos_csv_str() {
+        let csv = "Fecha;Importe;Intereses;Capital;Tipo\n17/04/2004;444,12;280,47;163,65;4,000\n\"17/05/2004\";\"1.444,12\";\"279,92\";\"1.164,20\";\n";
+        let recibos = carga_recibos_csv_str(csv).unwrap();
+        assert_eq!(2, recibos.len());
+        assert_eq!(Utc.ymd(2004, 4, 17), recibos[0].fecha);
+        assert_eq!(dec!(444.12), recibos[0].importe);
+        assert_eq!(Some(0.04), recibos[0].tipo);
+        assert_eq!(dec!(1444.12), recibos[1].importe);
+        assert_eq!(None, recibos[1].tipo);
+        let recibos = carga_recibos_csv_str("fecha,importe,intereses,capital\n2004-04-17,444.12,280.47,163.65\n").unwrap();
+        assert_eq!(dec!(163.65), recibos[0].capital);
+        // Tras el primer recibo, una fila que no lo es se rechaza indicando su línea
+        let csv = "Fecha;Importe;Intereses;Capital\n17/04/2004;444,12;280,47;163,65\n17/05/2004;444,12;X;163,65\n";
+        let error = carga_recibos_csv_str(csv).unwrap_err();
+        assert!(error.to_string().contains("línea 3"));
+        let csv = "17/04/2004;444,12;280,47;163,65;4\n\n17/05/2004;444,12;279,92;164,20;4,x\n";
+        assert!(carga_recibos_csv_str(csv).unwrap_err().to_string().contains("línea 3"));
+    }
+    #[test]
+    fn test_parse_fecha_recibo() {
+        assert_eq!(Some(Utc.ymd(2004, 4, 17)), parse_fecha_recibo("17-04-2004"));
+        assert_eq!(Some(Utc.ymd(2004, 4, 17)), parse_fecha_recibo("2004-04-17"));
+        assert_eq!(Some(Utc.ymd(2004, 4, 17)), parse_fecha_recibo("38094"));
+        assert_eq!(None, parse_fecha_recibo("31/02/2004"));
+        assert_eq!(None, parse_fecha_recibo("Fecha"));
+    }
+    #[test]
+    fn test_carga_recibos_xlsx() {
+        let mut book = umya_spreadsheet::new_file();
+        let worksheet = book.get_sheet_mut(&0).unwrap();
+        for (celda, valor) in [("A1", "Fecha"), ("B1", "Importe"), ("A2", "17/04/2004"), ("B2", "444.12"),
+                ("C2", "280.47"), ("D2", "163.65"), ("E2", "4")] {
+            worksheet.get_cell_mut(celda).set_value(valor);
+        }
+        let path = std::env::temp_dir().join("hipotecas_test_recibos.xlsx");
+        umya_spreadsheet::writer::xlsx::write(&book, &path).unwrap();
+        let recibos = carga_recibos(&path).unwrap();
+        assert_eq!(1, recibos.len());
+        assert_eq!(dec!(280.47), recibos[0].intereses);
+        assert_eq!(Some(0.04), recibos[0].tipo);
+        assert!(carga_recibos(Path::new("no_existe.csv")).is_err());
+    }
+}
@@ -0,0 +1,59 @@
+use chrono::prelude::*;
+use super::lib::*;
+use super::importe::*;
+use super::comparacion_tablas::ComparacionTablas;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Cuotas en las que se mantiene congelado el importe
+pub const MESES_CONGELACION: i32 = 12;
+/// Reducción del tipo de interés que devenga el capital aplazado
+pub const REDUCCION_TIPO_APLAZADO: f64 = 0.005;
+/// Ampliación máxima del plazo, en meses
+pub const MAX_MESES_AMPLIACION: i32 = 84;
+
+/// Simulación de las medidas del Código de Buenas Prácticas de 2022
+/// (RDL 19/2022): la cuota se congela durante doce meses en el importe
+/// anterior y el capital que deja de amortizarse se aplaza, devengando
+/// el tipo del préstamo menos medio punto, hasta el final de la
+/// congelación. El plazo puede ampliarse hasta siete años
+pub struct CongelacionCuota {
+    /// Fecha desde la que se aplican las medidas
+    pub fecha: Date<Utc>,
+    /// Meses en que se amplía el plazo
+    pub meses_ampliacion: i32,
+    /// Importe al que se congela la cuota
+    pub cuota_congelada: Importe,
+    /// Capital aplazado durante la congelación, con sus intereses, que
+    /// se suma al capital pendiente al terminar
+    pub capital_aplazado: Importe,
+    /// Total pendiente de pagar desde la fecha sin las medidas
+    pub total_anterior: Importe,
+    /// Tabla de amortización con las medidas
+    pub tabla: TablaAmortizacion,
+    /// Comparación de cada cuota sin y con las medidas
+    pub comparacion: ComparacionTablas,
+}
+
+impl CongelacionCuota {
+    /// Total pendiente de pagar desde la fecha con las medidas
+    pub fn total_novado(&self) -> Importe {
+        redondea_dos_decimales(self.tabla.cuotas.iter()
+            .filter(|x| x.fecha >= self.fecha).map(|x| x.cuota_total).sum())
+    }
+    /// Coste total de las medidas: lo que se paga de más hasta el final
+    pub fn coste_total(&self) -> Importe {
+        redondea_dos_decimales(self.total_novado() - self.total_anterior)
+    }
+    pub fn disp(&self) {
+        println!("Congelación de la cuota en {:.2} desde {} y ampliación del plazo en {} meses",
+            self.cuota_congelada, self.fecha, self.meses_ampliacion);
+        println!("Capital aplazado: {:.2} Total pendiente: {:.2} Total con las medidas: {:.2} Coste: {:.2}",
+            self.capital_aplazado, self.total_anterior, self.total_novado(), self.coste_total());
+    }
+    /// Escribe la tabla con las medidas en <nombre>_congelacion.txt y
+    /// las cuotas sin y con las medidas en <nombre>_diferencias_congelacion.txt
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        self.tabla.print(&(String::from(nombre) + "_congelacion"))?;
+        self.comparacion.print(&(String::from(nombre) + "_diferencias_congelacion"))
+    }
+}
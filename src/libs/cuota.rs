@@ -1,5 +1,7 @@
 use chrono::prelude::*;
+use std::fmt;
 use super::lib::*;
+use super::importe::Importe;
 
 // Las cuotas son cada uno de los pagos mensuales de la hipoteca
 pub struct Cuota {
@@ -15,43 +17,81 @@ pub struct Cuota {
     /// Meses restantes, incluyendo este
     pub meses_restantes_antes: i32,
     /// Capital pendiente de amortizar antes de pagar esta cuota 
-    pub cap_pendiente_antes: f64,
+    pub cap_pendiente_antes: Importe,
     /// Importe de la cuota a pagar 
-    pub cuota_total: f64, 
+    pub cuota_total: Importe, 
     /// Parte de capital de la cuota
-    pub cuota_capital: f64, 
+    pub cuota_capital: Importe, 
     /// Parte de intereses de la cuota
-    pub cuota_interes: f64, 
+    pub cuota_interes: Importe, 
+    /// Indica si en esta cuota surte efecto una novación
+    pub novacion: bool,
+    /// Indica si la fila corresponde a una amortización anticipada
+    /// en lugar de a una cuota mensual
+    pub amortizacion_anticipada: bool,
+    /// Comisión cobrada por la amortización anticipada. No forma
+    /// parte del importe de la cuota
+    pub comision: Importe,
+    /// Indica si en la revisión no había datos del índice y se
+    /// mantuvo el tipo anterior
+    pub sin_datos_indice: bool,
+    /// Indica si la cuota está en un periodo de carencia
+    pub carencia: bool,
 }
 impl Clone for Cuota {
     fn clone(&self) -> Self {
         Cuota{fecha: self.fecha, i: self.i, meses_restantes_antes: self.meses_restantes_antes,
             cap_pendiente_antes: self.cap_pendiente_antes, cuota_total: self.cuota_total,
-            cuota_capital: self.cuota_capital, cuota_interes: self.cuota_interes}
+            cuota_capital: self.cuota_capital, cuota_interes: self.cuota_interes,
+            novacion: self.novacion, amortizacion_anticipada: self.amortizacion_anticipada,
+            comision: self.comision, sin_datos_indice: self.sin_datos_indice,
+            carencia: self.carencia}
     }
 }
 impl Cuota {
     pub fn new(fecha: Date<Utc>,i:f64, meses_restantes_antes: i32,
-        cap_pendiente_antes:f64, cuota_total: f64, cuota_capital: f64, 
-        cuota_interes: f64) -> Self {
+        cap_pendiente_antes: Importe, cuota_total: Importe, cuota_capital: Importe, 
+        cuota_interes: Importe) -> Self {
         Cuota { fecha, i, meses_restantes_antes, cap_pendiente_antes,
-             cuota_total, cuota_capital, cuota_interes}
+             cuota_total, cuota_capital, cuota_interes, novacion: false,
+             amortizacion_anticipada: false, comision: Importe::ZERO, sin_datos_indice: false,
+             carencia: false}
+    }
+    /// Crea la fila correspondiente a una amortización anticipada de
+    /// capital, con los intereses devengados por el capital amortizado
+    /// desde el último vencimiento y la comisión como importe separado
+    pub fn new_amortizacion_anticipada(fecha: Date<Utc>, i: f64, meses_restantes_antes: i32,
+        cap_pendiente_antes: Importe, importe: Importe, intereses: Importe, comision: Importe) -> Self {
+        let mut cuota = Cuota::new(fecha, i, meses_restantes_antes, cap_pendiente_antes,
+            importe + intereses, importe, intereses);
+        cuota.amortizacion_anticipada = true;
+        cuota.comision = comision;
+        cuota
+    }
+    /// Crea una cuota de un periodo de carencia. En la carencia de capital
+    /// se pagan los intereses; en la total no se paga nada y los intereses
+    /// se capitalizan, como una amortización de capital negativa
+    pub fn new_carencia(fecha: Date<Utc>, i: f64, meses_restantes_antes: i32,
+        cap_pendiente_antes: Importe, intereses: Importe, capitaliza_intereses: bool) -> Self {
+        let mut cuota = if capitaliza_intereses {
+            Cuota::new(fecha, i, meses_restantes_antes, cap_pendiente_antes,
+                Importe::ZERO, -intereses, intereses)
+        } else {
+            Cuota::new(fecha, i, meses_restantes_antes, cap_pendiente_antes,
+                intereses, Importe::ZERO, intereses)
+        };
+        cuota.carencia = true;
+        cuota
     }
     /// Calcula el capital pendient de amortización
     /// despues de pagar esta cuota
-    pub fn cap_pendiente_despues(&self) -> f64 {
+    pub fn cap_pendiente_despues(&self) -> Importe {
         redondea_dos_decimales(self.cap_pendiente_antes - 
             self.cuota_capital)
     }    
     /// Muestra en pantalla la instancia de Cuota
     pub fn disp(&self) {
-        println!("{}", self.to_string());
-    }
-    pub fn to_string(&self) -> String {
-        format!("{}/{}/{} {:.5} {} {:.2} {:.2} {:.2} {:.2} {:.2}", self.fecha.day(), self.fecha.month(),
-            self.fecha.year(), self.i, self.meses_restantes_antes,
-            self.cap_pendiente_antes, self.cuota_total, self.cuota_capital, self.cuota_interes, 
-            self.cap_pendiente_despues())
+        println!("{}", self);
     }
     pub fn to_csv_string(&self) -> String {
         let i = self.i.to_string().replace(".", ",");
@@ -61,25 +101,60 @@ impl Cuota {
         let cuota_capital = self.cuota_capital.to_string().replace(".", ",");
         let cuota_interes = self.cuota_interes.to_string().replace(".", ",");
         let cap_pdte_despues = self.cap_pendiente_despues().to_string().replace(".", ",");
-        format!("{}/{}/{}; {}; {}; {}; {}; {}; {}; {}", 
+        let comision = self.comision.to_string().replace(".", ",");
+        format!("{}/{}/{}; {}; {}; {}; {}; {}; {}; {}; {}; {}", 
             self.fecha.day(), self.fecha.month(), self.fecha.year(), 
             i, meses, cap_pdte_antes, cuota_total, cuota_capital, 
-            cuota_interes, cap_pdte_despues)
+            cuota_interes, cap_pdte_despues, comision, self.observaciones())
+    }
+    /// Texto con las circunstancias especiales de la cuota
+    pub fn observaciones(&self) -> String {
+        let mut obs: Vec<&str> = Vec::new();
+        if self.novacion {
+            obs.push("Novacion");
+        }
+        if self.amortizacion_anticipada {
+            obs.push("Amortizacion anticipada");
+        }
+        if self.sin_datos_indice {
+            obs.push("Sin datos del indice");
+        }
+        if self.carencia {
+            obs.push("Carencia");
+        }
+        obs.join(", ")
+    }
+}
+impl fmt::Display for Cuota {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}/{} {:.5} {} {:.2} {:.2} {:.2} {:.2} {:.2}", self.fecha.day(), self.fecha.month(),
+            self.fecha.year(), self.i, self.meses_restantes_antes,
+            self.cap_pendiente_antes, self.cuota_total, self.cuota_capital, self.cuota_interes, 
+            self.cap_pendiente_despues())?;
+        if !self.comision.is_zero() {
+            write!(f, " comision {:.2}", self.comision)?;
+        }
+        let obs = self.observaciones();
+        if !obs.is_empty() {
+            write!(f, " ({})", obs)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::dec;
     #[test]
     fn test_new() {
         let cuota = Cuota::new(
             Utc.ymd(2004, 4, 17),0.04,
-            300, 84140.0, 444.12, 163.65, 280.47); 
+            300, dec!(84140), dec!(444.12), dec!(163.65), dec!(280.47)); 
         assert_eq!(2004, cuota.fecha.year());
         assert_eq!(4, cuota.fecha.month());
         assert_eq!(17, cuota.fecha.day());
-        assert_eq!(84140.0, cuota.cap_pendiente_antes);
+        assert_eq!(dec!(84140), cuota.cap_pendiente_antes);
         assert_eq!(0.04, cuota.i);
         assert_eq!(300, cuota.meses_restantes_antes);
         
@@ -88,14 +163,24 @@ mod tests {
     fn test_cuota_pendiente_despues() {
         let cuota = Cuota::new(
             Utc.ymd(2004, 4, 17),0.04,
-            300, 84140.0, 444.12, 163.65, 280.47); 
-        assert_eq!(83976.35, cuota.cap_pendiente_despues());
+            300, dec!(84140), dec!(444.12), dec!(163.65), dec!(280.47)); 
+        assert_eq!(dec!(83976.35), cuota.cap_pendiente_despues());
+    }
+    #[test]
+    fn test_new_carencia() {
+        let cuota = Cuota::new_carencia(Utc.ymd(2004, 4, 17), 0.04, 300, dec!(84140), dec!(280.47), true);
+        assert_eq!(dec!(0), cuota.cuota_total);
+        assert_eq!(dec!(84420.47), cuota.cap_pendiente_despues());
+        assert_eq!("Carencia", cuota.observaciones());
+        let cuota = Cuota::new_carencia(Utc.ymd(2004, 4, 17), 0.04, 300, dec!(84140), dec!(280.47), false);
+        assert_eq!(dec!(280.47), cuota.cuota_total);
+        assert_eq!(dec!(84140), cuota.cap_pendiente_despues());
     }
     #[test]
     fn test_disp() {
         let cuota = Cuota::new(
             Utc.ymd(2004, 4, 17),0.04,
-            300, 84140.0, 444.12, 163.65, 280.47); 
+            300, dec!(84140), dec!(444.12), dec!(163.65), dec!(280.47)); 
         cuota.disp();
     }
     #[test]
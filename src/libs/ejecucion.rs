@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+
+/// Límite de las costas de la ejecución sobre la cantidad reclamada
+/// (art. 575.1 bis LEC)
+pub const LIMITE_COSTAS: f64 = 0.30;
+
+/// Responsabilidad hipotecaria pactada en la escritura: importes máximos
+/// de los que responde la finca por cada concepto
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct ResponsabilidadHipotecaria {
+    pub capital: Importe,
+    pub intereses_ordinarios: Importe,
+    pub intereses_demora: Importe,
+    /// Costas y gastos
+    pub costas: Importe,
+}
+
+impl ResponsabilidadHipotecaria {
+    pub fn new(capital: Importe, intereses_ordinarios: Importe, intereses_demora: Importe,
+            costas: Importe) -> Self {
+        ResponsabilidadHipotecaria { capital, intereses_ordinarios, intereses_demora, costas }
+    }
+}
+
+/// Concepto de la liquidación con lo reclamado y el límite de la
+/// responsabilidad hipotecaria
+pub struct PartidaEjecucion {
+    pub concepto: &'static str,
+    pub reclamado: Importe,
+    /// Responsabilidad hipotecaria por el concepto, si se conoce
+    pub limite: Option<Importe>,
+}
+impl PartidaEjecucion {
+    /// Parte de lo reclamado garantizada con la hipoteca
+    pub fn garantizado(&self) -> Importe {
+        self.limite.map_or(self.reclamado, |limite| self.reclamado.min(limite))
+    }
+    /// Parte de lo reclamado que excede de la responsabilidad hipotecaria
+    pub fn exceso(&self) -> Importe {
+        redondea_dos_decimales(self.reclamado - self.garantizado())
+    }
+    pub fn to_csv_string(&self) -> String {
+        format!("{}; {}; {}; {}; {}", self.concepto,
+            self.reclamado.to_string().replace(".", ","),
+            self.limite.map_or(String::new(), |x| x.to_string().replace(".", ",")),
+            self.garantizado().to_string().replace(".", ","),
+            self.exceso().to_string().replace(".", ","))
+    }
+}
+
+/// Liquidación para la ejecución hipotecaria a la fecha de resolución:
+/// capital pendiente, cuotas vencidas, intereses ordinarios y de demora,
+/// gastos y límite de costas, con lo garantizado por la responsabilidad
+/// hipotecaria en cada concepto
+pub struct LiquidacionEjecucion {
+    /// Fecha de la liquidación
+    pub fecha: Date<Utc>,
+    /// Capital que vence anticipadamente con la resolución
+    pub capital_pendiente: Importe,
+    /// Capital de las cuotas vencidas y no pagadas antes de la resolución
+    pub capital_cuotas_vencidas: Importe,
+    pub intereses_ordinarios: Importe,
+    pub intereses_demora: Importe,
+    /// Gastos repercutidos pendientes
+    pub gastos: Importe,
+    pub responsabilidad: Option<ResponsabilidadHipotecaria>,
+}
+
+impl LiquidacionEjecucion {
+    pub fn total_capital(&self) -> Importe {
+        redondea_dos_decimales(self.capital_pendiente + self.capital_cuotas_vencidas)
+    }
+    /// Cantidad reclamada en la demanda ejecutiva
+    pub fn cantidad_reclamada(&self) -> Importe {
+        redondea_dos_decimales(self.total_capital() + self.intereses_ordinarios
+            + self.intereses_demora + self.gastos)
+    }
+    /// Importe máximo de las costas: el 30% de lo reclamado y, si es
+    /// menor, lo que queda de la responsabilidad hipotecaria por costas y
+    /// gastos después de garantizar los gastos reclamados
+    pub fn limite_costas(&self) -> Importe {
+        let limite = redondea_dos_decimales(self.cantidad_reclamada() * tipo(LIMITE_COSTAS));
+        self.responsabilidad.map_or(limite, |r| 
+            limite.min(redondea_dos_decimales(r.costas - self.gastos.min(r.costas))))
+    }
+    /// Conceptos de la liquidación frente a la responsabilidad hipotecaria
+    pub fn partidas(&self) -> Vec<PartidaEjecucion> {
+        let r = self.responsabilidad;
+        vec![
+            PartidaEjecucion { concepto: "Capital", reclamado: self.total_capital(),
+                limite: r.map(|x| x.capital) },
+            PartidaEjecucion { concepto: "Intereses ordinarios", reclamado: self.intereses_ordinarios,
+                limite: r.map(|x| x.intereses_ordinarios) },
+            PartidaEjecucion { concepto: "Intereses de demora", reclamado: self.intereses_demora,
+                limite: r.map(|x| x.intereses_demora) },
+            PartidaEjecucion { concepto: "Gastos", reclamado: self.gastos,
+                limite: r.map(|x| x.costas) },
+        ]
+    }
+    /// Parte de lo reclamado garantizada con la hipoteca
+    pub fn total_garantizado(&self) -> Importe {
+        redondea_dos_decimales(self.partidas().iter().map(|x| x.garantizado()).sum())
+    }
+    /// Parte de lo reclamado que solo puede exigirse al deudor personalmente
+    pub fn total_exceso(&self) -> Importe {
+        redondea_dos_decimales(self.partidas().iter().map(|x| x.exceso()).sum())
+    }
+    pub fn disp(&self) {
+        for partida in self.partidas() {
+            println!("{}", partida.to_csv_string());
+        }
+        println!("Reclamado: {:.2} Garantizado: {:.2} Exceso: {:.2} Límite de costas: {:.2}",
+            self.cantidad_reclamada(), self.total_garantizado(), self.total_exceso(), self.limite_costas());
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let importe = |i: Importe| i.to_string().replace(".", ",");
+        writeln!(&file, "Liquidación a {}/{}/{}", self.fecha.day(), self.fecha.month(), self.fecha.year())?;
+        writeln!(&file, "Capital pendiente vencido con la resolución; {}", importe(self.capital_pendiente))?;
+        writeln!(&file, "Capital de las cuotas vencidas; {}", importe(self.capital_cuotas_vencidas))?;
+        writeln!(&file, "Concepto; Reclamado; Responsabilidad; Garantizado; Exceso")?;
+        for partida in self.partidas() {
+            writeln!(&file, "{}", partida.to_csv_string())?;
+        }
+        writeln!(&file, "Total; {}; ; {}; {}", importe(self.cantidad_reclamada()),
+            importe(self.total_garantizado()), importe(self.total_exceso()))?;
+        writeln!(&file, "Límite de costas; {}", importe(self.limite_costas()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_costas_y_gastos() {
+        let l = LiquidacionEjecucion { fecha: Utc.ymd(2022, 8, 5), capital_pendiente: dec!(50000),
+            capital_cuotas_vencidas: dec!(10000), intereses_ordinarios: dec!(2000),
+            intereses_demora: dec!(3000), gastos: dec!(3000),
+            responsabilidad: Some(ResponsabilidadHipotecaria::new(dec!(84140), dec!(10000),
+                dec!(5000), dec!(8000))) };
+        // Los gastos consumen parte de la responsabilidad por costas y gastos
+        assert_eq!(dec!(0), l.total_exceso());
+        assert_eq!(dec!(5000), l.limite_costas());
+        let l = LiquidacionEjecucion { gastos: dec!(9000), ..l };
+        assert_eq!(dec!(1000), l.total_exceso());
+        assert_eq!(dec!(0), l.limite_costas());
+    }
+    #[test]
+    fn test_partidas() {
+        let mut l = LiquidacionEjecucion { fecha: Utc.ymd(2022, 8, 5), capital_pendiente: dec!(50000),
+            capital_cuotas_vencidas: dec!(10000), intereses_ordinarios: dec!(2000),
+            intereses_demora: dec!(3000), gastos: dec!(0), responsabilidad: None };
+        assert_eq!(dec!(65000), l.cantidad_reclamada());
+        assert_eq!(dec!(19500), l.limite_costas());
+        assert_eq!(dec!(0), l.total_exceso());
+        l.responsabilidad = Some(ResponsabilidadHipotecaria::new(dec!(84140), dec!(10000),
+            dec!(2500), dec!(8000)));
+        assert_eq!(dec!(8000), l.limite_costas());
+        assert_eq!(dec!(500), l.total_exceso());
+        assert_eq!(dec!(64500), l.total_garantizado());
+    }
+}
@@ -0,0 +1,69 @@
+use chrono::prelude::*;
+use std::fmt;
+use super::indice_referencia::IndiceReferencia;
+
+/// Errores de los cálculos de la hipoteca y de la lectura de sus datos.
+/// Cada error indica el campo, la celda o el dato que lo produjo
+#[derive(Clone, PartialEq, Debug)]
+pub enum HipotecaError {
+    /// Un dato de entrada no es válido
+    EntradaInvalida { campo: String, motivo: String },
+    /// No hay datos de un índice de referencia para el mes de una revisión
+    SinDatosIndice { indice: IndiceReferencia, agno: i32, mes: u32, disponibles: String },
+    /// No hay cuotas vencidas entre la fecha de impago y la de resolución
+    SinCuotasImpago { fecha_impago: Date<Utc>, fecha_resolucion: Date<Utc> },
+    /// No se puede leer un dato de la hoja de cálculo
+    HojaCalculo { celda: String, motivo: String },
+    /// No se puede leer un fichero
+    Fichero { ruta: String, motivo: String },
+}
+
+impl HipotecaError {
+    pub fn entrada_invalida(campo: &str, motivo: &str) -> Self {
+        HipotecaError::EntradaInvalida { campo: campo.to_owned(), motivo: motivo.to_owned() }
+    }
+    pub fn hoja_calculo(celda: &str, motivo: &str) -> Self {
+        HipotecaError::HojaCalculo { celda: celda.to_owned(), motivo: motivo.to_owned() }
+    }
+}
+
+impl fmt::Display for HipotecaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HipotecaError::EntradaInvalida { campo, motivo } =>
+                write!(f, "Dato no válido en {}: {}", campo, motivo),
+            HipotecaError::SinDatosIndice { indice, agno, mes, disponibles } =>
+                write!(f, "No hay datos de {} para {}/{}. Datos disponibles: {}",
+                    indice.nombre(), mes, agno, disponibles),
+            HipotecaError::SinCuotasImpago { fecha_impago, fecha_resolucion } =>
+                write!(f, "No hay cuotas entre la fecha de impago {}/{}/{} y la de resolución {}/{}/{}",
+                    fecha_impago.day(), fecha_impago.month(), fecha_impago.year(),
+                    fecha_resolucion.day(), fecha_resolucion.month(), fecha_resolucion.year()),
+            HipotecaError::HojaCalculo { celda, motivo } =>
+                write!(f, "Error en la celda {} de la hoja de cálculo: {}", celda, motivo),
+            HipotecaError::Fichero { ruta, motivo } =>
+                write!(f, "No se pudo leer el fichero {}: {}", ruta, motivo),
+        }
+    }
+}
+
+impl std::error::Error for HipotecaError {}
+
+pub type Result<T> = std::result::Result<T, HipotecaError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mensajes() {
+        assert_eq!("Dato no válido en meses: debe ser mayor que cero",
+            HipotecaError::entrada_invalida("meses", "debe ser mayor que cero").to_string());
+        assert_eq!("Error en la celda C8 de la hoja de cálculo: no es un número",
+            HipotecaError::hoja_calculo("C8", "no es un número").to_string());
+        let e = HipotecaError::SinCuotasImpago { fecha_impago: Utc.ymd(2018, 5, 17),
+            fecha_resolucion: Utc.ymd(2018, 5, 20) };
+        assert_eq!("No hay cuotas entre la fecha de impago 17/5/2018 y la de resolución 20/5/2018",
+            e.to_string());
+    }
+}
@@ -1,13 +1,219 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::path::Path;
 use chrono::prelude::*;
 use super::lib::*;
+use super::error::*;
+use super::indice_referencia::*;
+
+/// Series de valores mensuales de los índices de referencia. De cada
+/// índice puede haber una serie de medias mensuales (el valor oficial
+/// publicado en el BOE) y otra de valores al cierre de cada mes. El
+/// euribor a 12 meses al cierre de mes viene incluido hasta diciembre
+/// de 2021; el resto de datos se cargan desde ficheros CSV o JSON, o
+/// con `set_valores_agno`
+#[derive(Clone)]
 pub struct EuriborData {
-    tabla: HashMap<i32, Vec<f64>>,
+    /// Valores en tanto por ciento de cada índice y serie por (año, mes)
+    series: HashMap<(IndiceReferencia, TipoSerie), BTreeMap<(i32, u32), f64>>,
+    /// Indica si en las revisiones posteriores a la resolución del contrato
+    /// sin datos del índice se proyecta el tipo de la revisión anterior
+    proyeccion: bool,
 }
 
 impl EuriborData {
     pub fn new() -> Self {
-        EuriborData { tabla: Self::read_euribor_table() }
+        let mut ed = EuriborData { series: HashMap::new(), proyeccion: false };
+        for (agno, valores) in Self::read_euribor_table() {
+            for (i, valor) in valores.iter().enumerate() {
+                ed.set_valor_mes_serie(IndiceReferencia::Euribor12Meses, TipoSerie::FinDeMes,
+                    *valor, (i+1) as u32, agno);
+            }
+        }
+        ed
+    }
+    /// Crea las series con la proyección activada: las revisiones
+    /// posteriores a la resolución sin datos del índice mantienen el
+    /// tipo anterior en lugar de devolver un error
+    pub fn con_proyeccion() -> Self {
+        let mut ed = Self::new();
+        ed.set_proyeccion(true);
+        ed
+    }
+    /// Activa o desactiva la proyección del tipo en las revisiones
+    /// posteriores a la resolución sin datos del índice
+    pub fn set_proyeccion(&mut self, proyeccion: bool) {
+        self.proyeccion = proyeccion;
+    }
+    /// Indica si está activada la proyección del tipo en las revisiones
+    /// posteriores a la resolución sin datos del índice
+    pub fn proyeccion(&self) -> bool {
+        self.proyeccion
+    }
+    /// Añade o sustituye las medias mensuales (en tanto por ciento)
+    /// de un índice para un año, empezando por enero
+    pub fn set_valores_agno(&mut self, indice: IndiceReferencia, agno: i32, valores: Vec<f64>) {
+        for (i, valor) in valores.iter().enumerate() {
+            self.set_valor_mes(indice, *valor, (i+1) as u32, agno);
+        }
+    }
+    /// Añade o sustituye la media mensual (en tanto por ciento) de un índice en un mes
+    pub fn set_valor_mes(&mut self, indice: IndiceReferencia, valor: f64, mes: u32, agno: i32) {
+        self.set_valor_mes_serie(indice, TipoSerie::MediaMensual, valor, mes, agno);
+    }
+    /// Añade o sustituye el valor (en tanto por ciento) de una serie de un índice en un mes
+    pub fn set_valor_mes_serie(&mut self, indice: IndiceReferencia, serie: TipoSerie, 
+            valor: f64, mes: u32, agno: i32) {
+        self.series.entry((indice, serie)).or_default().insert((agno, mes), valor);
+    }
+    /// Indica si hay datos cargados para un índice en alguna serie
+    pub fn tiene_datos(&self, indice: IndiceReferencia) -> bool {
+        self.series.iter().any(|(k, v)| k.0 == indice && !v.is_empty())
+    }
+    /// Serie que se usa cuando la regla de referencia no indica ninguna: la
+    /// media mensual publicada si hay algún dato suyo y, si no, el cierre de
+    /// mes. Se elige una sola serie por índice para no mezclar valores de
+    /// ambas en las revisiones de una misma tabla
+    pub fn serie_por_defecto(&self, indice: IndiceReferencia) -> TipoSerie {
+        if self.cobertura_serie(indice, TipoSerie::MediaMensual).is_some() {
+            TipoSerie::MediaMensual
+        } else {
+            TipoSerie::FinDeMes
+        }
+    }
+    /// Primer y último mes, como (año, mes), con datos de un índice en
+    /// cualquiera de sus series
+    pub fn cobertura(&self, indice: IndiceReferencia) -> Option<((i32, u32), (i32, u32))> {
+        let coberturas: Vec<((i32, u32), (i32, u32))> = [TipoSerie::MediaMensual, TipoSerie::FinDeMes]
+            .into_iter().filter_map(|serie| self.cobertura_serie(indice, serie)).collect();
+        let primero = coberturas.iter().map(|x| x.0).min()?;
+        let ultimo = coberturas.iter().map(|x| x.1).max()?;
+        Some((primero, ultimo))
+    }
+    /// Primer y último mes, como (año, mes), con datos de una serie de un índice
+    pub fn cobertura_serie(&self, indice: IndiceReferencia, serie: TipoSerie) -> Option<((i32, u32), (i32, u32))> {
+        let serie = self.series.get(&(indice, serie))?;
+        let primero = *serie.keys().next()?;
+        let ultimo = *serie.keys().next_back()?;
+        Some((primero, ultimo))
+    }
+    /// Indica si hay dato de un índice para el mes anterior a una fecha,
+    /// que es el que se usa en la revisión de esa fecha
+    pub fn cubre_fecha(&self, indice: IndiceReferencia, fecha: Date<Utc>) -> bool {
+        self.cubre_referencia(indice, &ReferenciaRevision::default(), fecha)
+    }
+    /// Indica si hay datos de todos los meses que la regla de referencia
+    /// usa en la revisión de una fecha
+    pub fn cubre_referencia(&self, indice: IndiceReferencia, referencia: &ReferenciaRevision,
+            fecha: Date<Utc>) -> bool {
+        meses_referencia(referencia, fecha).iter()
+            .all(|(agno, mes)| self.valor_mes_serie_exacto(indice, referencia.serie, *mes, *agno).is_some())
+    }
+    /// Texto con el periodo cubierto por cada serie de datos de cada
+    /// índice y los meses que faltan dentro de ese periodo
+    pub fn informe_cobertura(&self) -> String {
+        let mut claves: Vec<&(IndiceReferencia, TipoSerie)> = self.series.keys().collect();
+        claves.sort_by_key(|x| (x.0.nombre(), x.1.nombre()));
+        let mut lineas: Vec<String> = Vec::new();
+        for (indice, serie) in claves {
+            if let Some(((agno_ini, mes_ini), (agno_fin, mes_fin))) = self.cobertura_serie(*indice, *serie) {
+                let meses_periodo = (agno_fin - agno_ini) * 12 + mes_fin as i32 - mes_ini as i32 + 1;
+                let huecos = meses_periodo - self.series[&(*indice, *serie)].len() as i32;
+                let mut linea = format!("{} ({}): de {}/{} a {}/{}", indice.nombre(), serie.nombre(),
+                    mes_ini, agno_ini, mes_fin, agno_fin);
+                if huecos > 0 {
+                    linea += &format!(" ({} meses sin datos)", huecos);
+                }
+                lineas.push(linea);
+            }
+        }
+        lineas.join("\n")
+    }
+    /// Carga la serie de un índice desde un fichero CSV guardado localmente,
+    /// como los de tipos de referencia oficiales del Banco de España. 
+    /// La primera columna es el mes (aaaa-mm, mm/aaaa, dd/mm/aaaa o ENE 2022)
+    /// y la segunda el valor en tanto por ciento. El separador puede ser
+    /// punto y coma (con coma decimal) o coma. Solo se admiten cabeceras al
+    /// principio: una línea posterior sin ese formato es un error. Devuelve
+    /// el número de valores cargados, que son medias mensuales
+    pub fn carga_csv(&mut self, indice: IndiceReferencia, path: &Path) -> io::Result<usize> {
+        self.carga_csv_serie(indice, TipoSerie::MediaMensual, path)
+    }
+    /// Carga una serie de un índice desde un fichero CSV con el formato
+    /// de `carga_csv`, por ejemplo los valores de cierre de mes
+    pub fn carga_csv_serie(&mut self, indice: IndiceReferencia, serie: TipoSerie, 
+            path: &Path) -> io::Result<usize> {
+        let contenido = fs::read_to_string(path)?;
+        self.carga_csv_str(indice, serie, &contenido)
+    }
+    /// Interpreta el contenido de un CSV de `carga_csv`. Las líneas anteriores
+    /// al primer valor se toman como cabeceras y se ignoran, igual que las
+    /// vacías; cualquier otra línea que no sea un valor es un error que indica
+    /// su número de línea, y en ese caso no se carga ningún valor
+    fn carga_csv_str(&mut self, indice: IndiceReferencia, serie: TipoSerie, contenido: &str) -> io::Result<usize> {
+        let mut valores: Vec<(i32, u32, f64)> = Vec::new();
+        for (n, linea) in contenido.lines().enumerate() {
+            if linea.trim().is_empty() {
+                continue;
+            }
+            let separador = if linea.contains(';') { ';' } else { ',' };
+            let campos: Vec<String> = linea.split(separador)
+                .map(|x| x.trim().trim_matches('"').trim().to_owned())
+                .collect();
+            let mes_agno = campos.first().and_then(|x| parse_mes(x));
+            let valor: Option<f64> = campos.get(1)
+                .and_then(|x| x.replace(',', ".").parse().ok()).filter(|x: &f64| x.is_finite());
+            match (mes_agno, valor) {
+                (Some((agno, mes)), Some(valor)) => valores.push((agno, mes, valor)),
+                _ if valores.is_empty() => continue,
+                _ => return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "la línea {} no es un valor de {} (mes y valor en %): {}", n + 1, indice.codigo(), linea.trim()))),
+            }
+        }
+        for (agno, mes, valor) in valores.iter() {
+            self.set_valor_mes_serie(indice, serie, *valor, *mes, *agno);
+        }
+        Ok(valores.len())
+    }
+    /// Carga series de uno o varios índices desde un fichero JSON con
+    /// el formato `{"EURIBOR12M": {"2022": [-0.477, ...], ...}, ...}`,
+    /// con los valores de cada año en tanto por ciento empezando por
+    /// enero. Los meses sin publicar pueden ser `null`. Son medias
+    /// mensuales salvo que el código del índice termine en `_CIERRE`
+    /// (`EURIBOR12M_CIERRE`), que indica valores de cierre de mes.
+    /// Devuelve el número de valores cargados
+    pub fn carga_json(&mut self, path: &Path) -> io::Result<usize> {
+        let contenido = fs::read_to_string(path)?;
+        self.carga_json_str(&contenido)
+    }
+    fn carga_json_str(&mut self, contenido: &str) -> io::Result<usize> {
+        let datos: HashMap<String, HashMap<String, Vec<Option<f64>>>> = serde_json::from_str(contenido)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut cargados: usize = 0;
+        for (clave, agnos) in datos.iter() {
+            let (codigo, serie) = match clave.rsplit_once('_').and_then(|(c, s)| Some((c, TipoSerie::from_codigo(s)?))) {
+                Some((codigo, serie)) => (codigo, serie),
+                None => (clave.as_str(), TipoSerie::MediaMensual),
+            };
+            let indice = IndiceReferencia::from_codigo(codigo).ok_or_else(|| 
+                io::Error::new(io::ErrorKind::InvalidData, format!("Índice desconocido: {}", codigo)))?;
+            for (agno, valores) in agnos.iter() {
+                let agno: i32 = agno.parse().map_err(|_| 
+                    io::Error::new(io::ErrorKind::InvalidData, format!("Año no válido: {}", agno)))?;
+                if valores.len() > 12 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, 
+                        format!("{} valores de {} en {}: un año tiene doce meses", valores.len(), clave, agno)));
+                }
+                for (i, valor) in valores.iter().enumerate() {
+                    if let Some(valor) = valor {
+                        self.set_valor_mes_serie(indice, serie, *valor, (i+1) as u32, agno);
+                        cargados += 1;
+                    }
+                }
+            }
+        }
+        Ok(cargados)
     }
     /// Valores en tanto por ciento del euribor al cierre de los meses de cada año
     fn read_euribor_table() -> HashMap<i32, Vec<f64>> {
@@ -86,43 +292,138 @@ impl EuriborData {
         eu.insert(agno, v);
         eu
     } 
-    pub fn last_year(&self) -> i32 {
-        let last_year: i32 = *self.tabla.keys().into_iter().max().unwrap();
-        last_year
+    pub fn last_year(&self) -> Option<i32> {
+        self.last_year_indice(IndiceReferencia::Euribor12Meses)
+    }
+    /// Último año con datos de un índice, si los hay
+    pub fn last_year_indice(&self, indice: IndiceReferencia) -> Option<i32> {
+        self.cobertura(indice).map(|(_, (last_year, _))| last_year)
     }
     /// Devuelve el euribor correspondiente a un mes.
-    /// Si el mes es posterior al último de los almacenados en la tabla de datos,
-    /// devuelve el correspondiente al mes 12 del último año de la tabla de datos
-    pub fn euribor_mes(&self, mes:u32, agno: i32) -> f64 {
-        let mut agno_posible = agno;
-        let mut mes_posible = mes;
-        let agno_max: i32= self.last_year();
-        if agno >  agno_max {
-            agno_posible = agno_max;
-            mes_posible = 12;
+    /// Si no hay datos de ese mes se produce un error
+    pub fn euribor_mes(&self, mes:u32, agno: i32) -> Result<f64> {
+        self.valor_mes(IndiceReferencia::Euribor12Meses, mes, agno)
+    }
+    /// Devuelve el valor de un índice correspondiente a un mes.
+    /// Si no hay datos de ese mes se produce un error
+    pub fn valor_mes(&self, indice: IndiceReferencia, mes:u32, agno: i32) -> Result<f64> {
+        self.valor_mes_exacto(indice, mes, agno).ok_or_else(|| HipotecaError::SinDatosIndice {
+            indice, agno, mes, disponibles: self.informe_cobertura() })
+    }
+    /// Devuelve el valor de una serie de un índice correspondiente a un
+    /// mes. Sin serie se toma la de `serie_por_defecto`. Si no hay datos
+    /// de ese mes se produce un error
+    pub fn valor_mes_serie(&self, indice: IndiceReferencia, serie: Option<TipoSerie>, 
+            mes:u32, agno: i32) -> Result<f64> {
+        self.valor_mes_serie_exacto(indice, serie, mes, agno).ok_or_else(|| HipotecaError::SinDatosIndice {
+            indice, agno, mes, disponibles: self.informe_cobertura() })
+    }
+    /// Devuelve el valor de un índice en un mes sólo si está en los datos
+    fn valor_mes_exacto(&self, indice: IndiceReferencia, mes:u32, agno: i32) -> Option<f64> {
+        self.valor_mes_serie_exacto(indice, None, mes, agno)
+    }
+    /// Devuelve el valor de una serie de un índice en un mes sólo si está en los datos
+    fn valor_mes_serie_exacto(&self, indice: IndiceReferencia, serie: Option<TipoSerie>, 
+            mes:u32, agno: i32) -> Option<f64> {
+        let valor_serie = |serie: TipoSerie| self.series.get(&(indice, serie))?.get(&(agno, mes)).copied();
+        let valor = match serie {
+            Some(serie) => valor_serie(serie)?,
+            None => valor_serie(self.serie_por_defecto(indice))?,
+        };
+        Some(redondea_cinco_decimales(valor/100.0))
+    }
+    /// Devuelve el euribor correspondiente al mes anterior
+    pub fn euribor_fecha(&self, fecha: Date<Utc>) -> Result<f64> {
+        self.valor_fecha(IndiceReferencia::Euribor12Meses, fecha)
+    }
+    /// Devuelve el valor de un índice correspondiente al mes anterior
+    pub fn valor_fecha(&self, indice: IndiceReferencia, fecha: Date<Utc>) -> Result<f64> {
+        self.valor_referencia(indice, &ReferenciaRevision::default(), fecha)
+    }
+    /// Devuelve el valor de un índice en la revisión de una fecha según
+    /// la regla de referencia pactada: el valor del mes que corresponda
+    /// o la media de los valores de varios meses
+    pub fn valor_referencia(&self, indice: IndiceReferencia, referencia: &ReferenciaRevision,
+            fecha: Date<Utc>) -> Result<f64> {
+        let meses = meses_referencia(referencia, fecha);
+        let mut suma = 0.0;
+        for (agno, mes) in meses.iter() {
+            suma += self.valor_mes_serie(indice, referencia.serie, *mes, *agno)?;
         }
-        let indice_mes = usize::try_from(mes_posible-1).unwrap();
-        redondea_cinco_decimales(self.tabla.get(&agno_posible).unwrap()[indice_mes]/100.0)
+        Ok(redondea_cinco_decimales(suma / meses.len() as f64))
     }
-    /// Devuelve el euribor correspondiente al cierre del mes anterior
-    pub fn euribor_fecha(&self, fecha: Date<Utc>) -> f64 {
-        let (agno, mes) = mes_anterior(fecha.year(), fecha.month());
-        self.euribor_mes(mes, agno)
+    /// Media de las diferencias mensuales entre dos índices desde una
+    /// fecha hasta otra (sin incluir su mes), con los meses en que hay
+    /// datos de ambos. Es el diferencial que se suma al índice sustitutivo
+    /// cuando desaparece un índice (DA 15ª Ley 14/2013)
+    pub fn diferencial_medio(&self, indice: IndiceReferencia, sustitutivo: IndiceReferencia,
+            desde: Date<Utc>, hasta: Date<Utc>) -> f64 {
+        let mut diferencias: Vec<f64> = Vec::new();
+        let (mut agno, mut mes) = (desde.year(), desde.month());
+        while (agno, mes) < (hasta.year(), hasta.month()) {
+            if let (Some(a), Some(b)) = (self.valor_mes_exacto(indice, mes, agno), 
+                    self.valor_mes_exacto(sustitutivo, mes, agno)) {
+                diferencias.push(a - b);
+            }
+            (agno, mes) = if mes == 12 { (agno+1, 1) } else { (agno, mes+1) };
+        }
+        if diferencias.is_empty() {
+            return 0.0;
+        }
+        redondea_cinco_decimales(diferencias.iter().sum::<f64>() / diferencias.len() as f64)
     }
     /// Hace la actualización del euribor al cierre del mes anterior a la fecha,
     /// ajustando a un tipo mínimo y máximo
-    pub fn actualiza_euribor(&self, fecha:Date<Utc>, incremento: f64, i_min: f64, i_max: f64) -> f64 {
-        let mut tipo = self.euribor_fecha(fecha) + incremento;
-        if tipo < i_min {
-            tipo = i_min;
-        }
-        if tipo > i_max {
-            tipo = i_max;
-        }
-        redondea_cinco_decimales(tipo)
+    pub fn actualiza_euribor(&self, fecha:Date<Utc>, incremento: f64, i_min: f64, i_max: f64) -> Result<f64> {
+        self.actualiza_euribor_con_limites(fecha, incremento, Some(i_min), Some(i_max))
+    }
+    /// Hace la actualización del euribor al cierre del mes anterior a la fecha,
+    /// ajustando sólo a los límites indicados. Sirve para recalcular la 
+    /// hipoteca sin cláusula suelo, o sin suelo ni techo
+    pub fn actualiza_euribor_con_limites(&self, fecha:Date<Utc>, incremento: f64, 
+            i_min: Option<f64>, i_max: Option<f64>) -> Result<f64> {
+        self.actualiza_indice(IndiceReferencia::Euribor12Meses, fecha, incremento, i_min, i_max)
+    }
+    /// Hace la actualización con el valor de un índice en el mes anterior a 
+    /// la fecha, ajustando a los límites indicados
+    pub fn actualiza_indice(&self, indice: IndiceReferencia, fecha:Date<Utc>, incremento: f64, 
+            i_min: Option<f64>, i_max: Option<f64>) -> Result<f64> {
+        Ok(ajusta_limites(self.valor_fecha(indice, fecha)? + incremento, i_min, i_max))
     }
 
 }
+/// Meses, como (año, mes), cuyos valores se usan en la revisión de una
+/// fecha según la regla de referencia, del último al primero
+fn meses_referencia(referencia: &ReferenciaRevision, fecha: Date<Utc>) -> Vec<(i32, u32)> {
+    let mes_revision = Utc.ymd(fecha.year(), fecha.month(), 1);
+    (0..referencia.meses_media.max(1))
+        .map(|i| add_n_months(mes_revision, -(referencia.meses_desfase + i)))
+        .map(|x| (x.year(), x.month()))
+        .collect()
+}
+/// Interpreta el mes de una línea de datos: aaaa-mm, mm/aaaa, dd/mm/aaaa
+/// o la abreviatura del mes seguida del año (ENE 2022). Devuelve (año, mes)
+fn parse_mes(cad: &str) -> Option<(i32, u32)> {
+    const MESES: [&str; 12] = ["ENE", "FEB", "MAR", "ABR", "MAY", "JUN", 
+        "JUL", "AGO", "SEP", "OCT", "NOV", "DIC"];
+    let (agno, mes): (i32, u32) = if let Some((a, m)) = cad.split_once('-') {
+        (a.parse().ok()?, m.parse().ok()?)
+    } else if cad.contains('/') {
+        let partes: Vec<&str> = cad.split('/').collect();
+        let n = partes.len();
+        (partes[n-1].parse().ok()?, partes[n-2].parse().ok()?)
+    } else {
+        let (m, a) = cad.split_once(' ')?;
+        let mes = MESES.iter().position(|x| m.to_uppercase().starts_with(x))? + 1;
+        (a.trim().parse().ok()?, mes as u32)
+    };
+    if (1..=12).contains(&mes) { Some((agno, mes)) } else { None }
+}
+impl Default for EuriborData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -135,32 +436,158 @@ mod tests {
     #[test]
     fn test_last_year() {
         let ed = EuriborData::new();
-        assert_eq!(2021, ed.last_year());
+        assert_eq!(Some(2021), ed.last_year());
     }
     #[test]
     fn test_euribor_mes() {
         let ed = EuriborData::new();
-        assert_eq!(-0.484/100.0, ed.euribor_mes(6, 2021));
-        assert_eq!(-0.108/100.0, ed.euribor_mes(4, 2020));
-        assert_eq!(-0.502/100.0, ed.euribor_mes(4, 2022));
+        assert_eq!(Ok(-0.484/100.0), ed.euribor_mes(6, 2021));
+        assert_eq!(Ok(-0.108/100.0), ed.euribor_mes(4, 2020));
+    }
+    #[test]
+    fn test_euribor_mes_sin_datos() {
+        let ed = EuriborData::new();
+        match ed.euribor_mes(4, 2022) {
+            Err(HipotecaError::SinDatosIndice { indice, agno, mes, .. }) => {
+                assert_eq!(IndiceReferencia::Euribor12Meses, indice);
+                assert_eq!((2022, 4), (agno, mes));
+            },
+            otro => panic!("{:?}", otro),
+        }
     }
     #[test]
     fn test_euribor_fecha() {
         let ed = EuriborData::new();
         assert_eq!(ed.euribor_mes(12, 2021), ed.euribor_fecha(Utc.ymd(2022, 1, 25)));        
+        assert!(ed.cubre_fecha(IndiceReferencia::Euribor12Meses, Utc.ymd(2022, 1, 25)));
+        assert!(!ed.cubre_fecha(IndiceReferencia::Euribor12Meses, Utc.ymd(2022, 2, 1)));
         assert_eq!(ed.euribor_mes(5, 2021), ed.euribor_fecha(Utc.ymd(2021, 6, 17)));
-        assert_eq!(redondea_cinco_decimales(-0.108/100.0), ed.euribor_fecha(Utc.ymd(2020, 5, 1)));
-        assert_eq!(redondea_cinco_decimales(1.924/100.0), ed.euribor_fecha(Utc.ymd(2011, 4, 1)));
-        assert_eq!(redondea_cinco_decimales(-0.502/100.0), ed.euribor_fecha(Utc.ymd(2022, 5, 30)));
-        assert_eq!(redondea_cinco_decimales(2.301/100.0), ed.euribor_fecha(Utc.ymd(2005, 1, 31)));
+        assert_eq!(Ok(redondea_cinco_decimales(-0.108/100.0)), ed.euribor_fecha(Utc.ymd(2020, 5, 1)));
+        assert_eq!(Ok(redondea_cinco_decimales(1.924/100.0)), ed.euribor_fecha(Utc.ymd(2011, 4, 1)));
+        assert_eq!(Ok(redondea_cinco_decimales(2.301/100.0)), ed.euribor_fecha(Utc.ymd(2005, 1, 31)));
     }
 
     #[test]
     fn test_actualiza_euribor() {
         let ed = EuriborData::new();
-        assert_eq!(0.00892, ed.actualiza_euribor(Utc.ymd(2020,5,1), 0.01, -0.1, 0.12));
-        assert_eq!(0.03, ed.actualiza_euribor(Utc.ymd(2020,5,1), 0.01, 0.03, 0.12));
-        assert_eq!(0.07893, ed.actualiza_euribor(Utc.ymd(2008,8,1), 0.025, 0.04, 0.12));
-        assert_eq!(0.12, ed.actualiza_euribor(Utc.ymd(2008,8,1), 0.1, 0.04, 0.12));
+        assert_eq!(Ok(0.00892), ed.actualiza_euribor(Utc.ymd(2020,5,1), 0.01, -0.1, 0.12));
+        assert_eq!(Ok(0.03), ed.actualiza_euribor(Utc.ymd(2020,5,1), 0.01, 0.03, 0.12));
+        assert_eq!(Ok(0.07893), ed.actualiza_euribor(Utc.ymd(2008,8,1), 0.025, 0.04, 0.12));
+        assert_eq!(Ok(0.12), ed.actualiza_euribor(Utc.ymd(2008,8,1), 0.1, 0.04, 0.12));
+    }
+    #[test]
+    fn test_actualiza_euribor_con_limites() {
+        let ed = EuriborData::new();
+        assert_eq!(Ok(0.00892), ed.actualiza_euribor_con_limites(Utc.ymd(2020,5,1), 0.01, None, Some(0.12)));
+        assert_eq!(Ok(0.12), ed.actualiza_euribor_con_limites(Utc.ymd(2008,8,1), 0.1, None, Some(0.12)));
+        assert_eq!(Ok(0.15393), ed.actualiza_euribor_con_limites(Utc.ymd(2008,8,1), 0.1, None, None));
     }
-}
\ No newline at end of file
+    #[test]
+    fn test_set_valores_agno() {
+        let mut ed = EuriborData::new();
+        assert!(!ed.tiene_datos(IndiceReferencia::IrphEntidades));
+        ed.set_valores_agno(IndiceReferencia::IrphEntidades, 2012, vec![3.0; 12]);
+        ed.set_valores_agno(IndiceReferencia::IrphEntidades, 2013, vec![3.5; 12]);
+        assert!(ed.tiene_datos(IndiceReferencia::IrphEntidades));
+        assert_eq!(Some(2013), ed.last_year_indice(IndiceReferencia::IrphEntidades));
+        assert_eq!(Ok(0.035), ed.valor_fecha(IndiceReferencia::IrphEntidades, Utc.ymd(2013, 2, 1)));
+        assert_eq!(Ok(0.03), ed.valor_fecha(IndiceReferencia::IrphEntidades, Utc.ymd(2013, 1, 1)));
+        assert_eq!(Ok(0.04), ed.actualiza_indice(IndiceReferencia::IrphEntidades, 
+            Utc.ymd(2013, 1, 1), 0.0025, Some(0.04), None));
+    }
+    #[test]
+    fn test_diferencial_medio() {
+        let mut ed = EuriborData::new();
+        ed.set_valores_agno(IndiceReferencia::IrphEntidades, 2013, vec![3.0; 12]);
+        ed.set_valores_agno(IndiceReferencia::IrphCajas, 2013, 
+            vec![3.2, 3.2, 3.2, 3.2, 3.2, 3.4, 3.4, 3.4, 3.4, 3.4, 3.4, 3.4]);
+        assert_eq!(0.003, ed.diferencial_medio(IndiceReferencia::IrphCajas, 
+            IndiceReferencia::IrphEntidades, Utc.ymd(2013, 1, 17), Utc.ymd(2013, 11, 1)));
+        assert_eq!(0.0, ed.diferencial_medio(IndiceReferencia::Ceca, 
+            IndiceReferencia::IrphEntidades, Utc.ymd(2013, 1, 17), Utc.ymd(2013, 11, 1)));
+    }
+    #[test]
+    fn test_cobertura() {
+        let mut ed = EuriborData::new();
+        assert_eq!(Some(((2004, 1), (2021, 12))), ed.cobertura(IndiceReferencia::Euribor12Meses));
+        assert_eq!(None, ed.cobertura(IndiceReferencia::IrphEntidades));
+        assert_eq!("Euribor 12 meses (cierre de mes): de 1/2004 a 12/2021", ed.informe_cobertura());
+        ed.set_valor_mes_serie(IndiceReferencia::Euribor12Meses, TipoSerie::FinDeMes, 3.0, 2, 2022);
+        assert_eq!("Euribor 12 meses (cierre de mes): de 1/2004 a 2/2022 (1 meses sin datos)", ed.informe_cobertura());
+    }
+    #[test]
+    fn test_serie_por_defecto() {
+        let mut ed = EuriborData::new();
+        assert_eq!(TipoSerie::FinDeMes, ed.serie_por_defecto(IndiceReferencia::Euribor12Meses));
+        assert!(ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, None, 6, 2021).is_ok());
+        // Con la media mensual cargada no se completa con los valores de cierre
+        ed.set_valores_agno(IndiceReferencia::Euribor12Meses, 2022, vec![-0.477, -0.335]);
+        assert_eq!(TipoSerie::MediaMensual, ed.serie_por_defecto(IndiceReferencia::Euribor12Meses));
+        assert_eq!(Ok(-0.00335), ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, None, 2, 2022));
+        assert!(ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, None, 6, 2021).is_err());
+        assert!(ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, Some(TipoSerie::FinDeMes), 6, 2021).is_ok());
+    }
+    #[test]
+    fn test_parse_mes() {
+        assert_eq!(Some((2022, 3)), parse_mes("2022-03"));
+        assert_eq!(Some((2022, 3)), parse_mes("03/2022"));
+        assert_eq!(Some((2022, 3)), parse_mes("31/03/2022"));
+        assert_eq!(Some((2022, 3)), parse_mes("MAR 2022"));
+        assert_eq!(Some((2022, 12)), parse_mes("dic 2022"));
+        assert_eq!(None, parse_mes("Fecha"));
+        assert_eq!(None, parse_mes("2022-13"));
+    }
+    #[test]
+    fn test_carga_csv_str() {
+        let mut ed = EuriborData::new();
+        let csv = "Tipos de referencia oficiales\n\"Fecha\";\"IRPH\"\n\"ENE 2022\";\"1,431\"\n\"FEB 2022\";\"1,427\"\n";
+        assert_eq!(2, ed.carga_csv_str(IndiceReferencia::IrphEntidades, TipoSerie::MediaMensual, csv).unwrap());
+        assert_eq!(Ok(0.01427), ed.valor_mes(IndiceReferencia::IrphEntidades, 2, 2022));
+        let csv = "fecha,valor\n2022-01,-0.477\n\n2022-02,-0.335\n";
+        assert_eq!(2, ed.carga_csv_str(IndiceReferencia::Euribor12Meses, TipoSerie::MediaMensual, csv).unwrap());
+        assert_eq!(Ok(-0.00335), ed.euribor_mes(2, 2022));
+        // Una línea no válida después de los valores es un error y no se carga nada
+        let csv = "fecha,valor\n2022-03,-0.2\n2022-04,n.d.\n";
+        let error = ed.carga_csv_str(IndiceReferencia::Euribor12Meses, TipoSerie::MediaMensual, csv).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!("la línea 3 no es un valor de EURIBOR12M (mes y valor en %): 2022-04,n.d.", error.to_string());
+        assert!(ed.euribor_mes(3, 2022).is_err());
+    }
+    #[test]
+    fn test_carga_json_str() {
+        let mut ed = EuriborData::new();
+        let json = r#"{"EURIBOR12M": {"2022": [-0.477, -0.335, null]}, "IRPH_ENTIDADES": {"2022": [1.431]}}"#;
+        assert_eq!(3, ed.carga_json_str(json).unwrap());
+        assert_eq!(Some(((2004, 1), (2022, 2))), ed.cobertura(IndiceReferencia::Euribor12Meses));
+        assert_eq!(Ok(0.01431), ed.valor_mes(IndiceReferencia::IrphEntidades, 1, 2022));
+        assert!(ed.carga_json_str(r#"{"LIBOR": {"2022": [1.0]}}"#).is_err());
+        let error = ed.carga_json_str(r#"{"IRPH_ENTIDADES": {"2021": [1,2,3,4,5,6,7,8,9,10,11,12,13]}}"#).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert_eq!("13 valores de IRPH_ENTIDADES en 2021: un año tiene doce meses", error.to_string());
+        assert_eq!(1, ed.carga_json_str(r#"{"EURIBOR12M_CIERRE": {"2022": [-0.5]}}"#).unwrap());
+        assert_eq!(Ok(-0.005), ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, 
+            Some(TipoSerie::FinDeMes), 1, 2022));
+        assert_eq!(Ok(-0.00477), ed.euribor_mes(1, 2022));
+    }
+    #[test]
+    fn test_valor_referencia() {
+        let mut ed = EuriborData::new();
+        ed.set_valores_agno(IndiceReferencia::Euribor12Meses, 2021, 
+            vec![-0.505, -0.501, -0.487, -0.484, -0.481, -0.484, -0.491, -0.498, -0.492, -0.477, -0.487, -0.502]);
+        let fecha = Utc.ymd(2021, 6, 17);
+        // Por defecto, la media mensual publicada del mes anterior
+        assert_eq!(Ok(-0.00481), ed.valor_referencia(IndiceReferencia::Euribor12Meses, 
+            &ReferenciaRevision::default(), fecha));
+        // Dos meses antes, al cierre de mes
+        let referencia = ReferenciaRevision::new(2, Some(TipoSerie::FinDeMes), 1).unwrap();
+        assert_eq!(ed.valor_mes_serie(IndiceReferencia::Euribor12Meses, Some(TipoSerie::FinDeMes), 4, 2021),
+            ed.valor_referencia(IndiceReferencia::Euribor12Meses, &referencia, fecha));
+        // Media de los tres meses anteriores publicados en el BOE
+        let referencia = ReferenciaRevision::new(1, Some(TipoSerie::MediaMensual), 3).unwrap();
+        assert_eq!(Ok(-0.00484), ed.valor_referencia(IndiceReferencia::Euribor12Meses, &referencia, fecha));
+        assert!(ed.cubre_referencia(IndiceReferencia::Euribor12Meses, &referencia, Utc.ymd(2021, 4, 1)));
+        assert!(!ed.cubre_referencia(IndiceReferencia::Euribor12Meses, &referencia, Utc.ymd(2021, 3, 1)));
+        assert!(!ed.cubre_referencia(IndiceReferencia::Euribor12Meses, &ReferenciaRevision::boe(1).unwrap(), 
+            Utc.ymd(2020, 6, 1)));
+    }
+}
@@ -2,24 +2,58 @@ use chrono::prelude::*;
 use chrono::Utc;
 
 use super::lib::*;
+use super::importe::*;
+use super::base_calculo::*;
+use super::calendario::*;
+use super::sistema_amortizacion::SistemaAmortizacion;
+use super::carencia::*;
+use super::tramo_interes::*;
+use super::redondeo_tipo::*;
+use super::error::*;
 use super::cuota::Cuota;
 use super::tabla_amort::TablaAmortizacion;
 use super::euribor_data::EuriborData;
+use super::indice_referencia::*;
 use super::novacion::Novacion;
+use super::amortizacion_anticipada::*;
+use super::interes_legal::InteresLegal;
+use super::liquidacion_impago::*;
+use super::comparacion_tablas::ComparacionTablas;
+use super::sustitucion_indice::SustitucionIndice;
+use super::conciliacion::*;
+use super::pagos::*;
+use super::cancelacion::Cancelacion;
+use super::buenas_practicas::*;
+use super::congelacion_cuota::*;
+use super::vencimiento_anticipado::*;
+use super::ejecucion::*;
+use super::subasta::*;
 
+#[derive(Clone)]
 pub struct Hipoteca {
     /// Usado para los nombres de los ficheros que se creen
     pub nombre_operacion: String, 
     /// Fecha inicial de la hipoteca establecida en la escritura
     pub fecha_escritura: Date<Utc>, 
     /// Capital inicial prestado, según establece la escritura
-    pub capital_prestado: f64, 
+    pub capital_prestado: Importe, 
     /// Tipo de interés nominal anual establecido por la escritura
     pub tipo_interes_anual: f64, 
     /// Meses para amortizar la hipoteca
     pub meses: i32, 
+    /// Fecha de vencimiento de la primera cuota. Por defecto un mes
+    /// después de la escritura; si es otra, los intereses de la primera
+    /// cuota se calculan por los días transcurridos desde la escritura
+    pub fecha_primera_cuota: Date<Utc>,
     /// Fecha último vencimiento
     pub fecha_ultimo_vencimiento: Date<Utc>,
+    /// Regla de fin de mes: si el día de vencimiento es el último de su
+    /// mes, todas las cuotas vencen el último día de cada mes
+    pub fin_de_mes: bool,
+    /// Ajuste de los vencimientos que caen en día inhábil
+    pub ajuste_dia_habil: AjusteDiaHabil,
+    /// Calendario de festivos para el ajuste de los vencimientos
+    pub calendario: CalendarioFestivos,
     /// Meses de aplicación del tipo de interes nominal inicial, antes 
     /// de la primera actualización
     pub meses_hasta_primera_revision: i32, 
@@ -27,6 +61,24 @@ pub struct Hipoteca {
     pub intervalo_revisiones: i32,
     /// Incremento aplicado sobre el euribor en las revisiones 
     pub incremento_euribor: f64, 
+    /// Índice de referencia de las revisiones. Por defecto el euribor
+    /// a 12 meses; el incremento se aplica sobre este índice
+    pub indice: IndiceReferencia,
+    /// Regla para obtener el valor del índice en cada revisión: mes que se
+    /// toma, serie (media mensual o cierre de mes) y meses que se promedian
+    pub referencia_revision: ReferenciaRevision,
+    /// Índice sustitutivo pactado para el caso de que desaparezca el índice
+    pub indice_sustitutivo: Option<IndiceReferencia>,
+    /// Diferencial pactado sobre el índice sustitutivo
+    pub diferencial_sustitutivo: f64,
+    /// Series de valores de los índices de referencia usadas en las revisiones
+    pub indices: EuriborData,
+    /// Tramos de tipo de interés de las hipotecas mixtas. Si hay tramos
+    /// sustituyen al tipo inicial y al incremento sobre el euribor en la
+    /// tabla con actualización del euribor
+    pub tramos: Vec<TramoInteres>,
+    /// Cláusula de redondeo del tipo revisado (índice más diferencial)
+    pub redondeo_tipo: RedondeoTipo,
     /// Tipo mínimo establecido en las cláusulas de la hipoteca
     pub i_min: f64, 
     /// Tipo máximo establecido en las cláusulas de la hipoteca
@@ -35,8 +87,32 @@ pub struct Hipoteca {
     pub fecha_impago: Date<Utc>,
     /// Fecha en la que se produjo la resolución del contrato
     pub fecha_resolucion: Date<Utc>,
+    /// Tipo de interés de demora pactado. Si no se pactó
+    /// se aplica el límite legal
+    pub interes_demora: Option<f64>,
     /// Novaciones: lista de novaciones y ampliaciones
     pub novaciones: Vec<Novacion>,
+    /// Amortizaciones anticipadas parciales o totales
+    pub amortizaciones_anticipadas: Vec<AmortizacionAnticipada>,
+    /// Periodos de carencia de capital o total
+    pub carencias: Vec<Carencia>,
+    /// Pagos realizados por el deudor después del impago
+    pub pagos: Vec<Pago>,
+    /// Gastos repercutidos por la entidad después del impago
+    pub gastos: Vec<Gasto>,
+    /// Orden en que se imputan los pagos a los conceptos de la deuda.
+    /// Por defecto el del art. 1173 CC
+    pub orden_imputacion: Vec<ConceptoImputacion>,
+    /// Responsabilidad hipotecaria pactada en la escritura
+    pub responsabilidad_hipotecaria: Option<ResponsabilidadHipotecaria>,
+    /// Comisión pactada por amortización anticipada
+    pub comision_amortizacion: ComisionAmortizacion,
+    /// Forma de redondear a céntimos las cuotas y los intereses
+    pub redondeo: Redondeo,
+    /// Base de cálculo pactada para los intereses de cada periodo
+    pub base_calculo: BaseCalculo,
+    /// Sistema de amortización: francés, alemán, americano o progresivo
+    pub sistema_amortizacion: SistemaAmortizacion,
     /// Tabla de amortización completa, pero con todas
     /// las cuotas calculadas con el interés inicial i
     pub tabla_amort_sin_actualizacion: TablaAmortizacion,
@@ -48,32 +124,100 @@ pub struct Hipoteca {
 impl Hipoteca {
         
     /// Crea una instancia de Hipoteca
-    pub fn new(nombre_operacion: String, fecha_escritura: Date<Utc>, capital_prestado: f64, 
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(nombre_operacion: String, fecha_escritura: Date<Utc>, capital_prestado: Importe, 
             tipo_interes_anual: f64, meses: i32, meses_hasta_primera_revision: i32, 
             intervalo_revisiones: i32, incremento_euribor: f64, 
-            i_min: f64, i_max: f64, fecha_impago:Date<Utc>, fecha_resolucion:Date<Utc>) -> Self {
-        let mut h = Hipoteca { 
+            i_min: f64, i_max: f64, fecha_impago:Date<Utc>, fecha_resolucion:Date<Utc>) -> Result<Self> {
+        Self::new_con_indices(nombre_operacion, fecha_escritura, capital_prestado, 
+            tipo_interes_anual, meses, meses_hasta_primera_revision, intervalo_revisiones, 
+            incremento_euribor, i_min, i_max, fecha_impago, fecha_resolucion, EuriborData::new())
+    }
+
+    /// Crea una instancia de Hipoteca con las series de índices indicadas,
+    /// por ejemplo las cargadas desde ficheros. Devuelve un error si algún
+    /// dato no es válido o faltan datos del índice para alguna revisión
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_con_indices(nombre_operacion: String, fecha_escritura: Date<Utc>, capital_prestado: Importe, 
+            tipo_interes_anual: f64, meses: i32, meses_hasta_primera_revision: i32, 
+            intervalo_revisiones: i32, incremento_euribor: f64, 
+            i_min: f64, i_max: f64, fecha_impago:Date<Utc>, fecha_resolucion:Date<Utc>,
+            indices: EuriborData) -> Result<Self> {
+        let mut h = Self::new_sin_tablas(nombre_operacion, fecha_escritura, capital_prestado, 
+            tipo_interes_anual, meses, meses_hasta_primera_revision, intervalo_revisiones, 
+            incremento_euribor, i_min, i_max, fecha_impago, fecha_resolucion, indices)?;
+        h.recalcula_tablas()?;
+        Ok(h)
+    }
+
+    /// Crea una instancia de Hipoteca sin calcular sus tablas de amortización,
+    /// para fijar antes el resto de condiciones del préstamo (base de cálculo,
+    /// índice, carencias...). Las tablas se calculan después con `recalcula_tablas`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_sin_tablas(nombre_operacion: String, fecha_escritura: Date<Utc>, capital_prestado: Importe, 
+            tipo_interes_anual: f64, meses: i32, meses_hasta_primera_revision: i32, 
+            intervalo_revisiones: i32, incremento_euribor: f64, 
+            i_min: f64, i_max: f64, fecha_impago:Date<Utc>, fecha_resolucion:Date<Utc>,
+            indices: EuriborData) -> Result<Self> {
+        if capital_prestado <= Importe::ZERO {
+            return Err(HipotecaError::entrada_invalida("capital_prestado", "debe ser mayor que cero"));
+        }
+        if meses <= 0 {
+            return Err(HipotecaError::entrada_invalida("meses", "debe ser mayor que cero"));
+        }
+        if meses_hasta_primera_revision < 0 {
+            return Err(HipotecaError::entrada_invalida("meses_hasta_primera_revision", "no puede ser negativo"));
+        }
+        if intervalo_revisiones <= 0 {
+            return Err(HipotecaError::entrada_invalida("intervalo_revisiones", "debe ser mayor que cero"));
+        }
+        if i_min > i_max {
+            return Err(HipotecaError::entrada_invalida("i_min", "es mayor que el tipo máximo"));
+        }
+        if fecha_resolucion < fecha_impago {
+            return Err(HipotecaError::entrada_invalida("fecha_resolucion", "es anterior a la fecha de impago"));
+        }
+        Ok(Hipoteca { 
             nombre_operacion,
             fecha_escritura, 
             capital_prestado, 
             tipo_interes_anual,  
             meses, 
+            fecha_primera_cuota: add_one_month(fecha_escritura),
             fecha_ultimo_vencimiento: add_n_months(fecha_escritura, meses),
+            fin_de_mes: false,
+            ajuste_dia_habil: AjusteDiaHabil::SinAjuste,
+            calendario: CalendarioFestivos::target2_y_nacional(),
             meses_hasta_primera_revision, 
             intervalo_revisiones,  
             incremento_euribor, 
+            indice: IndiceReferencia::Euribor12Meses,
+            referencia_revision: ReferenciaRevision::default(),
+            indice_sustitutivo: None,
+            diferencial_sustitutivo: 0.0,
+            indices,
+            tramos: Vec::<TramoInteres>::new(),
+            redondeo_tipo: RedondeoTipo::SinRedondeo,
             i_min, 
             i_max, 
             fecha_impago,
             fecha_resolucion,
+            interes_demora: None,
             novaciones: Vec::<Novacion>::new(),
+            amortizaciones_anticipadas: Vec::<AmortizacionAnticipada>::new(),
+            carencias: Vec::<Carencia>::new(),
+            pagos: Vec::<Pago>::new(),
+            gastos: Vec::<Gasto>::new(),
+            orden_imputacion: ConceptoImputacion::orden_legal().to_vec(),
+            responsabilidad_hipotecaria: None,
+            comision_amortizacion: ComisionAmortizacion::SinComision,
+            redondeo: Redondeo::MitadArriba,
+            base_calculo: BaseCalculo::Treinta360,
+            sistema_amortizacion: SistemaAmortizacion::Frances,
             tabla_amort_sin_actualizacion: TablaAmortizacion::new(),
             tabla_amort_con_actualizacion_euribor: TablaAmortizacion::new(),
             tabla_amort_impago: TablaAmortizacion::new(),
-        };
-        h.tabla_amort_sin_actualizacion = h.calcula_tabla_amort_sin_actualizacion();
-        h.tabla_amort_con_actualizacion_euribor = h.calcula_tabla_amort_con_actualizacion_euribor();
-        h        
+        })
     }
 
     /// Calcula la tabla de amortización con los datos iniciales de 
@@ -81,70 +225,492 @@ impl Hipoteca {
     /// de interés
     pub fn calcula_tabla_amort_sin_actualizacion(&mut self) -> TablaAmortizacion {
         let mut tabla : TablaAmortizacion = TablaAmortizacion::new();
-        let mut fecha: Date<Utc> = add_one_month(self.fecha_escritura);            
-        let cuota_total = importe_cuota_mensual(self.capital_prestado, self.tipo_interes_anual, self.meses);
+        let mut n_vencim = 0;
+        let mut fecha: Date<Utc> = self.fecha_vencimiento(n_vencim);            
+        let mut fecha_anterior: Date<Utc> = self.fecha_escritura;
+        let mut cuota_total = self.sistema_amortizacion.cuota_base(self.redondeo, self.capital_prestado, 
+            self.tipo_interes_anual, self.meses, 0);
         let tipo_interes = self.tipo_interes_anual;
         let mut meses_restantes_antes = self.meses;
-        let mut capital_pendiente_antes: f64 = self.capital_prestado;
-        for _i in 0..self.meses {
-            let cuota_interes = intereses_mes(capital_pendiente_antes, tipo_interes);
-            let cuota_capital = redondea_dos_decimales(cuota_total-cuota_interes);
-            let cuota: Cuota = Cuota::new(fecha, self.tipo_interes_anual, meses_restantes_antes,
-                capital_pendiente_antes,cuota_total, cuota_capital, cuota_interes);
+        let mut capital_pendiente_antes: Importe = self.capital_prestado;
+        let mut amortizaciones = self.amortizaciones_ordenadas().into_iter().peekable();
+        let carencias = self.rangos_carencia();
+        let mut tras_carencia = false;
+        while meses_restantes_antes > 0 {
+            while let Some(amortizacion) = amortizaciones.next_if(|x| x.fecha < fecha) {
+                self.aplica_amortizacion_anticipada(&amortizacion, &mut tabla, fecha_anterior, n_vencim, 
+                    tipo_interes, &mut capital_pendiente_antes, &mut meses_restantes_antes, &mut cuota_total);
+            }
+            if meses_restantes_antes <= 0 {
+                break;
+            }
+            let cuota: Cuota = match carencia_cuota(&carencias, n_vencim) {
+                Some(tipo_carencia) => {
+                    tras_carencia = true;
+                    self.cuota_carencia(tipo_carencia, fecha_anterior, fecha, capital_pendiente_antes,
+                        tipo_interes, meses_restantes_antes)
+                },
+                None => {
+                    // Al terminar la carencia la cuota se calcula sobre el plazo restante
+                    if tras_carencia {
+                        cuota_total = self.sistema_amortizacion.cuota_base(self.redondeo, capital_pendiente_antes,
+                            tipo_interes, meses_restantes_antes, n_vencim);
+                        tras_carencia = false;
+                    }
+                    let (total, cuota_capital, cuota_interes) = self.reparte_cuota(fecha_anterior, fecha, 
+                        n_vencim, capital_pendiente_antes, tipo_interes, cuota_total, meses_restantes_antes);
+                    Cuota::new(fecha, self.tipo_interes_anual, meses_restantes_antes,
+                        capital_pendiente_antes, total, cuota_capital, cuota_interes)
+                },
+            };
+            capital_pendiente_antes = cuota.cap_pendiente_despues();
             tabla.push(cuota);
 
-            capital_pendiente_antes = redondea_dos_decimales(capital_pendiente_antes - cuota_capital);
-            fecha = add_one_month(fecha);
+            fecha_anterior = fecha;
+            n_vencim += 1;
+            fecha = self.fecha_vencimiento(n_vencim);
             meses_restantes_antes -= 1;
         }
         // Ajuste de la ultima cuota por descuadres de redondeo
-        if capital_pendiente_antes != 0.0 {
-            let ult_cuota = tabla.cuotas.last_mut().unwrap();
-            //ult_cuota.cap_pendiente_antes += capital_pendiente_antes;
-            ult_cuota.cuota_total = redondea_dos_decimales(ult_cuota.cuota_total + capital_pendiente_antes);
-            ult_cuota.cuota_capital = redondea_dos_decimales(ult_cuota.cuota_capital + capital_pendiente_antes);
+        if !capital_pendiente_antes.is_zero() {
+            if let Some(ult_cuota) = tabla.cuotas.last_mut() {
+                ult_cuota.cuota_total = self.redondeo.importe(ult_cuota.cuota_total + capital_pendiente_antes);
+                ult_cuota.cuota_capital = self.redondeo.importe(ult_cuota.cuota_capital + capital_pendiente_antes);
+            }
         } 
         tabla
     }
 
     /// Calcula la tabla de amortización actualizando
-    /// con el auribor en cada periodo
-    pub fn calcula_tabla_amort_con_actualizacion_euribor(&mut self) -> TablaAmortizacion {
-        let ed = EuriborData::new();
+    /// con el auribor en cada periodo y aplicando las
+    /// novaciones a partir de la fecha en que surten efecto
+    pub fn calcula_tabla_amort_con_actualizacion_euribor(&mut self) -> Result<TablaAmortizacion> {
+        self.calcula_tabla_euribor(None, true, true)
+    }
+
+    /// Calcula la tabla de amortización con actualización del euribor
+    /// eliminando el tipo mínimo (cláusula suelo) y, si se indica,
+    /// también el tipo máximo
+    pub fn calcula_tabla_amort_sin_suelo(&mut self, mantener_techo: bool) -> Result<TablaAmortizacion> {
+        self.calcula_tabla_euribor(None, false, mantener_techo)
+    }
+
+    /// Calcula la tabla de amortización como si en todas las revisiones
+    /// se hubiera aplicado otro índice más el diferencial indicado, por
+    /// ejemplo el euribor en lugar del IRPH. Se mantienen los tipos mínimo
+    /// y máximo y las demás condiciones de las novaciones
+    pub fn calcula_tabla_amort_con_indice(&mut self, indice: IndiceReferencia, 
+            diferencial: f64) -> Result<TablaAmortizacion> {
+        self.calcula_tabla_euribor(Some((indice, diferencial)), true, true)
+    }
+
+    /// Calcula la tabla de amortización con actualización del índice,
+    /// aplicando o no los tipos mínimo y máximo en las revisiones. Si se
+    /// indica un índice con su diferencial, sustituye al de la escritura
+    /// y a los diferenciales pactados en las novaciones
+    fn calcula_tabla_euribor(&mut self, sustitucion: Option<(IndiceReferencia, f64)>, 
+            aplicar_suelo: bool, aplicar_techo: bool) -> Result<TablaAmortizacion> {
+        let valor_indice = |h: &Self, fecha: Date<Utc>| match sustitucion {
+            Some((indice, _)) => h.indices.valor_referencia(indice, &h.referencia_revision, fecha),
+            None => h.valor_indice(fecha),
+        };
+        let hay_datos_indice = |h: &Self, fecha: Date<Utc>| match sustitucion {
+            Some((indice, _)) => h.indices.cubre_referencia(indice, &h.referencia_revision, fecha),
+            None => h.hay_datos_indice(fecha),
+        };
         let mut tabla = self.calcula_amort_primer_periodo();
-        let ult_cuota = tabla.cuotas.last_mut().unwrap();
-        let mut fecha_prox_vencim = add_one_month(ult_cuota.fecha);
-        let mut meses_restantes_antes = ult_cuota.meses_restantes_antes-1;
-        let mut cap_pendiente_antes = ult_cuota.cap_pendiente_despues();
-        // Ir calculando periodos con actualización euribor en cada periodo
-        while fecha_prox_vencim <= self.fecha_ultimo_vencimiento {
-            // TODO Actualizar euribor + increm, imax, imin
-            let tipo_interes = ed.actualiza_euribor(fecha_prox_vencim, 
-                self.incremento_euribor, self.i_min, self.i_max);
-            let cuota_total: f64 = importe_cuota_mensual(cap_pendiente_antes, tipo_interes, meses_restantes_antes); 
-            for _i in 0..self.intervalo_revisiones {
-                let cuota_intereses = intereses_mes(cap_pendiente_antes, tipo_interes);
-                let cuota_capital = redondea_dos_decimales(cuota_total - cuota_intereses); 
-                let cuota = Cuota::new(fecha_prox_vencim, tipo_interes, 
-                    meses_restantes_antes,cap_pendiente_antes,cuota_total, cuota_capital,
-                    cuota_intereses);
-                tabla.push(cuota);
-                cap_pendiente_antes = redondea_dos_decimales(cap_pendiente_antes - cuota_capital);
-                meses_restantes_antes -= 1;
-                fecha_prox_vencim = add_one_month(fecha_prox_vencim);
-                if fecha_prox_vencim > self.fecha_ultimo_vencimiento {
-                    break;
+        // Con tramos de interés todas las cuotas se calculan según los tramos
+        let tramos = self.inicio_tramos();
+        if !tramos.is_empty() {
+            tabla.cuotas.clear();
+        }
+        // Las cuotas del primer periodo desde la primera carencia se vuelven a calcular
+        let carencias = self.rangos_carencia();
+        if let Some(primera) = carencias.iter().map(|x| x.0).min() {
+            tabla.cuotas.truncate(primera as usize);
+        }
+        let mut tras_carencia = false;
+        let mut novaciones: Vec<&Novacion> = self.novaciones.iter().collect();
+        novaciones.sort_by_key(|n| n.fecha);
+        // Las cuotas del primer periodo a partir de la primera novación
+        // se vuelven a calcular con las nuevas condiciones
+        if let Some(novacion) = novaciones.first() {
+            let fecha_novacion = novacion.fecha;
+            tabla.cuotas.retain(|x| x.fecha < fecha_novacion);
+        }
+        let amortizaciones = self.amortizaciones_ordenadas();
+        if let Some(amortizacion) = amortizaciones.first() {
+            let fecha_amortizacion = amortizacion.fecha;
+            tabla.cuotas.retain(|x| x.fecha <= fecha_amortizacion);
+        }
+        let mut amortizaciones = amortizaciones.into_iter().peekable();
+        // Las cuotas del primer periodo se calculan con la misma cuota de referencia
+        let cuota_inicial = self.sistema_amortizacion.cuota_base(self.redondeo, self.capital_prestado,
+            self.tipo_interes_anual, self.meses, 0);
+        // Se cuentan los vencimientos para situar las revisiones y obtener
+        // las fechas ajustadas de cada cuota
+        let mut n_vencim = tabla.cuotas.len() as i32;
+        let mut fecha_prox_vencim = self.fecha_vencimiento(n_vencim);
+        let (mut fecha_ult_vencim, mut meses_restantes_antes, mut cap_pendiente_antes,
+                mut tipo_interes, mut cuota_total) = match tabla.cuotas.last() {
+            Some(ult_cuota) => (ult_cuota.fecha, ult_cuota.meses_restantes_antes-1,
+                ult_cuota.cap_pendiente_despues(), ult_cuota.i, cuota_inicial),
+            None => (self.fecha_escritura, self.meses, self.capital_prestado,
+                self.tipo_interes_anual, cuota_inicial),
+        };
+        let mut n_prox_revision = if tramos.is_empty() { self.meses_hasta_primera_revision } else { i32::MAX };
+        let mut intervalo_revisiones = self.intervalo_revisiones;
+        let mut tramos = tramos.into_iter().peekable();
+        let mut fecha_ult_revision = self.fecha_vencimiento_nominal(self.meses_hasta_primera_revision);
+        let mut incremento_euribor = sustitucion.map_or(self.incremento_euribor, |x| x.1);
+        let mut i_min = self.i_min;
+        let mut i_max = self.i_max;
+        let mut novaciones = novaciones.into_iter().peekable();
+        // Ir calculando cuotas con actualización euribor en cada revisión
+        while meses_restantes_antes > 0 {
+            let mut recalcular_cuota = false;
+            let mut sin_datos_indice = false;
+            while let Some((_, tramo)) = tramos.next_if(|x| x.0 <= n_vencim) {
+                match tramo.tipo {
+                    TipoTramo::Fijo(tipo) => {
+                        tipo_interes = tipo;
+                        n_prox_revision = i32::MAX;
+                        recalcular_cuota = true;
+                    },
+                    TipoTramo::Variable(diferencial) => {
+                        // El tramo variable empieza con una revisión
+                        incremento_euribor = sustitucion.map_or(diferencial, |x| x.1);
+                        i_min = tramo.i_min.unwrap_or(self.i_min);
+                        i_max = tramo.i_max.unwrap_or(self.i_max);
+                        intervalo_revisiones = tramo.intervalo_revisiones.unwrap_or(self.intervalo_revisiones);
+                        n_prox_revision = n_vencim;
+                    },
                 }
             }
+            if n_vencim >= n_prox_revision {
+                // El índice se toma en la fecha de vencimiento pactada, sin ajustar.
+                // Sin datos del índice solo se mantiene el tipo anterior en las
+                // revisiones posteriores a la resolución, y si se ha activado la
+                // proyección en las series; en los demás casos es un error
+                let fecha_revision = self.fecha_vencimiento_nominal(n_vencim);
+                if self.indices.proyeccion() && fecha_prox_vencim > self.fecha_resolucion
+                        && !hay_datos_indice(self, fecha_revision) {
+                    sin_datos_indice = true;
+                } else {
+                    tipo_interes = ajusta_limites(
+                        self.redondeo_tipo.aplica(valor_indice(self, fecha_revision)? + incremento_euribor),
+                        aplicar_suelo.then_some(i_min), aplicar_techo.then_some(i_max));
+                    fecha_ult_revision = fecha_revision;
+                }
+                n_prox_revision += intervalo_revisiones;
+                recalcular_cuota = true;
+            }
+            let mut hay_novacion = false;
+            while let Some(novacion) = novaciones.next_if(|x| x.fecha <= fecha_prox_vencim) {
+                if let Some(incremento) = novacion.incremento_euribor.filter(|_| sustitucion.is_none()) {
+                    incremento_euribor = incremento;
+                }
+                if let Some(tipo) = novacion.i_min {
+                    i_min = tipo;
+                }
+                if let Some(tipo) = novacion.i_max {
+                    i_max = tipo;
+                }
+                if let Some(meses) = novacion.meses_restantes {
+                    meses_restantes_antes = meses;
+                }
+                cap_pendiente_antes = self.redondeo.importe(cap_pendiente_antes + novacion.ampliacion_capital);
+                if let Some(tipo) = novacion.tipo_interes_anual {
+                    tipo_interes = tipo;
+                } else if novacion.cambia_condiciones_revision() && n_vencim >= self.meses_hasta_primera_revision {
+                    // Las nuevas condiciones se aplican sobre el euribor de la última revisión
+                    tipo_interes = ajusta_limites(
+                        self.redondeo_tipo.aplica(valor_indice(self, fecha_ult_revision)? + incremento_euribor),
+                        aplicar_suelo.then_some(i_min), aplicar_techo.then_some(i_max));
+                }
+                hay_novacion = true;
+            }
+            while let Some(amortizacion) = amortizaciones.next_if(|x| x.fecha < fecha_prox_vencim) {
+                self.aplica_amortizacion_anticipada(&amortizacion, &mut tabla, fecha_ult_vencim, n_vencim, 
+                    tipo_interes, &mut cap_pendiente_antes, &mut meses_restantes_antes, &mut cuota_total);
+            }
+            if meses_restantes_antes <= 0 {
+                break;
+            }
+            let tipo_carencia = carencia_cuota(&carencias, n_vencim);
+            if tipo_carencia.is_none() && (recalcular_cuota || hay_novacion || tras_carencia) {
+                cuota_total = self.sistema_amortizacion.cuota_base(self.redondeo, cap_pendiente_antes, 
+                    tipo_interes, meses_restantes_antes, n_vencim); 
+            }
+            let mut cuota = match tipo_carencia {
+                Some(tipo_carencia) => self.cuota_carencia(tipo_carencia, fecha_ult_vencim, fecha_prox_vencim,
+                    cap_pendiente_antes, tipo_interes, meses_restantes_antes),
+                None => {
+                    let (total, cuota_capital, cuota_intereses) = self.reparte_cuota(fecha_ult_vencim, 
+                        fecha_prox_vencim, n_vencim, cap_pendiente_antes, tipo_interes, cuota_total, 
+                        meses_restantes_antes);
+                    Cuota::new(fecha_prox_vencim, tipo_interes, meses_restantes_antes, 
+                        cap_pendiente_antes, total, cuota_capital, cuota_intereses)
+                },
+            };
+            tras_carencia = tipo_carencia.is_some();
+            cuota.novacion = hay_novacion;
+            cuota.sin_datos_indice = sin_datos_indice;
+            cap_pendiente_antes = cuota.cap_pendiente_despues();
+            tabla.push(cuota);
+            meses_restantes_antes -= 1;
+            fecha_ult_vencim = fecha_prox_vencim;
+            n_vencim += 1;
+            fecha_prox_vencim = self.fecha_vencimiento(n_vencim);
         }
         // Ajuste de la ultima cuota por descuadres de redondeo
-        if cap_pendiente_antes != 0.0 {
-            let ult_cuota = tabla.cuotas.last_mut().unwrap();
-            //ult_cuota.cap_pendiente_antes += cap_pendiente_antes;
-            ult_cuota.cuota_total = redondea_dos_decimales(ult_cuota.cuota_total + cap_pendiente_antes);
-            ult_cuota.cuota_capital = redondea_dos_decimales(ult_cuota.cuota_capital + cap_pendiente_antes);
+        if !cap_pendiente_antes.is_zero() {
+            if let Some(ult_cuota) = tabla.cuotas.last_mut() {
+                ult_cuota.cuota_total = self.redondeo.importe(ult_cuota.cuota_total + cap_pendiente_antes);
+                ult_cuota.cuota_capital = self.redondeo.importe(ult_cuota.cuota_capital + cap_pendiente_antes);
+            }
+        }
+        Ok(tabla)
+    }
+
+    /// Valor del índice de referencia aplicable en la revisión de una fecha,
+    /// según la regla de referencia de la hipoteca. Si el índice ha
+    /// desaparecido se usa el sustitutivo pactado con su diferencial o, en
+    /// su defecto, el IRPH Entidades más la media de las diferencias entre
+    /// ambos índices desde la escritura (DA 15ª Ley 14/2013)
+    pub fn valor_indice(&self, fecha: Date<Utc>) -> Result<f64> {
+        let ed = &self.indices;
+        match self.indice.fecha_desaparicion() {
+            Some(fecha_desaparicion) if fecha >= fecha_desaparicion => {
+                match self.indice_sustitutivo {
+                    Some(sustitutivo) => Ok(redondea_cinco_decimales(
+                        ed.valor_referencia(sustitutivo, &self.referencia_revision, fecha)?
+                        + self.diferencial_sustitutivo)),
+                    None => Ok(redondea_cinco_decimales(
+                        ed.valor_referencia(IndiceReferencia::IrphEntidades, &self.referencia_revision, fecha)? 
+                        + ed.diferencial_medio(self.indice, IndiceReferencia::IrphEntidades,
+                            self.fecha_escritura, fecha_desaparicion))),
+                }
+            },
+            _ => ed.valor_referencia(self.indice, &self.referencia_revision, fecha),
+        }
+    }
+
+    /// Indica si hay datos del índice, o de su sustitutivo si ha
+    /// desaparecido, para la revisión de una fecha
+    pub fn hay_datos_indice(&self, fecha: Date<Utc>) -> bool {
+        match self.indice.fecha_desaparicion() {
+            Some(fecha_desaparicion) if fecha >= fecha_desaparicion => {
+                let sustitutivo = self.indice_sustitutivo.unwrap_or(IndiceReferencia::IrphEntidades);
+                self.indices.cubre_referencia(sustitutivo, &self.referencia_revision, fecha)
+            },
+            _ => self.indices.cubre_referencia(self.indice, &self.referencia_revision, fecha),
+        }
+    }
+
+    /// Añade una novación a la hipoteca y vuelve a calcular
+    /// las tablas de amortización
+    pub fn add_novacion(&mut self, novacion: Novacion) -> Result<()> {
+        let novaciones = self.novaciones.clone();
+        self.novaciones.push(novacion);
+        self.novaciones.sort_by_key(|n| n.fecha);
+        self.recalcula_o_deshace(|h| h.novaciones = novaciones)
+    }
+
+    /// Vuelve a calcular las tablas de amortización, por ejemplo
+    /// después de cambiar el índice o cargar sus datos
+    pub fn recalcula_tablas(&mut self) -> Result<()> {
+        self.valida_tipos()?;
+        self.valida_fecha_primera_cuota(self.fecha_primera_cuota)?;
+        for carencia in self.carencias.iter() {
+            self.valida_carencia(carencia)?;
+        }
+        for (i, tramo) in self.tramos.iter().enumerate() {
+            self.valida_tramo(i.checked_sub(1).map(|j| &self.tramos[j]), tramo)?;
+        }
+        if self.amortizaciones_anticipadas.iter().any(|a| a.importe <= Importe::ZERO) {
+            return Err(HipotecaError::entrada_invalida("importe", "la amortización debe ser mayor que cero"));
+        }
+        self.fecha_ultimo_vencimiento = self.fecha_vencimiento(self.meses-1);
+        self.tabla_amort_sin_actualizacion = self.calcula_tabla_amort_sin_actualizacion();
+        self.tabla_amort_con_actualizacion_euribor = self.calcula_tabla_amort_con_actualizacion_euribor()?;
+        Ok(())
+    }
+
+    /// Vuelve a calcular las tablas tras añadir un elemento. Si el cálculo
+    /// falla, `deshace` restaura los datos anteriores y se recuperan sus
+    /// tablas, de forma que la hipoteca no queda a medio modificar
+    fn recalcula_o_deshace(&mut self, deshace: impl FnOnce(&mut Self)) -> Result<()> {
+        if let Err(e) = self.recalcula_tablas() {
+            deshace(self);
+            self.recalcula_tablas()?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Añade una amortización anticipada y vuelve a calcular
+    /// las tablas de amortización
+    pub fn add_amortizacion_anticipada(&mut self, amortizacion: AmortizacionAnticipada) -> Result<()> {
+        let amortizaciones = self.amortizaciones_anticipadas.clone();
+        self.amortizaciones_anticipadas.push(amortizacion);
+        self.amortizaciones_anticipadas.sort_by_key(|a| a.fecha);
+        self.recalcula_o_deshace(|h| h.amortizaciones_anticipadas = amortizaciones)
+    }
+
+    /// Comprueba que todos los tipos de interés de la hipoteca, sus
+    /// novaciones y sus tramos son números finitos
+    fn valida_tipos(&self) -> Result<()> {
+        let mut tipos: Vec<(&str, Option<f64>)> = vec![
+            ("tipo_interes_anual", Some(self.tipo_interes_anual)),
+            ("incremento_euribor", Some(self.incremento_euribor)),
+            ("i_min", Some(self.i_min)),
+            ("i_max", Some(self.i_max)),
+            ("interes_demora", self.interes_demora),
+            ("diferencial_sustitutivo", Some(self.diferencial_sustitutivo)),
+        ];
+        for novacion in self.novaciones.iter() {
+            tipos.extend([("novacion", novacion.tipo_interes_anual), ("novacion", novacion.incremento_euribor),
+                ("novacion", novacion.i_min), ("novacion", novacion.i_max)]);
+        }
+        for tramo in self.tramos.iter() {
+            let (TipoTramo::Fijo(tipo) | TipoTramo::Variable(tipo)) = tramo.tipo;
+            tipos.extend([("tramo", Some(tipo)), ("tramo", tramo.i_min), ("tramo", tramo.i_max)]);
+        }
+        match tipos.into_iter().find(|(_, tipo)| tipo.is_some_and(|x| !x.is_finite())) {
+            Some((campo, Some(tipo))) => Err(HipotecaError::entrada_invalida(campo, 
+                &format!("{} no es un tipo de interés", tipo))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Añade un tramo de tipo de interés a continuación de los anteriores
+    /// y vuelve a calcular las tablas de amortización
+    pub fn add_tramo(&mut self, tramo: TramoInteres) -> Result<()> {
+        self.valida_tramo(self.tramos.last(), &tramo)?;
+        self.tramos.push(tramo);
+        self.recalcula_o_deshace(|h| { h.tramos.pop(); })
+    }
+
+    /// Comprueba que el tramo puede seguir al anterior y que sus meses,
+    /// su intervalo de revisiones y sus límites son válidos
+    fn valida_tramo(&self, anterior: Option<&TramoInteres>, tramo: &TramoInteres) -> Result<()> {
+        if anterior.is_some_and(|t| t.meses.is_none()) {
+            return Err(HipotecaError::entrada_invalida("tramo", 
+                "el tramo anterior dura hasta el final del préstamo"));
+        }
+        if tramo.meses.is_some_and(|m| m <= 0) {
+            return Err(HipotecaError::entrada_invalida("tramo", "los meses deben ser mayores que cero"));
+        }
+        if tramo.intervalo_revisiones.is_some_and(|m| m <= 0) {
+            return Err(HipotecaError::entrada_invalida("tramo", 
+                "el intervalo de revisiones debe ser mayor que cero"));
+        }
+        if tramo.i_min.unwrap_or(self.i_min) > tramo.i_max.unwrap_or(self.i_max) {
+            return Err(HipotecaError::entrada_invalida("tramo", "el tipo mínimo es mayor que el máximo"));
+        }
+        Ok(())
+    }
+
+    /// Número de la primera cuota de cada tramo de interés
+    fn inicio_tramos(&self) -> Vec<(i32, TramoInteres)> {
+        let mut inicio = 0;
+        self.tramos.iter().map(|t| {
+            let tramo = (inicio, *t);
+            inicio += t.meses.unwrap_or(0);
+            tramo
+        }).collect()
+    }
+
+    /// Añade un periodo de carencia y vuelve a calcular las tablas de
+    /// amortización. La carencia debe terminar antes del último vencimiento
+    pub fn add_carencia(&mut self, carencia: Carencia) -> Result<()> {
+        self.valida_carencia(&carencia)?;
+        let carencias = self.carencias.clone();
+        self.carencias.push(carencia);
+        self.carencias.sort_by_key(|c| c.fecha);
+        self.recalcula_o_deshace(|h| h.carencias = carencias)
+    }
+
+    /// Comprueba que la carencia tiene algún mes y termina antes del
+    /// último vencimiento
+    fn valida_carencia(&self, carencia: &Carencia) -> Result<()> {
+        if carencia.meses <= 0 {
+            return Err(HipotecaError::entrada_invalida("carencia", "los meses deben ser mayores que cero"));
+        }
+        if self.primera_cuota_desde(carencia.fecha) + carencia.meses >= self.meses {
+            return Err(HipotecaError::entrada_invalida("carencia", 
+                "debe terminar antes del último vencimiento"));
+        }
+        Ok(())
+    }
+
+    /// Número de la primera cuota que vence en la fecha indicada o después
+    fn primera_cuota_desde(&self, fecha: Date<Utc>) -> i32 {
+        let mut n = 0;
+        while n < self.meses && self.fecha_vencimiento(n) < fecha {
+            n += 1;
+        }
+        n
+    }
+
+    /// Primera cuota, número de cuotas y tipo de cada periodo de carencia
+    fn rangos_carencia(&self) -> Vec<(i32, i32, TipoCarencia)> {
+        self.carencias.iter()
+            .map(|c| (self.primera_cuota_desde(c.fecha), c.meses, c.tipo))
+            .collect()
+    }
+
+    /// Cuota que vence en `fecha` dentro de un periodo de carencia, con
+    /// los intereses devengados desde el vencimiento anterior
+    fn cuota_carencia(&self, tipo_carencia: TipoCarencia, desde: Date<Utc>, fecha: Date<Utc>,
+            capital_pendiente: Importe, tipo_interes: f64, meses_restantes: i32) -> Cuota {
+        let intereses = self.base_calculo.intereses(self.redondeo, capital_pendiente, 
+            tipo_interes, desde, fecha);
+        Cuota::new_carencia(fecha, tipo_interes, meses_restantes, capital_pendiente, intereses,
+            tipo_carencia == TipoCarencia::Total)
+    }
+
+    /// Devuelve una copia de las amortizaciones anticipadas ordenadas por fecha
+    fn amortizaciones_ordenadas(&self) -> Vec<AmortizacionAnticipada> {
+        let mut amortizaciones = self.amortizaciones_anticipadas.clone();
+        amortizaciones.sort_by_key(|a| a.fecha);
+        amortizaciones
+    }
+
+    /// Añade a la tabla la fila de una amortización anticipada, con su
+    /// comisión, y recalcula la cuota o el plazo según la modalidad elegida.
+    /// El capital amortizado paga en esa fila los intereses devengados desde
+    /// el último vencimiento (`desde`), porque la cuota siguiente solo los
+    /// calcula sobre el capital que queda pendiente.
+    /// Si se amortiza todo el capital pendiente no quedan meses restantes.
+    /// `n` es el número de la cuota siguiente a la amortización
+    #[allow(clippy::too_many_arguments)]
+    fn aplica_amortizacion_anticipada(&self, amortizacion: &AmortizacionAnticipada, 
+            tabla: &mut TablaAmortizacion, desde: Date<Utc>, n: i32, tipo_interes: f64, 
+            cap_pendiente: &mut Importe, meses_restantes: &mut i32, cuota_total: &mut Importe) {
+        let importe = amortizacion.importe.min(*cap_pendiente);
+        let intereses = self.base_calculo.intereses(self.redondeo, importe, tipo_interes, 
+            desde, amortizacion.fecha);
+        let comision = self.comision_amortizacion.importe(self.redondeo, self.fecha_escritura, 
+            amortizacion.fecha, importe);
+        tabla.push(Cuota::new_amortizacion_anticipada(amortizacion.fecha, tipo_interes,
+            *meses_restantes, *cap_pendiente, importe, intereses, comision));
+        *cap_pendiente = self.redondeo.importe(*cap_pendiente - importe);
+        if *cap_pendiente <= Importe::ZERO {
+            *meses_restantes = 0;
+            return;
+        }
+        match amortizacion.modalidad {
+            ModalidadAmortizacion::ReducirCuota => {
+                *cuota_total = self.sistema_amortizacion.cuota_base(self.redondeo, *cap_pendiente, 
+                    tipo_interes, *meses_restantes, n);
+            },
+            ModalidadAmortizacion::ReducirPlazo => {
+                *meses_restantes = self.sistema_amortizacion.meses_para_amortizar(self.redondeo, 
+                    *cap_pendiente, tipo_interes, *cuota_total, n, *meses_restantes);
+            },
         }
-        tabla
     }
 
     /// Calcula una tabla de amortización para los meses iniciales, 
@@ -152,53 +718,553 @@ impl Hipoteca {
     /// del euribor
     fn calcula_amort_primer_periodo(&mut self) -> TablaAmortizacion {
         let mut tabla = TablaAmortizacion::new();
-        let cuota_total = importe_cuota_mensual(self.capital_prestado, self.tipo_interes_anual, self.meses);
-        let mut capital_pendiente: f64 = self.capital_prestado;
-        let mut fecha_cuota: Date<Utc> = add_one_month(self.fecha_escritura);            
+        let cuota_total = self.sistema_amortizacion.cuota_base(self.redondeo, self.capital_prestado, 
+            self.tipo_interes_anual, self.meses, 0);
+        let mut capital_pendiente: Importe = self.capital_prestado;
+        let mut fecha_anterior: Date<Utc> = self.fecha_escritura;
         let mut meses_restantes_antes = self.meses;
-        for _i in 0..self.meses_hasta_primera_revision {
+        for n_vencim in 0..self.meses_hasta_primera_revision {
+            let fecha_cuota = self.fecha_vencimiento(n_vencim);
             if fecha_cuota > self.fecha_ultimo_vencimiento {
                 return tabla
             }
-            let cuota_interes = redondea_dos_decimales(capital_pendiente*self.tipo_interes_anual/12.0);
-            let cuota_capital = redondea_dos_decimales(cuota_total-cuota_interes);
+            let (total, cuota_capital, cuota_interes) = self.reparte_cuota(fecha_anterior, fecha_cuota, 
+                n_vencim, capital_pendiente, self.tipo_interes_anual, cuota_total, meses_restantes_antes);
             let cuota: Cuota = Cuota::new(fecha_cuota, self.tipo_interes_anual, meses_restantes_antes,
-                capital_pendiente,cuota_total, cuota_capital, cuota_interes);
+                capital_pendiente, total, cuota_capital, cuota_interes);
             tabla.push(cuota);
-            capital_pendiente = redondea_dos_decimales(capital_pendiente - cuota_capital);
-            fecha_cuota = add_one_month(fecha_cuota);
+            capital_pendiente = self.redondeo.importe(capital_pendiente - cuota_capital);
+            fecha_anterior = fecha_cuota;
             meses_restantes_antes -= 1;
         }
         tabla
     }
 
+    /// Indica si el primer periodo no es de un mes exacto, porque la
+    /// primera cuota no vence un mes después de la escritura
+    pub fn primer_periodo_irregular(&self) -> bool {
+        self.fecha_primera_cuota != add_one_month(self.fecha_escritura)
+    }
+
+    /// Cambia la fecha de vencimiento de la primera cuota y vuelve a
+    /// calcular las tablas de amortización. Las cuotas siguientes vencen
+    /// de mes en mes a partir de ella
+    pub fn set_fecha_primera_cuota(&mut self, fecha: Date<Utc>) -> Result<()> {
+        self.valida_fecha_primera_cuota(fecha)?;
+        self.fecha_primera_cuota = fecha;
+        self.recalcula_tablas()
+    }
+
+    /// Comprueba que la primera cuota vence después de la escritura
+    fn valida_fecha_primera_cuota(&self, fecha: Date<Utc>) -> Result<()> {
+        if fecha <= self.fecha_escritura {
+            return Err(HipotecaError::entrada_invalida("fecha_primera_cuota", 
+                "debe ser posterior a la fecha de la escritura"));
+        }
+        Ok(())
+    }
+
+    /// Fecha que fija el día de vencimiento de las cuotas: la escritura si la
+    /// primera cuota vence ese mismo día (o el último del mes, si es más
+    /// corto) y, si no, la primera cuota
+    fn fecha_ancla(&self) -> Date<Utc> {
+        let primera = self.fecha_primera_cuota;
+        if primera == fecha_mes(primera.year(), primera.month(), self.fecha_escritura.day()) {
+            self.fecha_escritura
+        } else {
+            primera
+        }
+    }
+
+    /// Día del mes en que vencen las cuotas. En los meses más cortos
+    /// vencen el último día del mes
+    pub fn dia_vencimiento(&self) -> u32 {
+        self.fecha_ancla().day()
+    }
+
+    /// Fecha de vencimiento pactada de la cuota `n` (la primera es la 0),
+    /// antes de ajustarla por días inhábiles
+    pub fn fecha_vencimiento_nominal(&self, n: i32) -> Date<Utc> {
+        let primera = self.fecha_primera_cuota;
+        let mes = add_n_months(Utc.ymd(primera.year(), primera.month(), 1), n);
+        let ancla = self.fecha_ancla();
+        if self.fin_de_mes && last_day_of_month(ancla.year(), ancla.month()) == Ok(ancla.day()) {
+            fecha_mes(mes.year(), mes.month(), 31)
+        } else {
+            fecha_mes(mes.year(), mes.month(), ancla.day())
+        }
+    }
+
+    /// Fecha en que vence la cuota `n` (la primera es la 0), ajustada
+    /// si cae en día inhábil
+    pub fn fecha_vencimiento(&self, n: i32) -> Date<Utc> {
+        self.calendario.ajusta(self.fecha_vencimiento_nominal(n), self.ajuste_dia_habil)
+    }
+
+    /// Reparte en capital e intereses la cuota `n` (la primera es la 0),
+    /// que vence en `fecha`, a partir de la cuota de referencia del sistema
+    /// de amortización. Devuelve el importe total, el de capital y el de
+    /// intereses, que se calculan con la base de cálculo de la hipoteca.
+    /// Si el primer periodo es irregular, la primera cuota amortiza el
+    /// mismo capital que una cuota ordinaria y sus intereses se calculan
+    /// por los días transcurridos desde la escritura
+    #[allow(clippy::too_many_arguments)]
+    fn reparte_cuota(&self, desde: Date<Utc>, fecha: Date<Utc>, n: i32, capital_pendiente: Importe, 
+            tipo_interes: f64, cuota_base: Importe, meses_restantes: i32) -> (Importe, Importe, Importe) {
+        let cuota_interes = self.base_calculo.intereses(self.redondeo, capital_pendiente, 
+            tipo_interes, desde, fecha);
+        let intereses_reparto = if desde != self.fecha_escritura || !self.primer_periodo_irregular() {
+            cuota_interes
+        } else {
+            self.redondeo.intereses_mes(capital_pendiente, tipo_interes)
+        };
+        let cuota_capital = self.sistema_amortizacion.cuota_capital(self.redondeo, cuota_base, n,
+            intereses_reparto, capital_pendiente, meses_restantes);
+        (self.redondeo.importe(cuota_capital + cuota_interes), cuota_capital, cuota_interes)
+    }
+
     /// Calcula la tabla de amortización desde el momento del 
-    /// impago hasta la fecha de resolución de la hipoteca
-    pub fn calcula_tabla_impago(&mut self) -> TablaAmortizacion  {
+    /// impago hasta la fecha de resolución de la hipoteca. La última
+    /// línea es el capital pendiente que vence con la resolución, con
+    /// los intereses corridos desde el último vencimiento, también si
+    /// la resolución coincide con el vencimiento de una cuota
+    pub fn calcula_tabla_impago(&mut self) -> Result<TablaAmortizacion>  {
         let mut t  =  TablaAmortizacion::new();
-        println!("tabla_amort:{}", self.tabla_amort_con_actualizacion_euribor.cuotas.len());
-        let cuotas_impago = self.tabla_amort_con_actualizacion_euribor
+        self.tabla_amort_con_actualizacion_euribor
             .cuotas.iter()
             .filter(|x| x.fecha>=self.fecha_impago && x.fecha<=self.fecha_resolucion)
+            .filter(|x| !x.amortizacion_anticipada)
             .for_each(|x| t.cuotas.push(x.clone()));
-        println!("impagos:{}", t.cuotas.len());
-        let ultima_cuota: &Cuota = t.cuotas.last().unwrap();
-        if ultima_cuota.fecha < self.fecha_resolucion {
-            let cap_pdte = ultima_cuota.cap_pendiente_despues();
-            let dias = (self.fecha_resolucion - ultima_cuota.fecha).num_days();
-            let intereses = redondea_dos_decimales(cap_pdte * dias as f64 * ultima_cuota.i / 365.0);
-            let cuota_total = cap_pdte + intereses;
-            let cuota = Cuota::new(self.fecha_resolucion, ultima_cuota.i,ultima_cuota.meses_restantes_antes,
-                cap_pdte, cuota_total, cap_pdte, intereses);
-            t.push(cuota);
-        }
-        t
+        let ultima_cuota: &Cuota = t.cuotas.last().ok_or(HipotecaError::SinCuotasImpago {
+            fecha_impago: self.fecha_impago, fecha_resolucion: self.fecha_resolucion })?;
+        let meses_restantes = ultima_cuota.meses_restantes_antes;
+        // El último movimiento puede ser una amortización anticipada posterior
+        // a la última cuota
+        if let Some(ultimo) = self.ultima_cuota_hasta(self.fecha_resolucion) {
+            let cap_pdte = ultimo.cap_pendiente_despues();
+            if !cap_pdte.is_zero() {
+                let intereses = self.base_calculo.intereses(self.redondeo, cap_pdte, ultimo.i,
+                    ultimo.fecha, self.fecha_resolucion);
+                let cuota_total = cap_pdte + intereses;
+                let cuota = Cuota::new(self.fecha_resolucion, ultimo.i, meses_restantes,
+                    cap_pdte, cuota_total, cap_pdte, intereses);
+                t.push(cuota);
+            }
+        }
+        Ok(t)
+    }
+
+    /// Último movimiento de la tabla con actualización del euribor, cuota
+    /// o amortización anticipada, con fecha igual o anterior a la indicada
+    fn ultima_cuota_hasta(&self, fecha: Date<Utc>) -> Option<&Cuota> {
+        self.tabla_amort_con_actualizacion_euribor.cuotas.iter().rev().find(|x| x.fecha <= fecha)
+    }
+
+    /// Añade un pago realizado por el deudor después del impago
+    pub fn add_pago(&mut self, pago: Pago) -> Result<()> {
+        if pago.importe <= Importe::ZERO {
+            return Err(HipotecaError::entrada_invalida("pago", "el importe debe ser mayor que cero"));
+        }
+        self.pagos.push(pago);
+        self.pagos.sort_by_key(|p| p.fecha);
+        Ok(())
+    }
+
+    /// Añade un gasto repercutido al deudor por la entidad
+    pub fn add_gasto(&mut self, gasto: Gasto) -> Result<()> {
+        if gasto.importe <= Importe::ZERO {
+            return Err(HipotecaError::entrada_invalida("gasto", "el importe debe ser mayor que cero"));
+        }
+        self.gastos.push(gasto);
+        self.gastos.sort_by_key(|g| g.fecha);
+        Ok(())
+    }
+
+    /// Calcula la posición deudora en una fecha: las cuotas vencidas desde
+    /// el impago (y el capital vencido en la resolución), los gastos y los
+    /// intereses de demora del principal pendiente, descontados los pagos
+    /// realizados según el orden de imputación
+    pub fn calcula_posicion_deudora(&mut self, fecha: Date<Utc>) -> Result<PosicionDeudora> {
+        if self.orden_imputacion.is_empty() {
+            return Err(HipotecaError::entrada_invalida("orden_imputacion", "no se indicó ningún concepto"));
+        }
+        let il = InteresLegal::new();
+        let tabla = self.calcula_tabla_impago()?;
+        let cuotas = tabla.cuotas.iter()
+            .map(|x| DeudaCuota::new(x.fecha, x.i, x.cuota_capital, x.cuota_interes))
+            .collect();
+        // Tipo ordinario de cada periodo, que rige hasta su vencimiento
+        let tipos: Vec<(Date<Utc>, f64)> = self.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .filter(|x| !x.amortizacion_anticipada)
+            .map(|x| (x.fecha, x.i))
+            .collect();
+        let tipo_ordinario = |fecha: Date<Utc>| tipos.get(tipos.partition_point(|x| x.0 <= fecha))
+            .or(tipos.last()).map_or(self.tipo_interes_anual, |x| x.1);
+        let demora = |principal, desde, hasta| intereses_demora(&il, self.base_calculo, self.redondeo,
+            self.fecha_escritura, self.interes_demora, tipo_ordinario, principal, desde, hasta);
+        Ok(PosicionDeudora::calcula(fecha, cuotas, &self.gastos, &self.pagos, 
+            &self.orden_imputacion, demora))
+    }
+
+    /// Calcula la liquidación del impago a la fecha de resolución:
+    /// principal vencido, intereses ordinarios e intereses de demora
+    /// de cada cuota impagada desde su vencimiento, con los límites 
+    /// legales que correspondan a la fecha de la escritura. Se descuentan
+    /// los pagos posteriores al impago y se omiten las cuotas ya pagadas
+    pub fn calcula_liquidacion_impago(&mut self) -> Result<LiquidacionImpago> {
+        let posicion = self.calcula_posicion_deudora(self.fecha_resolucion)?;
+        let mut liquidacion = LiquidacionImpago::new(self.fecha_resolucion);
+        liquidacion.gastos = posicion.total_gastos();
+        for cuota in posicion.cuotas.iter().filter(|x| !x.total().is_zero()) {
+            liquidacion.push(LineaImpago {
+                fecha: cuota.fecha,
+                tipo_interes: cuota.tipo_interes,
+                capital: cuota.capital,
+                intereses_ordinarios: cuota.intereses_ordinarios,
+                dias_demora: (self.fecha_resolucion - cuota.fecha).num_days(),
+                intereses_demora: cuota.intereses_demora,
+            });
+        }
+        Ok(liquidacion)
+    }
+
+    /// Calcula la liquidación para la ejecución hipotecaria a la fecha de
+    /// resolución a partir de la liquidación del impago: capital vencido con
+    /// la resolución, capital de las cuotas impagadas, intereses ordinarios,
+    /// intereses de demora y gastos, frente a la responsabilidad hipotecaria
+    pub fn calcula_liquidacion_ejecucion(&mut self) -> Result<LiquidacionEjecucion> {
+        let liquidacion = self.calcula_liquidacion_impago()?;
+        // Los pagos se imputan antes al capital de las cuotas vencidas
+        let capital_pendiente = self.ultima_cuota_hasta(self.fecha_resolucion)
+            .map_or(self.capital_prestado, |x| x.cap_pendiente_despues())
+            .min(liquidacion.total_capital());
+        Ok(LiquidacionEjecucion {
+            fecha: self.fecha_resolucion,
+            capital_pendiente,
+            capital_cuotas_vencidas: self.redondeo.importe(liquidacion.total_capital() - capital_pendiente),
+            intereses_ordinarios: liquidacion.total_intereses_ordinarios(),
+            intereses_demora: liquidacion.total_intereses_demora(),
+            gastos: liquidacion.gastos,
+            responsabilidad: self.responsabilidad_hipotecaria,
+        })
+    }
+
+    /// Simula el resultado de la subasta de la finca: deuda remanente o
+    /// sobrante tras la adjudicación, partiendo de la deuda a la fecha de
+    /// resolución según la liquidación para la ejecución. Las costas se
+    /// limitan a lo que permite esa liquidación
+    pub fn simula_subasta(&mut self, subasta: Subasta, costas: Importe) -> Result<ResultadoSubasta> {
+        if subasta.valor_tasacion <= Importe::ZERO {
+            return Err(HipotecaError::entrada_invalida("valor_tasacion", "debe ser mayor que cero"));
+        }
+        if !(subasta.porcentaje_adjudicacion > 0.0 && subasta.porcentaje_adjudicacion <= 1.0) {
+            return Err(HipotecaError::entrada_invalida("porcentaje_adjudicacion", 
+                "debe ser mayor que cero y no superar el 100%"));
+        }
+        if subasta.importe_adjudicacion < Importe::ZERO || costas < Importe::ZERO {
+            return Err(HipotecaError::entrada_invalida("subasta", "los importes no pueden ser negativos"));
+        }
+        if subasta.fecha < self.fecha_resolucion {
+            return Err(HipotecaError::entrada_invalida("subasta", "la adjudicación es anterior a la resolución"));
+        }
+        let liquidacion = self.calcula_liquidacion_ejecucion()?;
+        Ok(ResultadoSubasta { subasta, deuda: liquidacion.cantidad_reclamada(),
+            costas: costas.min(liquidacion.limite_costas()) })
+    }
+
+    /// Calcula el importe necesario para cancelar el préstamo en una fecha:
+    /// capital pendiente tras el último vencimiento, intereses corridos por
+    /// días desde ese vencimiento, deuda vencida desde el impago y comisión
+    /// por amortización anticipada. Desde la resolución todo el capital está
+    /// vencido y solo hay deuda vencida
+    pub fn calcula_cancelacion(&mut self, fecha: Date<Utc>) -> Result<Cancelacion> {
+        if fecha < self.fecha_escritura {
+            return Err(HipotecaError::entrada_invalida("fecha", "es anterior a la fecha de la escritura"));
+        }
+        let anterior = self.ultima_cuota_hasta(fecha);
+        let siguiente = self.tabla_amort_con_actualizacion_euribor.cuotas.iter().find(|x| x.fecha > fecha && !x.amortizacion_anticipada);
+        let (fecha_ultimo_vencimiento, mut capital_pendiente) = match anterior {
+            Some(cuota) => (cuota.fecha, cuota.cap_pendiente_despues()),
+            None => (self.fecha_escritura, self.capital_prestado),
+        };
+        let tipo_interes = siguiente.or(anterior).map(|x| x.i).unwrap_or(self.tipo_interes_anual);
+        if fecha >= self.fecha_resolucion {
+            capital_pendiente = Importe::ZERO;
+        }
+        let intereses_corridos = self.base_calculo.intereses(self.redondeo, capital_pendiente, 
+            tipo_interes, fecha_ultimo_vencimiento, fecha);
+        let comision = self.comision_amortizacion.importe(self.redondeo, self.fecha_escritura, fecha, capital_pendiente);
+        let deuda_vencida = if fecha >= self.fecha_impago {
+            match self.calcula_posicion_deudora(fecha) {
+                Ok(posicion) => posicion.total(),
+                Err(HipotecaError::SinCuotasImpago { .. }) => Importe::ZERO,
+                Err(e) => return Err(e),
+            }
+        } else {
+            Importe::ZERO
+        };
+        Ok(Cancelacion { fecha, fecha_ultimo_vencimiento, tipo_interes, capital_pendiente,
+            intereses_corridos, deuda_vencida, comision })
+    }
+
+    /// Comprueba cuándo podía la entidad dar por vencido el préstamo según el
+    /// art. 24 de la Ley 5/2019. Recorre las cuotas desde el impago sumando
+    /// las vencidas y descontando los pagos realizados, que se aplican a las
+    /// cuotas más antiguas, hasta alcanzar el umbral que corresponde a la
+    /// mitad del plazo en que empezó la mora. Compara el resultado con la
+    /// fecha de resolución
+    pub fn comprueba_vencimiento_anticipado(&self) -> Result<VencimientoAnticipado> {
+        let cuotas: Vec<&Cuota> = self.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .filter(|x| x.fecha >= self.fecha_impago && !x.amortizacion_anticipada)
+            .collect();
+        let primera = cuotas.first().ok_or(HipotecaError::SinCuotasImpago {
+            fecha_impago: self.fecha_impago, fecha_resolucion: self.fecha_resolucion })?;
+        let primera_mitad = primera.fecha < self.fecha_vencimiento(self.meses / 2);
+        let (proporcion, umbral_cuotas) = if primera_mitad {
+            (UMBRAL_PRIMERA_MITAD, CUOTAS_PRIMERA_MITAD)
+        } else {
+            (UMBRAL_SEGUNDA_MITAD, CUOTAS_SEGUNDA_MITAD)
+        };
+        let umbral_importe = self.redondeo.importe(self.capital_prestado * tipo(proporcion));
+        let mut vencimiento = VencimientoAnticipado { fecha_inicio_mora: primera.fecha, primera_mitad,
+            umbral_importe, umbral_cuotas, lineas: Vec::new(), fecha_umbral: None,
+            fecha_resolucion: self.fecha_resolucion };
+        for (k, cuota) in cuotas.iter().enumerate() {
+            let pagado: Importe = self.pagos.iter().filter(|x| x.fecha <= cuota.fecha).map(|x| x.importe).sum();
+            let vencidas = &cuotas[..=k];
+            let total_vencido: Importe = vencidas.iter().map(|x| x.cuota_total).sum();
+            let total_impagado = self.redondeo.importe((total_vencido - pagado).max(Importe::ZERO));
+            // Los pagos cubren por completo las cuotas más antiguas
+            let mut restante = pagado;
+            let cubiertas = vencidas.iter().take_while(|x| {
+                restante -= x.cuota_total;
+                restante >= Importe::ZERO
+            }).count();
+            let cuotas_impagadas = (vencidas.len() - cubiertas) as i32;
+            vencimiento.lineas.push(LineaVencimiento { fecha: cuota.fecha, cuota: cuota.cuota_total,
+                total_impagado, cuotas_impagadas });
+            if total_impagado >= umbral_importe || cuotas_impagadas >= umbral_cuotas {
+                vencimiento.fecha_umbral = Some(cuota.fecha);
+                break;
+            }
+        }
+        Ok(vencimiento)
+    }
+
+    /// Calcula las cantidades a devolver por la nulidad de la cláusula suelo.
+    /// Compara cuota a cuota la tabla cobrada por la entidad con la que resulta
+    /// sin tipo mínimo, para las cuotas pagadas antes del impago y de la fecha
+    /// de liquidación, y añade los intereses legales de cada diferencia desde
+    /// la fecha de pago hasta la de liquidación
+    pub fn calcula_devolucion_clausula_suelo(&mut self, mantener_techo: bool, 
+            fecha_liquidacion: Date<Utc>) -> Result<ComparacionTablas> {
+        let il = InteresLegal::new();
+        let tabla_sin_suelo = self.calcula_tabla_amort_sin_suelo(mantener_techo)?;
+        let hasta = fecha_liquidacion.min(self.fecha_impago.pred());
+        let mut devolucion = ComparacionTablas::compara(&self.tabla_amort_con_actualizacion_euribor,
+            &tabla_sin_suelo, hasta);
+        devolucion.lineas.retain(|x| !x.diferencia().is_zero());
+        devolucion.calcula_intereses_legales(&il, fecha_liquidacion);
+        Ok(devolucion)
+    }
+
+    /// Calcula la reclamación por sustitución del índice de la escritura
+    /// (normalmente un IRPH) por otro índice más un diferencial, por ejemplo
+    /// el euribor con el diferencial que ofrecía la entidad en esa fecha.
+    /// Compara las cuotas pagadas antes del impago y hasta la fecha de
+    /// liquidación con las que resultan del nuevo índice
+    pub fn calcula_sustitucion_indice(&mut self, indice: IndiceReferencia, diferencial: f64,
+            fecha_liquidacion: Date<Utc>) -> Result<SustitucionIndice> {
+        let tabla = self.calcula_tabla_amort_con_indice(indice, diferencial)?;
+        let hasta = std::cmp::min(self.fecha_impago.pred(), fecha_liquidacion);
+        let comparacion = ComparacionTablas::compara(&self.tabla_amort_con_actualizacion_euribor,
+            &tabla, hasta);
+        Ok(SustitucionIndice { indice, diferencial, tabla, comparacion })
+    }
+    /// Compara las cuotas pagadas antes del impago y hasta la fecha de
+    /// liquidación calculadas con la base de cálculo que aplicó la entidad
+    /// con las que resultan de la base pactada en la escritura
+    pub fn calcula_comparacion_base_calculo(&mut self, base_entidad: BaseCalculo,
+            fecha_liquidacion: Date<Utc>) -> Result<ComparacionBaseCalculo> {
+        let base_contractual = self.base_calculo;
+        self.base_calculo = base_entidad;
+        let tabla = self.calcula_tabla_amort_con_actualizacion_euribor();
+        self.base_calculo = base_contractual;
+        let tabla = tabla?;
+        let hasta = std::cmp::min(self.fecha_impago.pred(), fecha_liquidacion);
+        let comparacion = ComparacionTablas::compara(&tabla, 
+            &self.tabla_amort_con_actualizacion_euribor, hasta);
+        Ok(ComparacionBaseCalculo { base_entidad, base_contractual, tabla, comparacion })
+    }
+    /// Concilia los recibos cobrados por la entidad con las cuotas de la
+    /// tabla con actualización del índice
+    pub fn concilia_recibos(&self, recibos: &[ReciboBanco]) -> Conciliacion {
+        Conciliacion::concilia(&self.tabla_amort_con_actualizacion_euribor, recibos)
+    }
+    /// Calcula lo cobrado de más por la cláusula de redondeo del tipo
+    /// revisado: compara las cuotas pagadas antes del impago y hasta la
+    /// fecha de liquidación con las que resultan del redondeo alternativo
+    pub fn calcula_coste_redondeo(&mut self, redondeo_alternativo: RedondeoTipo,
+            fecha_liquidacion: Date<Utc>) -> Result<CosteRedondeo> {
+        let redondeo_pactado = self.redondeo_tipo;
+        self.redondeo_tipo = redondeo_alternativo;
+        let tabla = self.calcula_tabla_amort_con_actualizacion_euribor();
+        self.redondeo_tipo = redondeo_pactado;
+        let tabla = tabla?;
+        let hasta = std::cmp::min(self.fecha_impago.pred(), fecha_liquidacion);
+        let comparacion = ComparacionTablas::compara(&self.tabla_amort_con_actualizacion_euribor,
+            &tabla, hasta);
+        Ok(CosteRedondeo { redondeo_pactado, redondeo_alternativo, tabla, comparacion })
+    }
+
+    /// Simula la reestructuración del Código de Buenas Prácticas (RDL 6/2012)
+    /// desde una fecha: carencia de capital de cinco años con el diferencial
+    /// reducido a euribor más 0,25, que después vuelve al vigente, y plazo
+    /// ampliado hasta cuarenta años desde la concesión. Comprueba también
+    /// los umbrales de exclusión con la cuota vigente
+    pub fn simula_buenas_practicas(&self, fecha: Date<Utc>, 
+            solicitud: &SolicitudBuenasPracticas) -> Result<ReestructuracionBuenasPracticas> {
+        let anterior = self.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .find(|x| x.fecha >= fecha && !x.amortizacion_anticipada)
+            .ok_or_else(|| HipotecaError::entrada_invalida("fecha", "no quedan cuotas por vencer"))?;
+        let cuota_anterior = anterior.cuota_total;
+        let n = self.primera_cuota_desde(fecha);
+        let meses_restantes = std::cmp::max(PLAZO_BUENAS_PRACTICAS - n, anterior.meses_restantes_antes);
+        if meses_restantes <= MESES_CARENCIA_BUENAS_PRACTICAS {
+            return Err(HipotecaError::entrada_invalida("fecha", 
+                "el plazo restante no admite la carencia de cinco años"));
+        }
+        let total_anterior = self.redondeo.importe(self.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .filter(|x| x.fecha >= anterior.fecha).map(|x| x.cuota_total).sum());
+        let hasta = self.tabla_amort_con_actualizacion_euribor.cuotas.last().map_or(fecha, |x| x.fecha);
+        let fin_carencia = self.fecha_vencimiento(n + MESES_CARENCIA_BUENAS_PRACTICAS);
+        let mut inicio = Novacion::new(fecha);
+        inicio.incremento_euribor = Some(DIFERENCIAL_BUENAS_PRACTICAS);
+        inicio.meses_restantes = Some(meses_restantes);
+        let mut fin = Novacion::new(fin_carencia);
+        fin.incremento_euribor = Some(self.diferencial_vigente(fecha));
+        // La reestructuración se calcula sobre una copia, que no modifica la hipoteca
+        let mut reestructurada = self.clone();
+        reestructurada.novaciones.push(inicio);
+        reestructurada.novaciones.push(fin);
+        reestructurada.novaciones.sort_by_key(|n| n.fecha);
+        reestructurada.carencias.push(Carencia::new(fecha, MESES_CARENCIA_BUENAS_PRACTICAS, TipoCarencia::Capital));
+        reestructurada.carencias.sort_by_key(|c| c.fecha);
+        let tabla = reestructurada.calcula_tabla_amort_con_actualizacion_euribor()?;
+        let cuota = |desde: Date<Utc>| tabla.cuotas.iter()
+            .find(|x| x.fecha >= desde && !x.amortizacion_anticipada)
+            .map_or(Importe::ZERO, |x| x.cuota_total);
+        let cuota_carencia = cuota(fecha);
+        let cuota_tras_carencia = cuota(fin_carencia);
+        let comparacion = ComparacionTablas::compara(&self.tabla_amort_con_actualizacion_euribor,
+            &tabla, hasta);
+        Ok(ReestructuracionBuenasPracticas { fecha, elegibilidad: solicitud.evalua(cuota_anterior),
+            cuota_anterior, cuota_carencia, cuota_tras_carencia, total_anterior, tabla, comparacion })
+    }
+
+    /// Simula las medidas del Código de Buenas Prácticas de 2022 (RDL 19/2022)
+    /// desde una fecha: la cuota se congela durante doce meses en el importe
+    /// de la última cuota anterior y el plazo se amplía en los meses indicados,
+    /// hasta siete años. Lo que deja de amortizarse devenga el tipo de cada
+    /// cuota menos medio punto y se suma al capital pendiente al terminar la
+    /// congelación, recalculando la cuota para el plazo restante
+    pub fn simula_congelacion_cuota(&self, fecha: Date<Utc>, 
+            meses_ampliacion: i32) -> Result<CongelacionCuota> {
+        if !(0..=MAX_MESES_AMPLIACION).contains(&meses_ampliacion) {
+            return Err(HipotecaError::entrada_invalida("meses_ampliacion", "debe estar entre 0 y 84"));
+        }
+        let cuotas = &self.tabla_amort_con_actualizacion_euribor.cuotas;
+        let primera = cuotas.iter().find(|x| x.fecha >= fecha && !x.amortizacion_anticipada)
+            .ok_or_else(|| HipotecaError::entrada_invalida("fecha", "no quedan cuotas por vencer"))?;
+        if primera.meses_restantes_antes + meses_ampliacion <= MESES_CONGELACION {
+            return Err(HipotecaError::entrada_invalida("fecha", 
+                "el plazo restante no admite la congelación de doce meses"));
+        }
+        let cuota_congelada = cuotas.iter().rev()
+            .find(|x| x.fecha < fecha && !x.amortizacion_anticipada)
+            .unwrap_or(primera).cuota_total;
+        let meses_restantes = primera.meses_restantes_antes + meses_ampliacion;
+        let total_anterior = self.redondeo.importe(cuotas.iter()
+            .filter(|x| x.fecha >= primera.fecha).map(|x| x.cuota_total).sum());
+        let n = self.primera_cuota_desde(fecha);
+        let fin_congelacion = self.fecha_vencimiento(n + MESES_CONGELACION - 1);
+        // La congelación se calcula sobre una copia, que no modifica la hipoteca
+        let mut congelada = self.clone();
+        if meses_ampliacion > 0 {
+            let mut ampliacion = Novacion::new(fecha);
+            ampliacion.meses_restantes = Some(meses_restantes);
+            congelada.novaciones.push(ampliacion);
+            congelada.novaciones.sort_by_key(|n| n.fecha);
+        }
+        let (tabla, capital_aplazado) = congelada.calcula_tabla_congelacion(fecha, fin_congelacion, cuota_congelada)?;
+        let hasta = tabla.cuotas.last().map_or(fecha, |x| x.fecha);
+        let comparacion = ComparacionTablas::compara(&self.tabla_amort_con_actualizacion_euribor,
+            &tabla, hasta);
+        Ok(CongelacionCuota { fecha, meses_ampliacion, cuota_congelada, capital_aplazado,
+            total_anterior, tabla, comparacion })
+    }
+
+    /// Tabla con la cuota congelada hasta la fecha indicada y capital aplazado
+    /// al terminar la congelación. Las cuotas congeladas muestran lo pagado y
+    /// el capital pendiente incluye lo aplazado hasta cada una
+    fn calcula_tabla_congelacion(&mut self, fecha: Date<Utc>, fin_congelacion: Date<Utc>,
+            cuota_congelada: Importe) -> Result<(TablaAmortizacion, Importe)> {
+        let tabla = self.calcula_tabla_amort_con_actualizacion_euribor()?;
+        let mut aplazado = Importe::ZERO;
+        let mut ajustes: Vec<(usize, Importe, Importe)> = Vec::new();
+        let mut anterior: Option<Date<Utc>> = None;
+        let congeladas = tabla.cuotas.iter().enumerate()
+            .filter(|(_, x)| x.fecha >= fecha && x.fecha <= fin_congelacion && !x.amortizacion_anticipada);
+        for (k, cuota) in congeladas {
+            if let Some(desde) = anterior {
+                let tipo_aplazado = (cuota.i - REDUCCION_TIPO_APLAZADO).max(0.0);
+                aplazado += self.base_calculo.intereses(self.redondeo, aplazado, tipo_aplazado, desde, cuota.fecha);
+            }
+            let exceso = (cuota.cuota_total - cuota_congelada).max(Importe::ZERO);
+            ajustes.push((k, aplazado, exceso));
+            aplazado = self.redondeo.importe(aplazado + exceso);
+            anterior = Some(cuota.fecha);
+        }
+        if aplazado.is_zero() {
+            return Ok((tabla, aplazado));
+        }
+        let mut fin = Novacion::new(fin_congelacion.succ());
+        fin.ampliacion_capital = aplazado;
+        self.novaciones.push(fin);
+        self.novaciones.sort_by_key(|n| n.fecha);
+        let mut tabla = self.calcula_tabla_amort_con_actualizacion_euribor()?;
+        for (k, aplazado_antes, exceso) in ajustes {
+            let cuota = &mut tabla.cuotas[k];
+            cuota.cuota_total = self.redondeo.importe(cuota.cuota_total - exceso);
+            cuota.cuota_capital = self.redondeo.importe(cuota.cuota_capital - exceso);
+            cuota.cap_pendiente_antes = self.redondeo.importe(cuota.cap_pendiente_antes + aplazado_antes);
+        }
+        Ok((tabla, aplazado))
+    }
+
+    /// Diferencial sobre el índice vigente en una fecha: el de la última
+    /// novación que lo cambió o, si no hay ninguna, el de la escritura
+    fn diferencial_vigente(&self, fecha: Date<Utc>) -> f64 {
+        self.novaciones.iter().rev()
+            .filter(|x| x.fecha <= fecha)
+            .find_map(|x| x.incremento_euribor)
+            .unwrap_or(self.incremento_euribor)
     }
 }
 
 
+/// Tipo de carencia de la cuota `n`, si está en alguno de los periodos
+/// de carencia (primera cuota, número de cuotas y tipo)
+fn carencia_cuota(carencias: &[(i32, i32, TipoCarencia)], n: i32) -> Option<TipoCarencia> {
+    carencias.iter()
+        .find(|(primera, meses, _)| n >= *primera && n < primera + meses)
+        .map(|x| x.2)
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::dec;
     #[test]
     fn test_pruebas() {
         let d1 = Utc.ymd(2022, 5, 12);
@@ -211,98 +1277,1133 @@ mod tests {
     fn test_calcula_tabla_impago() {
         let nombre = String::from("Prueba");
         let fecha = Utc.ymd(2004,3,17);
-        let mut h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));
-        h1.tabla_amort_impago = h1.calcula_tabla_impago();
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.tabla_amort_impago = h1.calcula_tabla_impago().unwrap();
         h1.tabla_amort_impago.disp();
     }
     #[test]
     fn test_hipoteca() {
         let nombre = String::from("Prueba");
         let fecha = Utc.ymd(2004,3,17);
-        let h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let _h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
     }
     #[test]
     fn test_importe_cuota_mensual() {
-        let x = importe_cuota_mensual(84140.0, 0.04, 300);
-        assert_eq!(444.12, x);
+        let x = importe_cuota_mensual(Redondeo::MitadArriba, dec!(84140), 0.04, 300);
+        assert_eq!(dec!(444.12), x);
     }
     #[test]
     fn test_calcula_tabla_amort_sin_actualizacion() {
         let nombre = String::from("Prueba");
         let fecha = Utc.ymd(2004,3,17);
-        let mut h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
         h1.tabla_amort_sin_actualizacion = h1.calcula_tabla_amort_sin_actualizacion();
         assert_eq!(300, h1.tabla_amort_sin_actualizacion.len());
         let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[0];
         assert_eq!(Utc.ymd(2004, 4, 17), cuota.fecha);
         assert_eq!(0.04, cuota.i);
         assert_eq!(300, cuota.meses_restantes_antes);
-        assert_eq!(444.12, cuota.cuota_total);
-        assert_eq!(163.65, cuota.cuota_capital);
-        assert_eq!(280.47, cuota.cuota_interes);
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(163.65), cuota.cuota_capital);
+        assert_eq!(dec!(280.47), cuota.cuota_interes);
         let mes = usize::try_from(h1.meses_hasta_primera_revision).ok().unwrap();
         let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[mes-1];
         assert_eq!(Utc.ymd(2004, 9, 17), cuota.fecha);
         assert_eq!(0.04, cuota.i);
         assert_eq!(295, cuota.meses_restantes_antes);
-        assert_eq!(444.12, cuota.cuota_total);
-        assert_eq!(166.40, cuota.cuota_capital);
-        assert_eq!(277.72, cuota.cuota_interes);
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(166.40), cuota.cuota_capital);
+        assert_eq!(dec!(277.72), cuota.cuota_interes);
         let mes = usize::try_from(h1.meses).ok().unwrap();
         let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[mes-1];
         assert_eq!(Utc.ymd(2029, 3, 17), cuota.fecha);
         assert_eq!(0.04, cuota.i);
         assert_eq!(1, cuota.meses_restantes_antes);
-        assert_eq!(445.12, cuota.cuota_total);
-        assert_eq!(443.64, cuota.cuota_capital);
-        assert_eq!(1.48, cuota.cuota_interes);
+        assert_eq!(dec!(445.12), cuota.cuota_total);
+        assert_eq!(dec!(443.64), cuota.cuota_capital);
+        assert_eq!(dec!(1.48), cuota.cuota_interes);
+    }
+    #[test]
+    fn test_redondeo_truncamiento() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.redondeo = Redondeo::Truncamiento;
+        h1.recalcula_tablas().unwrap();
+        let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[0];
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(280.46), cuota.cuota_interes);
+        assert_eq!(dec!(163.66), cuota.cuota_capital);
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
     }
     #[test]
     fn test_calcula_amort_primer_periodo() {
         let nombre = String::from("Prueba");
         let fecha = Utc.ymd(2004,3,17);
-        let mut h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
         h1.tabla_amort_sin_actualizacion = h1.calcula_amort_primer_periodo();
         assert_eq!(6, h1.tabla_amort_sin_actualizacion.len());
         let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[0];
         assert_eq!(Utc.ymd(2004, 4, 17), cuota.fecha);
         assert_eq!(0.04, cuota.i);
         assert_eq!(300, cuota.meses_restantes_antes);
-        assert_eq!(444.12, cuota.cuota_total);
-        assert_eq!(163.65, cuota.cuota_capital);
-        assert_eq!(280.47, cuota.cuota_interes);
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(163.65), cuota.cuota_capital);
+        assert_eq!(dec!(280.47), cuota.cuota_interes);
         let mes = usize::try_from(h1.meses_hasta_primera_revision).ok().unwrap();
         let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[mes-1];
         assert_eq!(Utc.ymd(2004, 9, 17), cuota.fecha);
         assert_eq!(0.04, cuota.i);
         assert_eq!(295, cuota.meses_restantes_antes);
-        assert_eq!(444.12, cuota.cuota_total);
-        assert_eq!(166.40, cuota.cuota_capital);
-        assert_eq!(277.72, cuota.cuota_interes);
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(166.40), cuota.cuota_capital);
+        assert_eq!(dec!(277.72), cuota.cuota_interes);
 
     }
+    #[test]
+    fn test_primer_periodo_irregular() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        assert!(!h1.primer_periodo_irregular());
+        assert!(h1.set_fecha_primera_cuota(fecha).is_err());
+        h1.set_fecha_primera_cuota(Utc.ymd(2004, 5, 1)).unwrap();
+        assert!(h1.primer_periodo_irregular());
+        assert_eq!(Utc.ymd(2029, 4, 1), h1.fecha_ultimo_vencimiento);
+        for tabla in [&h1.tabla_amort_sin_actualizacion, &h1.tabla_amort_con_actualizacion_euribor] {
+            // 44 días de intereses en la primera cuota (base 30/360)
+            let cuota: &Cuota = &tabla.cuotas[0];
+            assert_eq!(Utc.ymd(2004, 5, 1), cuota.fecha);
+            assert_eq!(dec!(411.35), cuota.cuota_interes);
+            assert_eq!(dec!(163.65), cuota.cuota_capital);
+            assert_eq!(dec!(575.00), cuota.cuota_total);
+            let cuota: &Cuota = &tabla.cuotas[1];
+            assert_eq!(Utc.ymd(2004, 6, 1), cuota.fecha);
+            assert_eq!(dec!(444.12), cuota.cuota_total);
+            assert_eq!(dec!(279.92), cuota.cuota_interes);
+            assert_eq!(300, tabla.len());
+            assert_eq!(Utc.ymd(2029, 4, 1), tabla.cuotas.last().unwrap().fecha);
+            assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        }
+        let cuota = h1.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .find(|x| x.fecha == Utc.ymd(2004, 11, 1)).unwrap();
+        // La primera revisión se cuenta desde la primera cuota
+        assert_eq!(ajusta_limites(h1.valor_indice(Utc.ymd(2004, 11, 1)).unwrap() + 0.01,
+            Some(0.04), Some(0.12)), cuota.i);
+    }
 
     #[test]
     fn test_calcula_tabla_amort_con_actualizacion_euribor() {
         let nombre = String::from("Prueba");
         let fecha = Utc.ymd(2004,3,17);
-        let mut h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));        
-        h1.tabla_amort_con_actualizacion_euribor = h1.calcula_tabla_amort_con_actualizacion_euribor();
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();        
+        h1.tabla_amort_con_actualizacion_euribor = h1.calcula_tabla_amort_con_actualizacion_euribor().unwrap();
         h1.tabla_amort_con_actualizacion_euribor.disp();
     }
+    #[test]
+    fn test_add_novacion() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let sin_novacion = h1.tabla_amort_con_actualizacion_euribor.cuotas.clone();
+        let mut novacion = Novacion::new(Utc.ymd(2015, 1, 1));
+        novacion.i_min = Some(0.0);
+        novacion.incremento_euribor = Some(0.0075);
+        novacion.meses_restantes = Some(240);
+        novacion.ampliacion_capital = dec!(10000);
+        h1.add_novacion(novacion).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        // Las cuotas anteriores a la novación no cambian
+        let n = tabla.cuotas.iter().position(|x| x.novacion).unwrap();
+        for (antes, despues) in sin_novacion.iter().zip(tabla.cuotas.iter()).take(n) {
+            assert_eq!(antes.cuota_total, despues.cuota_total);
+        }
+        let cuota = &tabla.cuotas[n];
+        assert_eq!(Utc.ymd(2015, 1, 17), cuota.fecha);
+        assert_eq!(240, cuota.meses_restantes_antes);
+        assert_eq!(sin_novacion[n].cap_pendiente_antes + dec!(10000), cuota.cap_pendiente_antes);
+        // Euribor de la revisión de octubre de 2014 (0,362%) más 0,75%
+        assert_eq!(0.01112, cuota.i);
+        assert_eq!(1, tabla.cuotas.iter().filter(|x| x.novacion).count());
+        assert_eq!(n + 240, tabla.len());
+        assert_eq!(Utc.ymd(2034, 12, 17), tabla.cuotas.last().unwrap().fecha);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_novacion_primer_periodo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let mut novacion = Novacion::new(Utc.ymd(2004, 6, 1));
+        novacion.tipo_interes_anual = Some(0.035);
+        h1.add_novacion(novacion).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert_eq!(300, tabla.len());
+        assert!(!tabla.cuotas[1].novacion);
+        assert_eq!(0.04, tabla.cuotas[1].i);
+        assert!(tabla.cuotas[2].novacion);
+        assert_eq!(Utc.ymd(2004, 6, 17), tabla.cuotas[2].fecha);
+        assert_eq!(0.035, tabla.cuotas[2].i);
+        assert_eq!(0.035, tabla.cuotas[5].i);
+        // En la primera revisión se vuelve al euribor más el incremento
+        assert_eq!(Utc.ymd(2004, 10, 17), tabla.cuotas[6].fecha);
+        assert_eq!(0.04, tabla.cuotas[6].i);
+    }
+    #[test]
+    fn test_amortizacion_anticipada_reducir_cuota() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.comision_amortizacion = ComisionAmortizacion::VariableTresAgnos;
+        h1.add_amortizacion_anticipada(AmortizacionAnticipada::new(Utc.ymd(2004, 9, 17), 
+            dec!(10000), ModalidadAmortizacion::ReducirCuota)).unwrap();
+        let tabla = &h1.tabla_amort_sin_actualizacion;
+        assert_eq!(301, tabla.len());
+        let amortizacion = &tabla.cuotas[6];
+        assert!(amortizacion.amortizacion_anticipada);
+        assert_eq!(dec!(83149.85), amortizacion.cap_pendiente_antes);
+        assert_eq!(dec!(10000), amortizacion.cuota_capital);
+        // Coincide con un vencimiento: no hay intereses desde la cuota anterior
+        assert_eq!(dec!(0), amortizacion.cuota_interes);
+        // Escritura de 2004: 0,5% de la Ley 41/2007
+        assert_eq!(dec!(50), amortizacion.comision);
+        assert_eq!(294, amortizacion.meses_restantes_antes);
+        let cuota = &tabla.cuotas[7];
+        assert_eq!(Utc.ymd(2004, 10, 17), cuota.fecha);
+        assert_eq!(dec!(73149.85), cuota.cap_pendiente_antes);
+        assert_eq!(importe_cuota_mensual(Redondeo::MitadArriba, dec!(73149.85), 0.04, 294), cuota.cuota_total);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        assert_eq!(Utc.ymd(2029, 3, 17), tabla.cuotas.last().unwrap().fecha);
+        assert_eq!(dec!(50), tabla.total_comisiones());
+    }
+    #[test]
+    fn test_amortizacion_anticipada_reducir_plazo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.add_amortizacion_anticipada(AmortizacionAnticipada::new(Utc.ymd(2004, 8, 1), 
+            dec!(20000), ModalidadAmortizacion::ReducirPlazo)).unwrap();
+        let tabla = &h1.tabla_amort_sin_actualizacion;
+        // Intereses del capital amortizado del 17/07 al 01/08 (14 días en 30/360)
+        let amortizacion = &tabla.cuotas[4];
+        assert_eq!(dec!(31.11), amortizacion.cuota_interes);
+        assert_eq!(dec!(20031.11), amortizacion.cuota_total);
+        let cuota = &tabla.cuotas[5];
+        assert_eq!(Utc.ymd(2004, 8, 17), cuota.fecha);
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert!(cuota.meses_restantes_antes < 296);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        assert!(tabla.cuotas.last().unwrap().fecha < Utc.ymd(2029, 3, 17));
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert!(tabla.cuotas[4].amortizacion_anticipada);
+        assert_eq!(dec!(0), tabla.cuotas[4].comision);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_amortizacion_anticipada_total() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        assert!(h1.add_amortizacion_anticipada(AmortizacionAnticipada::new(Utc.ymd(2010, 2, 1), 
+            dec!(0), ModalidadAmortizacion::ReducirCuota)).is_err());
+        assert!(h1.amortizaciones_anticipadas.is_empty());
+        h1.add_amortizacion_anticipada(AmortizacionAnticipada::new(Utc.ymd(2010, 2, 1), 
+            dec!(100000), ModalidadAmortizacion::ReducirCuota)).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        let ult_cuota = tabla.cuotas.last().unwrap();
+        assert!(ult_cuota.amortizacion_anticipada);
+        assert_eq!(Utc.ymd(2010, 2, 1), ult_cuota.fecha);
+        assert_eq!(ult_cuota.cap_pendiente_antes, ult_cuota.cuota_capital);
+        assert_eq!(dec!(0), ult_cuota.cap_pendiente_despues());
+    }
+    #[test]
+    fn test_calcula_liquidacion_impago() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.interes_demora = Some(0.19);
+        let tabla = h1.calcula_tabla_impago().unwrap();
+        let liquidacion = h1.calcula_liquidacion_impago().unwrap();
+        assert_eq!(tabla.len(), liquidacion.lineas.len());
+        let linea = &liquidacion.lineas[0];
+        assert_eq!(Utc.ymd(2018, 5, 17), linea.fecha);
+        assert_eq!(tabla.cuotas[0].cuota_capital, linea.capital);
+        assert_eq!(tabla.cuotas[0].cuota_interes, linea.intereses_ordinarios);
+        // Limitado a tres veces el interés legal: 9% hasta 2022, con la base 30/360 del contrato
+        assert_eq!((Utc.ymd(2022, 8, 5) - Utc.ymd(2018, 5, 17)).num_days(), linea.dias_demora);
+        assert_eq!(redondea_dos_decimales(linea.capital * dec!(0.09) * dec!(1518) / dec!(360)),
+            linea.intereses_demora);
+        // La última línea es el capital que vence con la resolución
+        let linea = liquidacion.lineas.last().unwrap();
+        assert_eq!(Utc.ymd(2022, 8, 5), linea.fecha);
+        assert_eq!(0, linea.dias_demora);
+        assert_eq!(dec!(0), linea.intereses_demora);
+        let total_capital: Importe = tabla.cuotas.iter().map(|x| x.cuota_capital).sum();
+        assert_eq!(redondea_dos_decimales(total_capital), liquidacion.total_capital());
+        assert_eq!(tabla.cuotas[0].cap_pendiente_antes, liquidacion.total_capital());
+    }
+    #[test]
+    fn test_pagos_impago() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.interes_demora = Some(0.19);
+        let sin_pagos = h1.calcula_liquidacion_impago().unwrap();
+        let tabla = h1.calcula_tabla_impago().unwrap();
+        let (primera, segunda) = (&tabla.cuotas[0], &tabla.cuotas[1]);
+        assert!(h1.add_pago(Pago { fecha: Utc.ymd(2018, 6, 17), importe: dec!(0) }).is_err());
+        h1.add_gasto(Gasto { fecha: Utc.ymd(2018, 6, 1), importe: dec!(50),
+            concepto: String::from("Reclamación de posiciones deudoras") }).unwrap();
+        h1.add_pago(Pago { fecha: Utc.ymd(2018, 6, 17), importe: primera.cuota_total }).unwrap();
+        let posicion = h1.calcula_posicion_deudora(Utc.ymd(2018, 6, 17)).unwrap();
+        // Gastos, demora de la primera cuota, intereses de las dos cuotas vencidas y capital
+        let demora = redondea_dos_decimales(primera.cuota_capital * dec!(0.09) * dec!(30) / dec!(360));
+        assert_eq!(dec!(50), posicion.total_imputado(ConceptoImputacion::Gastos));
+        assert_eq!(demora, posicion.total_imputado(ConceptoImputacion::InteresesDemora));
+        assert_eq!(primera.cuota_interes + segunda.cuota_interes,
+            posicion.total_imputado(ConceptoImputacion::InteresesOrdinarios));
+        let capital = primera.cuota_total - dec!(50) - demora - primera.cuota_interes - segunda.cuota_interes;
+        assert_eq!(capital, posicion.total_imputado(ConceptoImputacion::Capital));
+        assert_eq!(primera.cuota_capital - capital, posicion.cuotas[0].capital);
+        assert_eq!(dec!(0), posicion.total_gastos());
+        let liquidacion = h1.calcula_liquidacion_impago().unwrap();
+        assert_eq!(sin_pagos.lineas.len(), liquidacion.lineas.len());
+        assert_eq!(sin_pagos.total_capital() - capital, liquidacion.total_capital());
+        assert_eq!(sin_pagos.total_intereses_ordinarios() - primera.cuota_interes - segunda.cuota_interes,
+            liquidacion.total_intereses_ordinarios());
+        assert!(liquidacion.total_intereses_demora() < sin_pagos.total_intereses_demora());
+        // Con el orden inverso el pago se imputa primero al capital
+        h1.orden_imputacion.reverse();
+        let posicion = h1.calcula_posicion_deudora(Utc.ymd(2018, 6, 17)).unwrap();
+        assert_eq!(primera.cuota_total, posicion.total_imputado(ConceptoImputacion::Capital));
+        assert_eq!(dec!(50), posicion.total_gastos());
+    }
+    #[test]
+    fn test_calcula_cancelacion() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.comision_amortizacion = ComisionAmortizacion::VariableCincoAgnos;
+        assert!(h1.calcula_cancelacion(Utc.ymd(2004, 3, 1)).is_err());
+        // Antes de la primera cuota: capital prestado e intereses desde la escritura
+        let c = h1.calcula_cancelacion(Utc.ymd(2004, 4, 2)).unwrap();
+        assert_eq!(Utc.ymd(2004, 3, 17), c.fecha_ultimo_vencimiento);
+        assert_eq!(dec!(84140), c.capital_pendiente);
+        assert_eq!(dec!(140.23), c.intereses_corridos);
+        // Escritura anterior a la Ley 5/2019: 0,5% los cinco primeros años
+        assert_eq!(dec!(420.70), c.comision);
+        // Entre dos vencimientos, con el tipo del periodo en curso
+        let c = h1.calcula_cancelacion(Utc.ymd(2010, 2, 1)).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        let anterior = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2010, 1, 17)).unwrap();
+        let siguiente = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2010, 2, 17)).unwrap();
+        assert_eq!(anterior.cap_pendiente_despues(), c.capital_pendiente);
+        assert_eq!(siguiente.i, c.tipo_interes);
+        // 15 días naturales, 14 en base 30/360
+        assert_eq!(15, c.dias());
+        assert_eq!(redondea_dos_decimales(c.capital_pendiente * tipo(c.tipo_interes) * dec!(14) / dec!(360)),
+            c.intereses_corridos);
+        // Y 0,25% a partir del quinto año
+        assert_eq!(redondea_dos_decimales(c.capital_pendiente * dec!(0.0025)), c.comision);
+        assert_eq!(dec!(0), c.deuda_vencida);
+        // En un vencimiento no hay intereses corridos
+        let c = h1.calcula_cancelacion(Utc.ymd(2010, 1, 17)).unwrap();
+        assert_eq!(dec!(0), c.intereses_corridos);
+        assert_eq!(c.capital_pendiente + c.comision, c.total());
+        // Después del impago se añade la deuda vencida
+        let c = h1.calcula_cancelacion(Utc.ymd(2018, 8, 1)).unwrap();
+        let posicion = h1.calcula_posicion_deudora(Utc.ymd(2018, 8, 1)).unwrap();
+        assert_eq!(3, posicion.cuotas.len());
+        assert_eq!(posicion.total(), c.deuda_vencida);
+        assert!(c.capital_pendiente > dec!(0));
+        // Después de la resolución todo es deuda vencida
+        let c = h1.calcula_cancelacion(Utc.ymd(2022, 9, 1)).unwrap();
+        assert_eq!(dec!(0), c.capital_pendiente);
+        assert_eq!(dec!(0), c.intereses_corridos);
+        assert_eq!(h1.calcula_posicion_deudora(Utc.ymd(2022, 9, 1)).unwrap().total(), c.total());
+    }
+    #[test]
+    fn test_simula_buenas_practicas() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let solicitud = SolicitudBuenasPracticas::new(dec!(700), dec!(532.51), dec!(110000), dec!(120000));
+        let r = h1.simula_buenas_practicas(Utc.ymd(2013, 1, 1), &solicitud).unwrap();
+        assert!(r.elegibilidad.es_elegible());
+        let tabla = &r.tabla;
+        // 105 cuotas pagadas y plazo hasta completar 40 años
+        assert_eq!(480, tabla.len());
+        assert_eq!(Utc.ymd(2044, 3, 17), tabla.cuotas.last().unwrap().fecha);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        let primera = &tabla.cuotas[105];
+        assert_eq!(Utc.ymd(2013, 1, 17), primera.fecha);
+        assert!(primera.carencia);
+        assert_eq!(primera.cuota_interes, r.cuota_carencia);
+        // Revisión de octubre de 2013: euribor de septiembre (0,543%) más 0,25
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2013, 10, 17)).unwrap();
+        assert_eq!(0.00793, cuota.i);
+        // Tras la carencia vuelve el diferencial de la escritura
+        let cuota = &tabla.cuotas[165];
+        assert!(!cuota.carencia);
+        assert_eq!(cuota.cuota_total, r.cuota_tras_carencia);
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2018, 10, 17)).unwrap();
+        assert_eq!(redondea_cinco_decimales(-0.00166 + 0.01), cuota.i);
+        assert!(r.reduccion_cuota() > dec!(0));
+        assert!(r.coste_total() > dec!(0));
+        assert!(h1.novaciones.is_empty() && h1.carencias.is_empty());
+        assert!(h1.simula_buenas_practicas(Utc.ymd(2030, 1, 1), &solicitud).is_err());
+    }
+    #[test]
+    fn test_simula_congelacion_cuota() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.0, 0.12, Utc.ymd(2021, 5, 17),
+            Utc.ymd(2021, 12, 31),
+            EuriborData::con_proyeccion()).unwrap();
+        assert!(h1.simula_congelacion_cuota(Utc.ymd(2006, 10, 1), 85).is_err());
+        // La revisión de octubre de 2006 sube la cuota (euribor de septiembre 3,715%)
+        let c = h1.simula_congelacion_cuota(Utc.ymd(2006, 10, 1), 0).unwrap();
+        let original = &h1.tabla_amort_con_actualizacion_euribor;
+        let septiembre = original.cuotas.iter().find(|x| x.fecha == Utc.ymd(2006, 9, 17)).unwrap();
+        assert_eq!(septiembre.cuota_total, c.cuota_congelada);
+        let congeladas: Vec<_> = c.tabla.cuotas.iter()
+            .filter(|x| x.fecha >= Utc.ymd(2006, 10, 17) && x.fecha <= Utc.ymd(2007, 9, 17)).collect();
+        assert_eq!(12, congeladas.len());
+        assert!(congeladas.iter().all(|x| x.cuota_total <= c.cuota_congelada));
+        assert!(c.capital_aplazado > dec!(0));
+        // El aplazado se suma al capital al terminar la congelación, sin ampliar el plazo
+        let octubre = c.tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2007, 10, 17)).unwrap();
+        assert!(octubre.novacion);
+        assert_eq!(c.tabla.cuotas.last().unwrap().fecha, original.cuotas.last().unwrap().fecha);
+        assert_eq!(dec!(0), c.tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        // Entre dos cuotas congeladas el capital pendiente crece con los intereses del aplazado
+        let intereses_aplazado = congeladas[1].cap_pendiente_antes - congeladas[0].cap_pendiente_despues();
+        assert!(intereses_aplazado > dec!(0) && intereses_aplazado < dec!(1));
+        assert!(c.coste_total() > dec!(0));
+        // Con siete años más la cuota baja y el coste sube
+        let ampliada = h1.simula_congelacion_cuota(Utc.ymd(2006, 10, 1), 84).unwrap();
+        assert_eq!(Utc.ymd(2036, 3, 17), ampliada.tabla.cuotas.last().unwrap().fecha);
+        let octubre_ampliada = ampliada.tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2007, 10, 17)).unwrap();
+        assert!(octubre_ampliada.cuota_total < octubre.cuota_total);
+        assert!(ampliada.coste_total() > c.coste_total());
+        assert!(h1.novaciones.is_empty());
+    }
+    #[test]
+    fn test_comprueba_vencimiento_anticipado() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        // Segunda mitad del plazo: el 7% (5.889,80) o quince cuotas
+        let v = h1.comprueba_vencimiento_anticipado().unwrap();
+        assert!(!v.primera_mitad);
+        assert_eq!(dec!(5889.80), v.umbral_importe);
+        assert_eq!(15, v.umbral_cuotas);
+        let ultima = v.lineas.last().unwrap();
+        assert_eq!(v.fecha_umbral, Some(ultima.fecha));
+        assert!(ultima.total_impagado >= v.umbral_importe || ultima.cuotas_impagadas == 15);
+        let anterior = &v.lineas[v.lineas.len() - 2];
+        assert!(anterior.total_impagado < v.umbral_importe && anterior.cuotas_impagadas < 15);
+        assert!(!v.resolucion_prematura());
+        // Con un pago que cubre las dos primeras cuotas el umbral se alcanza más tarde
+        let cuotas = &v.lineas;
+        h1.add_pago(Pago { fecha: Utc.ymd(2018, 7, 1), importe: cuotas[0].cuota + cuotas[1].cuota }).unwrap();
+        let con_pago = h1.comprueba_vencimiento_anticipado().unwrap();
+        assert_eq!(2, con_pago.lineas[1].cuotas_impagadas);
+        assert_eq!(1, con_pago.lineas[2].cuotas_impagadas);
+        assert!(con_pago.fecha_umbral > v.fecha_umbral);
+        // Primera mitad del plazo: el 3% (2.524,20) o doce cuotas
+        h1.pagos.clear();
+        h1.fecha_impago = Utc.ymd(2008, 1, 17);
+        h1.fecha_resolucion = Utc.ymd(2008, 6, 1);
+        let v = h1.comprueba_vencimiento_anticipado().unwrap();
+        assert!(v.primera_mitad);
+        assert_eq!(dec!(2524.20), v.umbral_importe);
+        assert!(v.lineas.last().unwrap().cuotas_impagadas < 12);
+        assert!(v.resolucion_prematura());
+    }
+    #[test]
+    fn test_calcula_liquidacion_ejecucion() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.interes_demora = Some(0.19);
+        let impago = h1.calcula_liquidacion_impago().unwrap();
+        let l = h1.calcula_liquidacion_ejecucion().unwrap();
+        assert_eq!(impago.lineas.last().unwrap().capital, l.capital_pendiente);
+        assert_eq!(impago.total_capital(), l.total_capital());
+        assert_eq!(impago.total(), l.cantidad_reclamada());
+        assert_eq!(redondea_dos_decimales(l.cantidad_reclamada() * dec!(0.3)), l.limite_costas());
+        assert_eq!(l.cantidad_reclamada(), l.total_garantizado());
+        // La responsabilidad por intereses de demora no cubre todo lo reclamado
+        h1.responsabilidad_hipotecaria = Some(ResponsabilidadHipotecaria::new(dec!(84140),
+            dec!(10000), dec!(1000), dec!(12000)));
+        let l = h1.calcula_liquidacion_ejecucion().unwrap();
+        assert_eq!(l.intereses_demora - dec!(1000), l.total_exceso());
+        assert_eq!(dec!(12000), l.limite_costas());
+    }
+    #[test]
+    fn test_simula_subasta() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.interes_demora = Some(0.19);
+        let ejecucion = h1.calcula_liquidacion_ejecucion().unwrap();
+        let subasta = Subasta::new(Utc.ymd(2023, 6, 1), dec!(90000), true, 0.6, dec!(54000));
+        assert!(h1.simula_subasta(Subasta::new(Utc.ymd(2022, 1, 1), dec!(90000), true, 0.6, dec!(54000)),
+            dec!(0)).is_err());
+        // Las costas se limitan al 30% de lo reclamado
+        let r = h1.simula_subasta(subasta, dec!(1000000)).unwrap();
+        assert_eq!(ejecucion.cantidad_reclamada(), r.deuda);
+        assert_eq!(ejecucion.limite_costas(), r.costas);
+        let r = h1.simula_subasta(subasta, dec!(3000)).unwrap();
+        assert_eq!(r.deuda + dec!(3000) - dec!(54000), r.deuda_remanente());
+        // La deuda es inferior al 70% de la tasación: basta el 60%
+        assert_eq!(0.6, subasta.porcentaje_minimo_acreedor(r.deuda_total()));
+        assert!(r.cumple_minimos());
+        assert!(r.deuda_remanente_tras_venta(Utc.ymd(2025, 6, 1), dec!(70000), dec!(2000)) < r.deuda_remanente());
+    }
+    #[test]
+    fn test_resolucion_en_vencimiento() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.interes_demora = Some(0.19);
+        let antes = h1.calcula_liquidacion_ejecucion().unwrap();
+        // La resolución coincide con el vencimiento de la cuota de agosto
+        h1.fecha_resolucion = Utc.ymd(2022, 8, 17);
+        let cuota_agosto = h1.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .find(|x| x.fecha == h1.fecha_resolucion).unwrap().clone();
+        let tabla = h1.calcula_tabla_impago().unwrap();
+        let ultima = tabla.cuotas.last().unwrap();
+        assert_eq!(h1.fecha_resolucion, ultima.fecha);
+        assert_eq!(cuota_agosto.cap_pendiente_despues(), ultima.cuota_capital);
+        assert_eq!(dec!(0), ultima.cuota_interes);
+        let l = h1.calcula_liquidacion_ejecucion().unwrap();
+        assert_eq!(cuota_agosto.cap_pendiente_despues(), l.capital_pendiente);
+        assert_eq!(antes.capital_pendiente - cuota_agosto.cuota_capital, l.capital_pendiente);
+        assert_eq!(antes.total_capital(), l.total_capital());
+        // Después de la resolución todo el capital forma parte de la deuda vencida
+        let c = h1.calcula_cancelacion(Utc.ymd(2022, 9, 1)).unwrap();
+        assert_eq!(dec!(0), c.capital_pendiente);
+        assert!(c.deuda_vencida > l.total_capital());
+        let r = h1.simula_subasta(Subasta::new(Utc.ymd(2023, 6, 1), dec!(90000), true, 0.7,
+            dec!(63000)), dec!(0)).unwrap();
+        assert_eq!(l.cantidad_reclamada(), r.deuda);
+    }
+    #[test]
+    fn test_calcula_tabla_amort_sin_suelo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let tabla = h1.calcula_tabla_amort_sin_suelo(true).unwrap();
+        assert_eq!(300, tabla.len());
+        // Revisión de octubre de 2014: euribor de septiembre (0,362%) más 1%
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2014, 10, 17)).unwrap();
+        assert_eq!(0.01362, cuota.i);
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2017, 10, 17)).unwrap();
+        assert_eq!(0.00832, cuota.i);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_calcula_devolucion_clausula_suelo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let devolucion = h1.calcula_devolucion_clausula_suelo(true, Utc.ymd(2019, 1, 1)).unwrap();
+        // El suelo se aplica ya en la primera revisión: euribor 2,377% más 1%
+        let linea = &devolucion.lineas[0];
+        assert_eq!(Utc.ymd(2004, 10, 17), linea.fecha);
+        assert_eq!(0.04, linea.tipo_cobrado);
+        assert_eq!(0.03377, linea.tipo_alternativo);
+        assert!(linea.diferencia() > Importe::ZERO);
+        assert!(linea.intereses_legales > Importe::ZERO);
+        // Sólo se incluyen las cuotas pagadas antes del impago
+        assert!(devolucion.lineas.iter().all(|x| x.fecha < Utc.ymd(2018, 5, 17)));
+        assert!(devolucion.total_diferencias() > Importe::ZERO);
+        assert_eq!(Some(Utc.ymd(2019, 1, 1)), devolucion.fecha_liquidacion);
+        assert_eq!(redondea_dos_decimales(devolucion.total_diferencias() 
+            + devolucion.total_intereses_legales()), devolucion.total_a_devolver());
+    }
+    #[test]
+    fn test_valor_indice() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(h1.indices.euribor_fecha(Utc.ymd(2010, 3, 17)).unwrap(), h1.valor_indice(Utc.ymd(2010, 3, 17)).unwrap());
+        for agno in 2004..2022 {
+            h1.indices.set_valores_agno(IndiceReferencia::IrphEntidades, agno, vec![3.0; 12]);
+        }
+        for agno in 2004..2014 {
+            h1.indices.set_valores_agno(IndiceReferencia::IrphCajas, agno, vec![3.25; 12]);
+        }
+        h1.indice = IndiceReferencia::IrphCajas;
+        assert_eq!(0.0325, h1.valor_indice(Utc.ymd(2013, 10, 17)).unwrap());
+        // Desaparecido el índice: IRPH Entidades más el diferencial medio
+        assert_eq!(0.0325, h1.valor_indice(Utc.ymd(2013, 11, 17)).unwrap());
+        assert_eq!(0.0325, h1.valor_indice(Utc.ymd(2016, 3, 17)).unwrap());
+        h1.recalcula_tablas().unwrap();
+        let cuota = h1.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .find(|x| x.fecha == Utc.ymd(2016, 10, 17)).unwrap();
+        assert_eq!(0.0425, cuota.i);
+        // Con índice sustitutivo pactado
+        h1.indice_sustitutivo = Some(IndiceReferencia::Euribor12Meses);
+        h1.diferencial_sustitutivo = 0.005;
+        assert_eq!(redondea_cinco_decimales(h1.indices.euribor_fecha(Utc.ymd(2016, 3, 17)).unwrap() + 0.005), 
+            h1.valor_indice(Utc.ymd(2016, 3, 17)).unwrap());
+    }
+    #[test]
+    fn test_revisiones_sin_datos_indice() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2021, 10, 17)).unwrap();
+        assert!(!cuota.sin_datos_indice);
+        assert_eq!(0.00508, cuota.i);
+        // Posterior a la resolución y sin datos: con la proyección se mantiene el tipo anterior
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2022, 10, 17)).unwrap();
+        assert!(cuota.sin_datos_indice);
+        assert_eq!(0.00508, cuota.i);
+        // Sin la proyección es un error
+        let h2 = Hipoteca::new(String::from("Prueba"), fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5));
+        match h2 {
+            Err(HipotecaError::SinDatosIndice { agno, mes, .. }) => assert_eq!((2022, 9), (agno, mes)),
+            _ => panic!("Se esperaba un error por falta de datos del índice"),
+        }
+    }
+    #[test]
+    fn test_calcula_sustitucion_indice() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.0025, 
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2021, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        for agno in 2004..2022 {
+            h1.indices.set_valores_agno(IndiceReferencia::IrphEntidades, agno, vec![3.5; 12]);
+        }
+        h1.indice = IndiceReferencia::IrphEntidades;
+        h1.recalcula_tablas().unwrap();
+        let sustitucion = h1.calcula_sustitucion_indice(IndiceReferencia::Euribor12Meses, 
+            0.01, Utc.ymd(2020, 1, 1)).unwrap();
+        // Sin revisiones, las cuotas del primer periodo no cambian
+        let linea = &sustitucion.comparacion.lineas[0];
+        assert_eq!(Utc.ymd(2004, 4, 17), linea.fecha);
+        assert_eq!(dec!(0), linea.diferencia());
+        let linea = sustitucion.comparacion.lineas.iter()
+            .find(|x| x.fecha == Utc.ymd(2014, 10, 17)).unwrap();
+        assert_eq!(0.03750, linea.tipo_cobrado);
+        assert_eq!(0.01362, linea.tipo_alternativo);
+        assert!(linea.diferencia() > Importe::ZERO);
+        // Las cuotas impagadas no se incluyen
+        assert_eq!(Utc.ymd(2018, 4, 17), sustitucion.comparacion.lineas.last().unwrap().fecha);
+        assert!(sustitucion.total_cobrado_de_mas() > Importe::ZERO);
+        // La tabla recalculada queda totalmente amortizada
+        assert_eq!(dec!(0), sustitucion.tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_fechas_vencimiento() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,1,31);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let fechas = |h: &Hipoteca| h.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .take(6).map(|x| x.fecha).collect::<Vec<Date<Utc>>>();
+        // Las cuotas vencen el día de la escritura o el último del mes si es más corto
+        assert_eq!(vec![Utc.ymd(2004, 2, 29), Utc.ymd(2004, 3, 31), Utc.ymd(2004, 4, 30),
+            Utc.ymd(2004, 5, 31), Utc.ymd(2004, 6, 30), Utc.ymd(2004, 7, 31)], fechas(&h1));
+        assert_eq!(Utc.ymd(2029, 1, 31), h1.fecha_ultimo_vencimiento);
+        // El domingo 29 de febrero pasa al viernes 27 para no cambiar de mes
+        // y el sábado 31 de julio al viernes 30
+        h1.ajuste_dia_habil = AjusteDiaHabil::SiguienteModificado;
+        h1.recalcula_tablas().unwrap();
+        assert_eq!(vec![Utc.ymd(2004, 2, 27), Utc.ymd(2004, 3, 31), Utc.ymd(2004, 4, 30),
+            Utc.ymd(2004, 5, 31), Utc.ymd(2004, 6, 30), Utc.ymd(2004, 7, 30)], fechas(&h1));
+        h1.ajuste_dia_habil = AjusteDiaHabil::Siguiente;
+        h1.recalcula_tablas().unwrap();
+        assert_eq!(Utc.ymd(2004, 3, 1), fechas(&h1)[0]);
+        assert_eq!(Utc.ymd(2004, 8, 2), fechas(&h1)[5]);
+        assert_eq!(300, h1.tabla_amort_con_actualizacion_euribor.len());
+        assert_eq!(dec!(0), h1.tabla_amort_con_actualizacion_euribor.cuotas.last().unwrap().cap_pendiente_despues());
+
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,4,30);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(vec![Utc.ymd(2004, 5, 30), Utc.ymd(2004, 6, 30), Utc.ymd(2004, 7, 30)],
+            fechas(&h1)[..3].to_vec());
+        h1.fin_de_mes = true;
+        h1.recalcula_tablas().unwrap();
+        assert_eq!(vec![Utc.ymd(2004, 5, 31), Utc.ymd(2004, 6, 30), Utc.ymd(2004, 7, 31)],
+            fechas(&h1)[..3].to_vec());
+    }
+    #[test]
+    fn test_carencia_capital() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.add_carencia(Carencia::new(fecha, 12, TipoCarencia::Capital)).unwrap();
+        for tabla in [&h1.tabla_amort_sin_actualizacion, &h1.tabla_amort_con_actualizacion_euribor] {
+            assert_eq!(300, tabla.len());
+            for cuota in tabla.cuotas.iter().take(12) {
+                assert!(cuota.carencia);
+                assert_eq!(dec!(0), cuota.cuota_capital);
+                assert_eq!(cuota.cuota_interes, cuota.cuota_total);
+            }
+            assert!(!tabla.cuotas[12].carencia);
+            assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        }
+        // Tras la carencia la cuota amortiza el capital en los 288 meses restantes
+        let cuota = &h1.tabla_amort_sin_actualizacion.cuotas[12];
+        assert_eq!(dec!(280.47), h1.tabla_amort_sin_actualizacion.cuotas[11].cuota_total);
+        assert_eq!(Utc.ymd(2005, 4, 17), cuota.fecha);
+        assert_eq!(288, cuota.meses_restantes_antes);
+        assert_eq!(importe_cuota_mensual(Redondeo::MitadArriba, dec!(84140), 0.04, 288), cuota.cuota_total);
+    }
+    #[test]
+    fn test_carencia_total() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.add_carencia(Carencia::new(fecha, 12, TipoCarencia::Total)).unwrap();
+        let tabla = &h1.tabla_amort_sin_actualizacion;
+        assert_eq!(dec!(0), tabla.cuotas[0].cuota_total);
+        assert_eq!(dec!(84420.47), tabla.cuotas[0].cap_pendiente_despues());
+        // Los intereses capitalizados se suman al capital pendiente
+        let cuota = &tabla.cuotas[12];
+        assert_eq!(dec!(87567.98), cuota.cap_pendiente_antes);
+        assert_eq!(importe_cuota_mensual(Redondeo::MitadArriba, dec!(87567.98), 0.04, 288), cuota.cuota_total);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_carencia_intermedia() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let sin_carencia = h1.tabla_amort_con_actualizacion_euribor.cuotas.clone();
+        h1.add_carencia(Carencia::new(Utc.ymd(2010, 5, 1), 24, TipoCarencia::Capital)).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        let n = tabla.cuotas.iter().position(|x| x.carencia).unwrap();
+        assert_eq!(Utc.ymd(2010, 5, 17), tabla.cuotas[n].fecha);
+        for (antes, despues) in sin_carencia.iter().zip(tabla.cuotas.iter()).take(n) {
+            assert_eq!(antes.cuota_total, despues.cuota_total);
+        }
+        assert_eq!(24, tabla.cuotas.iter().filter(|x| x.carencia).count());
+        let capital = tabla.cuotas[n].cap_pendiente_antes;
+        assert_eq!(capital, tabla.cuotas[n + 24].cap_pendiente_antes);
+        assert!(tabla.cuotas[n + 24].cuota_total > sin_carencia[n + 24].cuota_total);
+        assert_eq!(300, tabla.len());
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        // La carencia no puede llegar al último vencimiento
+        assert!(h1.add_carencia(Carencia::new(Utc.ymd(2028, 1, 1), 24, TipoCarencia::Total)).is_err());
+        assert!(h1.add_carencia(Carencia::new(Utc.ymd(2012, 1, 1), 0, TipoCarencia::Total)).is_err());
+    }
+    #[test]
+    fn test_tramos_interes() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2021, 12, 31),
+            EuriborData::con_proyeccion()).unwrap();
+        // Un tramo que impide calcular las tablas no se conserva
+        assert!(h1.add_tramo(TramoInteres::fijo(f64::NAN, 60)).is_err());
+        assert!(h1.tramos.is_empty());
+        h1.add_tramo(TramoInteres::fijo(0.02, 60)).unwrap();
+        let mut tramo = TramoInteres::variable(0.01, Some(120));
+        tramo.i_min = Some(0.0);
+        h1.add_tramo(tramo).unwrap();
+        let mut tramo = TramoInteres::variable(0.015, None);
+        tramo.intervalo_revisiones = Some(6);
+        h1.add_tramo(tramo).unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert_eq!(300, tabla.len());
+        // Tramo fijo sin revisiones, con la cuota calculada al 2%
+        assert_eq!(0.02, tabla.cuotas[0].i);
+        assert_eq!(0.02, tabla.cuotas[59].i);
+        assert_eq!(importe_cuota_mensual(Redondeo::MitadArriba, dec!(84140), 0.02, 300), tabla.cuotas[0].cuota_total);
+        // Primer tramo variable, con suelo del 0% y revisiones anuales
+        let tipo = |n: i32, diferencial: f64, i_min: f64| ajusta_limites(
+            h1.valor_indice(h1.fecha_vencimiento_nominal(n)).unwrap() + diferencial, Some(i_min), Some(0.12));
+        assert_eq!(tipo(60, 0.01, 0.0), tabla.cuotas[60].i);
+        assert_eq!(tipo(60, 0.01, 0.0), tabla.cuotas[71].i);
+        assert_eq!(tipo(168, 0.01, 0.0), tabla.cuotas[168].i);
+        // Segundo tramo variable con revisiones semestrales
+        assert_eq!(tipo(180, 0.015, 0.04), tabla.cuotas[180].i);
+        assert_eq!(tipo(186, 0.015, 0.04), tabla.cuotas[186].i);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        // No se pueden añadir tramos tras uno que dura hasta el final
+        assert!(h1.add_tramo(TramoInteres::fijo(0.03, 12)).is_err());
+    }
+    #[test]
+    fn test_tramo_no_valido() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2021, 12, 31),
+            EuriborData::con_proyeccion()).unwrap();
+        assert!(h1.add_tramo(TramoInteres::fijo(0.02, 0)).is_err());
+        let mut tramo = TramoInteres::variable(0.01, Some(60));
+        tramo.intervalo_revisiones = Some(0);
+        assert!(h1.add_tramo(tramo).is_err());
+        // Tipo mínimo del tramo mayor que el máximo de la escritura
+        let mut tramo = TramoInteres::variable(0.01, Some(60));
+        tramo.i_min = Some(0.15);
+        assert!(h1.add_tramo(tramo).is_err());
+        let mut tramo = TramoInteres::variable(0.01, Some(60));
+        tramo.i_min = Some(0.05);
+        tramo.i_max = Some(0.03);
+        assert!(h1.add_tramo(tramo).is_err());
+        assert!(h1.tramos.is_empty());
+        h1.add_tramo(TramoInteres::variable(0.01, None)).unwrap();
+        assert!(h1.add_tramo(TramoInteres::fijo(0.03, 12)).is_err());
+        assert_eq!(1, h1.tramos.len());
+    }
+    #[test]
+    fn test_redondeo_tipo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let sin_redondeo = h1.tabla_amort_con_actualizacion_euribor.cuotas.clone();
+        h1.redondeo_tipo = RedondeoTipo::Arriba(0.0025);
+        h1.recalcula_tablas().unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        for (antes, despues) in sin_redondeo.iter().zip(tabla.cuotas.iter()).skip(6) {
+            assert_eq!(RedondeoTipo::Arriba(0.0025).aplica(antes.i), despues.i);
+        }
+        let coste = h1.calcula_coste_redondeo(RedondeoTipo::SinRedondeo, h1.fecha_resolucion).unwrap();
+        assert!(coste.total_cobrado_de_mas() > dec!(0));
+        assert!(coste.comparacion.lineas.iter().all(|x| x.diferencia() >= dec!(0)));
+        assert_eq!(RedondeoTipo::Arriba(0.0025), h1.redondeo_tipo);
+    }
+    #[test]
+    fn test_referencia_revision() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.0, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let revision = Utc.ymd(2010, 10, 17);
+        assert_eq!(h1.indices.euribor_mes(9, 2010), h1.valor_indice(revision));
+        h1.referencia_revision = ReferenciaRevision::new(2, None, 1).unwrap();
+        assert_eq!(h1.indices.euribor_mes(8, 2010), h1.valor_indice(revision));
+        h1.referencia_revision = ReferenciaRevision::new(1, None, 3).unwrap();
+        let media = (h1.indices.euribor_mes(7, 2010).unwrap() + h1.indices.euribor_mes(8, 2010).unwrap()
+            + h1.indices.euribor_mes(9, 2010).unwrap()) / 3.0;
+        assert_eq!(redondea_cinco_decimales(media), h1.valor_indice(revision).unwrap());
+        h1.recalcula_tablas().unwrap();
+        let cuota = h1.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .find(|x| x.fecha == revision).unwrap();
+        assert_eq!(ajusta_limites(redondea_cinco_decimales(media) + 0.01, Some(0.0), Some(0.12)), cuota.i);
+        // Sin medias mensuales cargadas no hay datos del BOE
+        h1.referencia_revision = ReferenciaRevision::boe(1).unwrap();
+        assert!(h1.recalcula_tablas().is_err());
+    }
+    #[test]
+    fn test_concilia_recibos() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        // Recibos cobrados un día después del vencimiento
+        let mut recibos: Vec<ReciboBanco> = h1.tabla_amort_con_actualizacion_euribor.cuotas.iter().take(12)
+            .map(|x| ReciboBanco { fecha: x.fecha.succ(), importe: x.cuota_total, intereses: x.cuota_interes,
+                capital: x.cuota_capital, tipo: Some(x.i) })
+            .collect();
+        recibos[3].intereses += dec!(1);
+        recibos[3].importe += dec!(1);
+        recibos[7].tipo = Some(0.045);
+        recibos.remove(9);
+        let conciliacion = h1.concilia_recibos(&recibos);
+        assert_eq!(12, conciliacion.lineas.len());
+        let discrepancias = conciliacion.lineas_con_discrepancias();
+        assert_eq!(3, discrepancias.len());
+        assert_eq!("Intereses distintos", discrepancias[0].observaciones());
+        assert_eq!("Tipo distinto", discrepancias[1].observaciones());
+        assert_eq!("Cuota sin recibo", discrepancias[2].observaciones());
+        let cuota_sin_recibo = h1.tabla_amort_con_actualizacion_euribor.cuotas[9].cuota_total;
+        assert_eq!(dec!(1) - cuota_sin_recibo, conciliacion.total_diferencias());
+    }
+    #[test]
+    fn test_sistemas_amortizacion() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.sistema_amortizacion = SistemaAmortizacion::Aleman;
+        h1.recalcula_tablas().unwrap();
+        let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[0];
+        assert_eq!(dec!(280.47), cuota.cuota_capital);
+        assert_eq!(dec!(280.47), cuota.cuota_interes);
+        assert_eq!(dec!(560.94), cuota.cuota_total);
+        let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[1];
+        assert_eq!(dec!(280.47), cuota.cuota_capital);
+        assert!(cuota.cuota_total < dec!(560.94));
+        // Tras la revisión la cuota de capital es la misma
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        let cuota = tabla.cuotas.iter().find(|x| x.fecha == Utc.ymd(2010, 9, 17)).unwrap();
+        assert_eq!(dec!(280.47), cuota.cuota_capital);
+        for tabla in [&h1.tabla_amort_sin_actualizacion, &h1.tabla_amort_con_actualizacion_euribor] {
+            assert_eq!(300, tabla.len());
+            assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        }
+
+        h1.sistema_amortizacion = SistemaAmortizacion::Americano;
+        h1.recalcula_tablas().unwrap();
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert_eq!(dec!(0), tabla.cuotas[0].cuota_capital);
+        assert_eq!(dec!(280.47), tabla.cuotas[0].cuota_total);
+        assert_eq!(dec!(84140), tabla.cuotas[298].cap_pendiente_despues());
+        assert_eq!(dec!(84140), tabla.cuotas.last().unwrap().cuota_capital);
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+
+        // Cuota creciente un 2% anual: las doce primeras cuotas son iguales
+        h1.sistema_amortizacion = SistemaAmortizacion::Progresivo(0.02);
+        h1.recalcula_tablas().unwrap();
+        let tabla = &h1.tabla_amort_sin_actualizacion;
+        assert!(tabla.cuotas[0].cuota_total < dec!(444.12));
+        assert_eq!(tabla.cuotas[0].cuota_total, tabla.cuotas[11].cuota_total);
+        assert_eq!(redondea_dos_decimales(tabla.cuotas[0].cuota_total * dec!(1.02)), tabla.cuotas[12].cuota_total);
+        assert_eq!(300, tabla.len());
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+        assert!((tabla.cuotas.last().unwrap().cuota_total - tabla.cuotas[298].cuota_total).abs() < dec!(1));
+        let tabla = &h1.tabla_amort_con_actualizacion_euribor;
+        assert_eq!(dec!(0), tabla.cuotas.last().unwrap().cap_pendiente_despues());
+    }
+    #[test]
+    fn test_base_calculo() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01,
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        h1.base_calculo = BaseCalculo::Real365;
+        h1.recalcula_tablas().unwrap();
+        // 31 días de intereses entre el 17 de marzo y el 17 de abril
+        let cuota: &Cuota = &h1.tabla_amort_sin_actualizacion.cuotas[0];
+        assert_eq!(dec!(444.12), cuota.cuota_total);
+        assert_eq!(dec!(285.85), cuota.cuota_interes);
+        assert_eq!(dec!(158.27), cuota.cuota_capital);
+        assert_eq!(dec!(0), h1.tabla_amort_sin_actualizacion.cuotas.last().unwrap().cap_pendiente_despues());
+        assert_eq!(dec!(0), h1.tabla_amort_con_actualizacion_euribor.cuotas.last().unwrap().cap_pendiente_despues());
+
+        h1.base_calculo = BaseCalculo::Treinta360;
+        h1.recalcula_tablas().unwrap();
+        let comparacion = h1.calcula_comparacion_base_calculo(BaseCalculo::Real360,
+            Utc.ymd(2020, 1, 1)).unwrap();
+        assert_eq!(BaseCalculo::Treinta360, h1.base_calculo);
+        assert_eq!(BaseCalculo::Treinta360, comparacion.base_contractual);
+        assert_eq!(dec!(289.82), comparacion.tabla.cuotas[0].cuota_interes);
+        // Las cuotas del primer periodo son iguales; la base Real/360
+        // amortiza menos capital y las cuotas revisadas son mayores
+        assert_eq!(dec!(0), comparacion.comparacion.lineas[0].diferencia());
+        assert_eq!(Utc.ymd(2018, 4, 17), comparacion.comparacion.lineas.last().unwrap().fecha);
+        assert!(comparacion.total_cobrado_de_mas() > Importe::ZERO);
+    }
+    #[test]
+    fn test_revision_sin_datos_indice_antes_resolucion() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h = Hipoteca::new(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2023, 8, 5));
+        match h {
+            Err(HipotecaError::SinDatosIndice { indice, .. }) => 
+                assert_eq!(IndiceReferencia::Euribor12Meses, indice),
+            _ => panic!("Se esperaba un error por falta de datos del índice"),
+        }
+    }
+    #[test]
+    fn test_tipo_no_finito() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h = Hipoteca::new(nombre, fecha, dec!(84140), f64::NAN,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5));
+        assert!(matches!(h, Err(HipotecaError::EntradaInvalida { campo, .. }) if campo == "tipo_interes_anual"));
+        let mut h1 = Hipoteca::new_con_indices(String::from("Prueba"), fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();
+        let mut novacion = Novacion::new(Utc.ymd(2015, 1, 1));
+        novacion.incremento_euribor = Some(f64::INFINITY);
+        let total = |h: &Hipoteca| h.tabla_amort_con_actualizacion_euribor.cuotas.iter()
+            .map(|x| x.cuota_total).sum::<Importe>();
+        let total_antes = total(&h1);
+        assert!(h1.add_novacion(novacion).is_err());
+        // La novación rechazada no se conserva ni altera las tablas
+        assert!(h1.novaciones.is_empty());
+        assert_eq!(total_antes, total(&h1));
+        h1.add_carencia(Carencia::new(Utc.ymd(2010, 1, 1), 12, TipoCarencia::Capital)).unwrap();
+    }
+    #[test]
+    fn test_entrada_invalida() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let h = Hipoteca::new(nombre, fecha, dec!(84140), 0.04,
+            0,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2022, 8, 5));
+        assert_eq!(Some(HipotecaError::entrada_invalida("meses", "debe ser mayor que cero")), h.err());
+    }
+    #[test]
+    fn test_new_sin_tablas() {
+        let nombre = String::from("Prueba");
+        let fecha = Utc.ymd(2004,3,17);
+        let mut h = Hipoteca::new_sin_tablas(nombre, fecha, dec!(84140), 0.04,
+            300,6,12,0.01, 
+            0.04, 0.12, Utc.ymd(2018, 5, 17),
+            Utc.ymd(2021, 12, 31),
+            EuriborData::con_proyeccion()).unwrap();
+        assert!(h.tabla_amort_sin_actualizacion.cuotas.is_empty());
+        h.fecha_primera_cuota = Utc.ymd(2004, 5, 1);
+        h.carencias.push(Carencia::new(Utc.ymd(2005, 1, 1), 300, TipoCarencia::Capital));
+        assert_eq!(Err(HipotecaError::entrada_invalida("carencia", 
+            "debe terminar antes del último vencimiento")), h.recalcula_tablas());
+        h.carencias.clear();
+        h.recalcula_tablas().unwrap();
+        assert_eq!(300, h.tabla_amort_sin_actualizacion.cuotas.len());
+        assert_eq!(Utc.ymd(2004, 5, 1), h.tabla_amort_sin_actualizacion.cuotas[0].fecha);
+    }
 }
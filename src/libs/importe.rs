@@ -0,0 +1,132 @@
+use rust_decimal::prelude::*;
+pub use rust_decimal::Decimal;
+use super::error::*;
+
+/// Importe monetario en euros. Se usa un decimal exacto en lugar de
+/// `f64` para que las cifras cuadren al céntimo con las de la entidad
+pub type Importe = Decimal;
+
+/// Forma de redondear los importes a céntimos
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Redondeo {
+    /// Al céntimo más próximo; las mitades hacia arriba
+    #[default]
+    MitadArriba,
+    /// Al céntimo más próximo; las mitades al céntimo par (redondeo bancario)
+    MitadPar,
+    /// Se eliminan los decimales a partir del tercero, como hacen algunas entidades
+    Truncamiento,
+}
+
+impl Redondeo {
+    /// Todas las formas de redondeo disponibles
+    pub fn todos() -> [Redondeo; 3] {
+        [Redondeo::MitadArriba, Redondeo::MitadPar, Redondeo::Truncamiento]
+    }
+    /// Código del redondeo usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            Redondeo::MitadArriba => "MITAD_ARRIBA",
+            Redondeo::MitadPar => "MITAD_PAR",
+            Redondeo::Truncamiento => "TRUNCAMIENTO",
+        }
+    }
+    /// Obtiene el redondeo a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        Self::todos().into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    fn estrategia(&self) -> RoundingStrategy {
+        match self {
+            Redondeo::MitadArriba => RoundingStrategy::MidpointAwayFromZero,
+            Redondeo::MitadPar => RoundingStrategy::MidpointNearestEven,
+            Redondeo::Truncamiento => RoundingStrategy::ToZero,
+        }
+    }
+    /// Redondea un importe a céntimos
+    pub fn importe(&self, valor: Importe) -> Importe {
+        valor.round_dp_with_strategy(2, self.estrategia())
+    }
+    /// Importe mensual de la cuota con el método de amortización
+    /// francés (cuotas mensuales iguales)
+    pub fn cuota_mensual(&self, capital_pendiente: Importe, tipo_interes_anual: f64, meses: i32) -> Importe {
+        if meses <= 0 {
+            return self.importe(capital_pendiente);
+        }
+        let i_mensual = tipo(tipo_interes_anual) / Decimal::from(12);
+        if i_mensual.is_zero() {
+            return self.importe(capital_pendiente / Decimal::from(meses));
+        }
+        let factor = (Decimal::ONE + i_mensual).powi(meses as i64);
+        self.importe(capital_pendiente * i_mensual * factor / (factor - Decimal::ONE))
+    }
+    /// Intereses de un mes a partir del capital pendiente y el tipo de interés anual
+    pub fn intereses_mes(&self, capital_pendiente: Importe, tipo_interes_anual: f64) -> Importe {
+        self.importe(capital_pendiente * tipo(tipo_interes_anual) / Decimal::from(12))
+    }
+    /// Intereses de un número de días sobre una base anual de días
+    pub fn intereses_dias(&self, capital: Importe, tipo_interes_anual: f64, dias: i64, base: i64) -> Importe {
+        self.importe(capital * tipo(tipo_interes_anual) * Decimal::from(dias) / Decimal::from(base))
+    }
+}
+
+/// Convierte un importe leído como `f64` (por ejemplo de una hoja
+/// de cálculo) en un importe exacto en céntimos. Devuelve un error si
+/// el valor no es un número finito
+pub fn importe(valor: f64) -> Result<Importe> {
+    Decimal::from_f64(valor).map(|x| x.round_dp(2))
+        .ok_or_else(|| HipotecaError::entrada_invalida("importe", &format!("{} no es un importe", valor)))
+}
+
+/// Convierte a decimal un tipo de interés en tanto por uno. Los tipos
+/// se guardan redondeados a cinco decimales, que se conservan exactos.
+/// Los tipos de la hipoteca se validan al calcular las tablas
+/// (`Hipoteca::recalcula_tablas`), por lo que siempre son finitos
+pub fn tipo(tipo_interes: f64) -> Decimal {
+    Decimal::from_f64(tipo_interes).unwrap_or_default()
+}
+
+/// Convierte un importe a `f64`, para los cálculos que no son
+/// monetarios (logaritmos, comparaciones con límites, etc.)
+pub fn a_f64(valor: Importe) -> f64 {
+    valor.to_f64().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_redondeo() {
+        assert_eq!(dec!(2.35), Redondeo::MitadArriba.importe(dec!(2.345)));
+        assert_eq!(dec!(2.34), Redondeo::MitadPar.importe(dec!(2.345)));
+        assert_eq!(dec!(2.36), Redondeo::MitadPar.importe(dec!(2.355)));
+        assert_eq!(dec!(2.34), Redondeo::Truncamiento.importe(dec!(2.349)));
+        assert_eq!(dec!(-2.35), Redondeo::MitadArriba.importe(dec!(-2.345)));
+    }
+    #[test]
+    fn test_redondeo_from_codigo() {
+        assert_eq!(Some(Redondeo::MitadPar), Redondeo::from_codigo("mitad_par"));
+        assert_eq!(Some(Redondeo::Truncamiento), Redondeo::from_codigo(" TRUNCAMIENTO "));
+        assert_eq!(None, Redondeo::from_codigo("HACIA_ABAJO"));
+    }
+    #[test]
+    fn test_cuota_mensual() {
+        assert_eq!(dec!(444.12), Redondeo::MitadArriba.cuota_mensual(dec!(84140), 0.04, 300));
+        assert_eq!(dec!(100), Redondeo::MitadArriba.cuota_mensual(dec!(1200), 0.0, 12));
+    }
+    #[test]
+    fn test_intereses_mes() {
+        assert_eq!(dec!(280.47), Redondeo::MitadArriba.intereses_mes(dec!(84140), 0.04));
+        assert_eq!(dec!(280.46), Redondeo::Truncamiento.intereses_mes(dec!(84140), 0.04));
+    }
+    #[test]
+    fn test_importe() {
+        assert_eq!(Ok(dec!(444.12)), importe(444.12));
+        assert_eq!(Ok(dec!(0.1)), importe(0.1));
+        assert!(importe(f64::NAN).is_err());
+        assert!(importe(f64::INFINITY).is_err());
+        assert_eq!(dec!(0.04), tipo(0.04));
+        assert_eq!(dec!(0.01362), tipo(0.01362));
+    }
+}
@@ -0,0 +1,170 @@
+use chrono::prelude::*;
+use super::error::*;
+
+/// Índices de referencia oficiales para las revisiones del tipo de interés
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum IndiceReferencia {
+    /// Euribor a 12 meses
+    Euribor12Meses,
+    /// Euribor a 6 meses
+    Euribor6Meses,
+    /// IRPH del conjunto de entidades de crédito
+    IrphEntidades,
+    /// IRPH de cajas de ahorros, desaparecido en 2013
+    IrphCajas,
+    /// IRPH de bancos, desaparecido en 2013
+    IrphBancos,
+    /// Indicador CECA tipo activo, desaparecido en 2013
+    Ceca,
+}
+
+impl IndiceReferencia {
+    /// Fecha a partir de la cual el índice dejó de publicarse. Los
+    /// índices suprimidos por la Orden EHA/2899/2011 desaparecieron
+    /// el 1 de noviembre de 2013 (DA 15ª Ley 14/2013)
+    pub fn fecha_desaparicion(&self) -> Option<Date<Utc>> {
+        match self {
+            IndiceReferencia::IrphCajas | IndiceReferencia::IrphBancos
+                | IndiceReferencia::Ceca => Some(Utc.ymd(2013, 11, 1)),
+            _ => None,
+        }
+    }
+    /// Código del índice usado en los ficheros de datos
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            IndiceReferencia::Euribor12Meses => "EURIBOR12M",
+            IndiceReferencia::Euribor6Meses => "EURIBOR6M",
+            IndiceReferencia::IrphEntidades => "IRPH_ENTIDADES",
+            IndiceReferencia::IrphCajas => "IRPH_CAJAS",
+            IndiceReferencia::IrphBancos => "IRPH_BANCOS",
+            IndiceReferencia::Ceca => "CECA",
+        }
+    }
+    /// Todos los índices disponibles
+    pub fn todos() -> [IndiceReferencia; 6] {
+        [IndiceReferencia::Euribor12Meses, IndiceReferencia::Euribor6Meses,
+            IndiceReferencia::IrphEntidades, IndiceReferencia::IrphCajas,
+            IndiceReferencia::IrphBancos, IndiceReferencia::Ceca]
+    }
+    /// Obtiene el índice a partir de su código en los ficheros de datos
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        Self::todos().into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Nombre del índice, tal como se escribe en los informes
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            IndiceReferencia::Euribor12Meses => "Euribor 12 meses",
+            IndiceReferencia::Euribor6Meses => "Euribor 6 meses",
+            IndiceReferencia::IrphEntidades => "IRPH Entidades",
+            IndiceReferencia::IrphCajas => "IRPH Cajas",
+            IndiceReferencia::IrphBancos => "IRPH Bancos",
+            IndiceReferencia::Ceca => "CECA",
+        }
+    }
+}
+
+/// Serie de valores mensuales de un índice
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum TipoSerie {
+    /// Media de los valores diarios del mes, que es el valor oficial
+    /// publicado en el BOE y por el Banco de España
+    MediaMensual,
+    /// Valor del último día hábil del mes
+    FinDeMes,
+}
+
+impl TipoSerie {
+    /// Código de la serie usado en la hoja de cálculo y en los ficheros de datos
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            TipoSerie::MediaMensual => "MEDIA",
+            TipoSerie::FinDeMes => "CIERRE",
+        }
+    }
+    /// Obtiene la serie a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        [TipoSerie::MediaMensual, TipoSerie::FinDeMes]
+            .into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Nombre de la serie, tal como se escribe en los informes
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            TipoSerie::MediaMensual => "media mensual",
+            TipoSerie::FinDeMes => "cierre de mes",
+        }
+    }
+}
+
+/// Regla pactada para obtener el valor del índice en cada revisión:
+/// qué mes se toma, de qué serie y si se promedian varios meses
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ReferenciaRevision {
+    /// Meses entre el de la revisión y el último que se toma: 1 es el
+    /// mes anterior a la revisión y 2 el anterior a este
+    pub meses_desfase: i32,
+    /// Serie de la que se toman los valores. `None` usa en todas las
+    /// revisiones la media mensual publicada si hay datos de ella y, si
+    /// no, el valor de cierre del mes
+    pub serie: Option<TipoSerie>,
+    /// Número de meses, hasta el último que se toma, cuyos valores se
+    /// promedian. 1 si se toma el valor de un solo mes
+    pub meses_media: i32,
+}
+
+impl ReferenciaRevision {
+    /// Crea la regla de revisión. Devuelve un error si el desfase es
+    /// negativo o no se promedia al menos un mes
+    pub fn new(meses_desfase: i32, serie: Option<TipoSerie>, meses_media: i32) -> Result<Self> {
+        if meses_desfase < 0 {
+            return Err(HipotecaError::entrada_invalida("meses_desfase", "no puede ser negativo"));
+        }
+        if meses_media < 1 {
+            return Err(HipotecaError::entrada_invalida("meses_media", "debe ser al menos 1"));
+        }
+        Ok(ReferenciaRevision { meses_desfase, serie, meses_media })
+    }
+    /// Valor oficial publicado en el BOE: media mensual del mes que
+    /// corresponda según el desfase
+    pub fn boe(meses_desfase: i32) -> Result<Self> {
+        ReferenciaRevision::new(meses_desfase, Some(TipoSerie::MediaMensual), 1)
+    }
+}
+impl Default for ReferenciaRevision {
+    /// Valor del mes anterior a la revisión
+    fn default() -> Self {
+        ReferenciaRevision { meses_desfase: 1, serie: None, meses_media: 1 }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fecha_desaparicion() {
+        assert_eq!(None, IndiceReferencia::Euribor12Meses.fecha_desaparicion());
+        assert_eq!(None, IndiceReferencia::IrphEntidades.fecha_desaparicion());
+        assert_eq!(Some(Utc.ymd(2013, 11, 1)), IndiceReferencia::IrphCajas.fecha_desaparicion());
+        assert_eq!(Some(Utc.ymd(2013, 11, 1)), IndiceReferencia::Ceca.fecha_desaparicion());
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(IndiceReferencia::IrphEntidades), IndiceReferencia::from_codigo("IRPH_ENTIDADES"));
+        assert_eq!(Some(IndiceReferencia::Euribor6Meses), IndiceReferencia::from_codigo("euribor6m"));
+        assert_eq!(None, IndiceReferencia::from_codigo("LIBOR"));
+    }
+    #[test]
+    fn test_tipo_serie_from_codigo() {
+        assert_eq!(Some(TipoSerie::FinDeMes), TipoSerie::from_codigo("cierre"));
+        assert_eq!(None, TipoSerie::from_codigo("DIARIO"));
+    }
+    #[test]
+    fn test_referencia_revision_no_valida() {
+        assert_eq!(Ok(ReferenciaRevision::default()), ReferenciaRevision::new(1, None, 1));
+        assert_eq!(Ok(0), ReferenciaRevision::boe(0).map(|r| r.meses_desfase));
+        assert_eq!(Err(HipotecaError::entrada_invalida("meses_desfase", "no puede ser negativo")),
+            ReferenciaRevision::new(-1, None, 1));
+        assert_eq!(Err(HipotecaError::entrada_invalida("meses_media", "debe ser al menos 1")),
+            ReferenciaRevision::new(1, Some(TipoSerie::MediaMensual), 0));
+    }
+}
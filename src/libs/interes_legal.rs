@@ -0,0 +1,94 @@
+use chrono::prelude::*;
+use super::lib::*;
+use super::importe::*;
+
+/// Fecha de entrada en vigor de la Ley 1/2013, que limita el interés de
+/// demora de los préstamos para vivienda habitual a tres veces el
+/// interés legal del dinero
+pub fn fecha_ley_1_2013() -> Date<Utc> {
+    Utc.ymd(2013, 5, 15)
+}
+/// Fecha de entrada en vigor de la Ley 5/2019, que fija el interés de
+/// demora en el interés remuneratorio más tres puntos
+pub fn fecha_ley_5_2019() -> Date<Utc> {
+    Utc.ymd(2019, 6, 16)
+}
+
+/// Interés legal del dinero fijado por las leyes de presupuestos
+pub struct InteresLegal {
+    /// Tramos (fecha desde la que se aplica, tipo en tanto por ciento),
+    /// ordenados por fecha
+    tramos: Vec<(Date<Utc>, f64)>,
+}
+
+impl InteresLegal {
+    pub fn new() -> Self {
+        InteresLegal { tramos: Self::read_interes_legal_table() }
+    }
+    /// Valores en tanto por ciento del interés legal del dinero
+    fn read_interes_legal_table() -> Vec<(Date<Utc>, f64)> {
+        let anuales: Vec<(i32, f64)> = vec![(2000, 4.25), (2001, 5.50), (2002, 4.25),
+            (2003, 4.25), (2004, 3.75), (2005, 4.00), (2006, 4.00), (2007, 5.00), (2008, 5.50),
+            (2009, 5.50), (2010, 4.00), (2011, 4.00), (2012, 4.00), (2013, 4.00), (2014, 4.00),
+            (2015, 3.50), (2016, 3.00), (2017, 3.00), (2018, 3.00), (2019, 3.00), (2020, 3.00),
+            (2021, 3.00), (2022, 3.00), (2023, 3.25), (2024, 3.25), (2025, 3.25)];
+        let mut tramos: Vec<(Date<Utc>, f64)> = anuales.iter()
+            .map(|(agno, tipo)| (Utc.ymd(*agno, 1, 1), *tipo))
+            .collect();
+        // En 2009 el tipo se redujo a partir del 1 de abril (RDL 3/2009)
+        tramos.push((Utc.ymd(2009, 4, 1), 4.00));
+        tramos.sort_by_key(|x| x.0);
+        tramos
+    }
+    /// Devuelve el interés legal del dinero (en tanto por uno) vigente
+    /// en una fecha. Para fechas posteriores al último tramo de la tabla
+    /// se mantiene el último valor, como ocurre con los presupuestos prorrogados
+    pub fn interes_legal(&self, fecha: Date<Utc>) -> f64 {
+        let tipo = self.tramos.iter()
+            .rev()
+            .find(|x| x.0 <= fecha)
+            .map(|x| x.1)
+            .unwrap_or(self.tramos[0].1);
+        redondea_cinco_decimales(tipo/100.0)
+    }
+    /// Intereses legales de un importe entre dos fechas, calculados
+    /// día a día con el tipo vigente cada día sobre una base de 365 días
+    pub fn intereses(&self, importe: Importe, desde: Date<Utc>, hasta: Date<Utc>) -> Importe {
+        let mut factor = Decimal::ZERO;
+        let mut fecha = desde;
+        while fecha < hasta {
+            factor += tipo(self.interes_legal(fecha)) / Decimal::from(365);
+            fecha = fecha.succ();
+        }
+        redondea_dos_decimales(importe * factor)
+    }
+}
+impl Default for InteresLegal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_interes_legal() {
+        let il = InteresLegal::new();
+        assert_eq!(0.0375, il.interes_legal(Utc.ymd(2004, 6, 1)));
+        assert_eq!(0.055, il.interes_legal(Utc.ymd(2009, 3, 31)));
+        assert_eq!(0.04, il.interes_legal(Utc.ymd(2009, 4, 1)));
+        assert_eq!(0.03, il.interes_legal(Utc.ymd(2020, 12, 31)));
+        assert_eq!(0.0325, il.interes_legal(Utc.ymd(2023, 1, 1)));
+    }
+    #[test]
+    fn test_intereses() {
+        let il = InteresLegal::new();
+        assert_eq!(dec!(30), il.intereses(dec!(1000), Utc.ymd(2018, 1, 1), Utc.ymd(2019, 1, 1)));
+        // 90 días al 5,5% y 275 al 4%
+        assert_eq!(redondea_dos_decimales(dec!(1000)*(dec!(90)*dec!(0.055) + dec!(275)*dec!(0.04))/dec!(365)), 
+            il.intereses(dec!(1000), Utc.ymd(2009, 1, 1), Utc.ymd(2010, 1, 1)));
+    }
+}
@@ -1,97 +1,158 @@
 use chrono::prelude::*;
+use super::importe::*;
+use super::error::*;
 
 
+/// Fecha del día indicado de un mes. Si el mes tiene menos días,
+/// el último día del mes
+pub fn fecha_mes(agno: i32, mes: u32, dia: u32) -> Date<Utc> {
+    let ultimo = last_day_of_month(agno, mes).unwrap_or(28);
+    Utc.ymd(agno, mes, std::cmp::min(dia, ultimo))
+}
 pub fn add_one_month(date: Date<Utc>) -> Date<Utc> {
-    let agno: i32 = match date.month() {
-        12 => date.year()+1,
-        _ =>  date.year()
-    };
-    let mes: u32 = match date.month() {
-        12 => 1,
-        _ => date.month()+1
-    };
-    let dia: u32 = match date.month() {
-        1 => {
-                if date.day() > 28 {
-                    if is_leap_year(date.year()) {29} else {28}
-                } else { date.day()}
-            }, 
-        2 | 4 | 6 | 9 | 11 => date.day(),
-        3 | 5 | 7 | 8 | 10 | 12 => if date.day() == 31 { 30} else {date.day()}
-        _ => panic!("Invalid month")
-    };
-    Utc.ymd(agno, mes, dia)
+    add_n_months(date, 1)
 }
+/// Suma meses a una fecha conservando su día. Si el mes de destino tiene
+/// menos días se toma el último; el recorte no se arrastra a los meses
+/// siguientes, de modo que el 31 de enero más dos meses es el 31 de marzo
 pub fn add_n_months(date: Date<Utc>, num_meses: i32) -> Date<Utc> {
-    let mut fecha:Date<Utc> = date;
-    for _i in 1..num_meses+1 {
-        fecha = add_one_month(fecha);
-    }
-    fecha
+    let meses = date.year() * 12 + date.month0() as i32 + num_meses;
+    fecha_mes(meses.div_euclid(12), meses.rem_euclid(12) as u32 + 1, date.day())
 }
-pub fn mes_anterior(year: i32, month: u32) -> (i32, u32) {
-    if month>12 {
-        panic!("Invalid month!");
-    }
+pub fn mes_anterior(year: i32, month: u32) -> Result<(i32, u32)> {
     match month {
-        1 => (year-1, 12),
-        _ => (year, month-1)
+        1 => Ok((year-1, 12)),
+        2..=12 => Ok((year, month-1)),
+        _ => Err(HipotecaError::entrada_invalida("mes", &format!("{} no es un mes válido", month))),
     }
 }
 pub fn is_leap_year(year: i32) -> bool {
-    return year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
 }
-pub fn last_day_of_month(year: i32, month: u32) -> u32 {
+pub fn last_day_of_month(year: i32, month: u32) -> Result<u32> {
     match month {
-        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
-        4 | 6 | 9 | 11 => 30,
-        2 => if is_leap_year(year) { 29 } else { 28 },
-        _ => panic!("invalid month: {}" , month),
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => Ok(31),
+        4 | 6 | 9 | 11 => Ok(30),
+        2 => Ok(if is_leap_year(year) { 29 } else { 28 }),
+        _ => Err(HipotecaError::entrada_invalida("mes", &format!("{} no es un mes válido", month))),
     }
 }
-pub fn mensualidad(c_0: f64, i_anual: f64, meses: i32) -> f64 {
-    let i_mensual: f64 = i_anual/12.0;
-    let a: f64 = c_0 * i_mensual / (1.0 - (1.0+i_mensual).powi(-meses));
-    redondea_dos_decimales(a)
+pub fn mensualidad(redondeo: Redondeo, c_0: Importe, i_anual: f64, meses: i32) -> Importe {
+    redondeo.cuota_mensual(c_0, i_anual, meses)
 }
-pub fn intereses_periodo(capital_pendiente: f64, interes_periodo: f64) -> f64 {
-    redondea_dos_decimales(capital_pendiente*interes_periodo)
+pub fn intereses_periodo(redondeo: Redondeo, capital_pendiente: Importe, interes_periodo: f64) -> Importe {
+    redondeo.importe(capital_pendiente*tipo(interes_periodo))
 }
 /// Calcula el importe mensual a pagar en un prestamo 
 /// con el método de amortización francés (cuotas mensuales iguales)
-pub fn importe_cuota_mensual(capital_pendiente:f64, tipo_interes_anual: f64, meses: i32 ) -> f64 {
-    let i_mensual: f64 = tipo_interes_anual /12.0;
-    let importe_mensualidad: f64 = capital_pendiente * i_mensual / (1.0 - (1.0+i_mensual).powi(-meses));
-    redondea_dos_decimales(importe_mensualidad)
+pub fn importe_cuota_mensual(redondeo: Redondeo, capital_pendiente: Importe, tipo_interes_anual: f64, 
+        meses: i32 ) -> Importe {
+    redondeo.cuota_mensual(capital_pendiente, tipo_interes_anual, meses)
 }
 /// Calcula los intereses a pagar en un mes a partir
-/// del capital pendiente y el tipo de interés anual
-pub fn intereses_mes(capital_pendiente: f64, tipo_interes_anual: f64) -> f64 {
-    redondea_dos_decimales(capital_pendiente*tipo_interes_anual/12.0)
+/// del capital pendiente y el tipo de interés anual (base 30/360)
+pub fn intereses_mes(redondeo: Redondeo, capital_pendiente: Importe, tipo_interes_anual: f64) -> Importe {
+    redondeo.intereses_mes(capital_pendiente, tipo_interes_anual)
+}
+/// Calcula el número de cuotas mensuales de importe `cuota` necesarias
+/// para amortizar un capital con el método francés. La última cuota
+/// puede resultar menor que las demás
+pub fn meses_para_amortizar(capital_pendiente: Importe, tipo_interes_anual: f64, cuota: Importe) -> i32 {
+    if capital_pendiente <= Importe::ZERO {
+        return 0;
+    }
+    let capital_pendiente = a_f64(capital_pendiente);
+    let cuota = a_f64(cuota);
+    let i_mensual: f64 = tipo_interes_anual / 12.0;
+    if i_mensual == 0.0 {
+        return (capital_pendiente / cuota).ceil() as i32;
+    }
+    let n = -(1.0 - capital_pendiente * i_mensual / cuota).ln() / (1.0 + i_mensual).ln();
+    // Se redondea antes de tomar el entero superior para no sumar un mes
+    // por el redondeo de la cuota; el descuadre lo absorbe la última cuota
+    ((n*100.0).round()/100.0).ceil() as i32
+}
+/// Años completos transcurridos entre dos fechas
+pub fn agnos_completos(desde: Date<Utc>, hasta: Date<Utc>) -> i32 {
+    let mut agnos = hasta.year() - desde.year();
+    if (hasta.month(), hasta.day()) < (desde.month(), desde.day()) {
+        agnos -= 1;
+    }
+    agnos
 }
 
-pub fn capital_periodo(a: f64, interes: f64) -> f64 {
+pub fn capital_periodo(a: Importe, interes: Importe) -> Importe {
     redondea_dos_decimales(a-interes)
 }
-pub fn redondea_dos_decimales(valor:f64) -> f64 {
-    (valor*100.0).round()/100.0
+/// Ajusta un tipo de interés a los límites mínimo y máximo, si los hay
+pub fn ajusta_limites(tipo: f64, i_min: Option<f64>, i_max: Option<f64>) -> f64 {
+    let mut tipo = tipo;
+    if let Some(i_min) = i_min {
+        if tipo < i_min {
+            tipo = i_min;
+        }
+    }
+    if let Some(i_max) = i_max {
+        if tipo > i_max {
+            tipo = i_max;
+        }
+    }
+    redondea_cinco_decimales(tipo)
+}
+/// Redondea un importe a céntimos con el redondeo por defecto, las
+/// mitades hacia arriba. Los cálculos de la hipoteca usan el redondeo
+/// pactado; este sirve para sumas y diferencias de importes ya redondeados
+pub fn redondea_dos_decimales(valor: Importe) -> Importe {
+    Redondeo::default().importe(valor)
 }
 pub fn redondea_cinco_decimales(valor:f64) -> f64 {
     (valor*100000.0).round()/100000.0
 }
+/// Interpreta una fecha escrita como dd/mm/aaaa o aaaa-mm-dd. Devuelve
+/// `None` si no tiene ninguno de esos formatos o no es una fecha válida
+pub fn parse_fecha(cad: &str) -> Option<Date<Utc>> {
+    let cad = cad.trim();
+    let (partes, agno_primero): (Vec<&str>, bool) = if cad.contains('/') {
+        (cad.split('/').collect(), false)
+    } else {
+        (cad.split('-').collect(), true)
+    };
+    if partes.len() != 3 || partes[if agno_primero { 0 } else { 2 }].len() != 4 {
+        return None;
+    }
+    let numeros: Vec<u32> = partes.iter().map(|x| x.parse().ok()).collect::<Option<Vec<u32>>>()?;
+    let (agno, mes, dia) = if agno_primero {
+        (numeros[0], numeros[1], numeros[2])
+    } else {
+        (numeros[2], numeros[1], numeros[0])
+    };
+    Utc.ymd_opt(agno as i32, mes, dia).single()
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::dec;
     #[test]
     fn test_mes_anterior() {
-        assert_eq!((2020, 2), mes_anterior(2020,3));
-        assert_eq!((2020, 12), mes_anterior(2021,1));
+        assert_eq!(Ok((2020, 2)), mes_anterior(2020,3));
+        assert_eq!(Ok((2020, 12)), mes_anterior(2021,1));
+    }
+    #[test]
+    fn test_parse_fecha() {
+        assert_eq!(Some(Utc.ymd(2004, 4, 17)), parse_fecha("17/04/2004"));
+        assert_eq!(Some(Utc.ymd(2004, 4, 7)), parse_fecha("7/4/2004"));
+        assert_eq!(Some(Utc.ymd(2004, 4, 17)), parse_fecha("2004-04-17"));
+        assert_eq!(None, parse_fecha("38094"));
+        assert_eq!(None, parse_fecha("17-04-2004"));
+        assert_eq!(None, parse_fecha("17/04/04"));
+        assert_eq!(None, parse_fecha("31/02/2004"));
+        assert_eq!(None, parse_fecha("Fecha"));
     }
     #[test]
-    #[should_panic]
-    fn test_mes_anterior_panic() {
-        mes_anterior(2020,13);
+    fn test_mes_anterior_error() {
+        assert!(mes_anterior(2020,13).is_err());
+        assert!(mes_anterior(2020,0).is_err());
     }
     #[test]
     fn test_is_leap_year() {
@@ -101,14 +162,14 @@ mod tests {
     }
     #[test]
     fn test_last_day_of_month() {
-        assert_eq!(31, last_day_of_month(2020, 8));
-        assert_eq!(29, last_day_of_month(2000, 2));
-        assert_eq!(28, last_day_of_month(2100, 2));
+        assert_eq!(Ok(31), last_day_of_month(2020, 8));
+        assert_eq!(Ok(29), last_day_of_month(2000, 2));
+        assert_eq!(Ok(28), last_day_of_month(2100, 2));
     }
     #[test]
-    #[should_panic]
-    fn test_last_day_of_month_panic() {
-        last_day_of_month(2020, 25);
+    fn test_last_day_of_month_error() {
+        assert_eq!(Err(HipotecaError::entrada_invalida("mes", "25 no es un mes válido")), 
+            last_day_of_month(2020, 25));
     }
 
     #[test]
@@ -173,67 +234,96 @@ mod tests {
         assert_eq!(4, dt2.month());
         assert_eq!(30, dt2.day());
 
-        /*let dt = Utc.ymd(2022, 5, 31);
-        let dt2 = add_month(dt);
+        let dt = Utc.ymd(2022, 5, 31);
+        let dt2 = add_one_month(dt);
         assert_eq!(2022, dt2.year());
         assert_eq!(6, dt2.month());
         assert_eq!(30, dt2.day());
 
         let dt = Utc.ymd(2022, 7, 31);
-        let dt2 = add_month(dt);
+        let dt2 = add_one_month(dt);
         assert_eq!(2022, dt2.year());
         assert_eq!(8, dt2.month());
         assert_eq!(31, dt2.day());
 
         let dt = Utc.ymd(2022, 8, 31);
-        let dt2 = add_month(dt);
+        let dt2 = add_one_month(dt);
         assert_eq!(2022, dt2.year());
         assert_eq!(9, dt2.month());
         assert_eq!(30, dt2.day());
 
         let dt = Utc.ymd(2022, 10, 31);
-        let dt2 = add_month(dt);
+        let dt2 = add_one_month(dt);
         assert_eq!(2022, dt2.year());
         assert_eq!(11, dt2.month());
         assert_eq!(30, dt2.day());
 
         let dt = Utc.ymd(2022, 12, 31);
-        let dt2 = add_month(dt);
+        let dt2 = add_one_month(dt);
         assert_eq!(2023, dt2.year());
         assert_eq!(1, dt2.month());
         assert_eq!(31, dt2.day());
-        */
     }
     #[test]
     fn test_add_n_months() {
         let fecha = Utc.ymd(2004, 3, 17);
         let meses = 300;
         assert_eq!(Utc.ymd(2029, 3, 17), add_n_months(fecha, meses));
+        // El día recortado en febrero no se arrastra
+        assert_eq!(Utc.ymd(2022, 3, 31), add_n_months(Utc.ymd(2022, 1, 31), 2));
+        assert_eq!(Utc.ymd(2024, 2, 29), add_n_months(Utc.ymd(2023, 12, 31), 2));
+        assert_eq!(Utc.ymd(2021, 11, 30), add_n_months(Utc.ymd(2022, 1, 30), -2));
+    }
+    #[test]
+    fn test_fecha_mes() {
+        assert_eq!(Utc.ymd(2022, 4, 30), fecha_mes(2022, 4, 31));
+        assert_eq!(Utc.ymd(2022, 5, 17), fecha_mes(2022, 5, 17));
     }
     #[test]
     fn test_interes_periodo() {
-        let int:f64 = intereses_periodo(84140.0, 0.04/12.0);
-        assert_eq!(280.47, int);
+        let int: Importe = intereses_periodo(Redondeo::MitadArriba, dec!(84140), 0.04/12.0);
+        assert_eq!(dec!(280.47), int);
+        assert_eq!(dec!(280.46), intereses_periodo(Redondeo::Truncamiento, dec!(84140), 0.04/12.0));
     }
     #[test]
     fn test_capital_periodo() {
-        assert_eq!(163.65, capital_periodo(444.12, 280.47));
+        assert_eq!(dec!(163.65), capital_periodo(dec!(444.12), dec!(280.47)));
     }
     #[test]
     fn test_mensualidad() {
-        let c_0: f64 = 84140.0;
+        let c_0: Importe = dec!(84140);
         let i_anual: f64 = 0.04;
         let meses: i32 = 300;
-        assert_eq!(444.12, mensualidad(c_0, i_anual, meses));
+        assert_eq!(dec!(444.12), mensualidad(Redondeo::MitadArriba, c_0, i_anual, meses));
+    }
+    #[test]
+    fn test_meses_para_amortizar() {
+        assert_eq!(300, meses_para_amortizar(dec!(84140), 0.04, dec!(444.12)));
+        assert_eq!(240, meses_para_amortizar(dec!(84140), 0.04, dec!(509.87)));
+        assert_eq!(10, meses_para_amortizar(dec!(1000), 0.0, dec!(100)));
+        assert_eq!(0, meses_para_amortizar(dec!(0), 0.04, dec!(444.12)));
+    }
+    #[test]
+    fn test_agnos_completos() {
+        assert_eq!(0, agnos_completos(Utc.ymd(2020, 3, 17), Utc.ymd(2021, 3, 16)));
+        assert_eq!(1, agnos_completos(Utc.ymd(2020, 3, 17), Utc.ymd(2021, 3, 17)));
+        assert_eq!(9, agnos_completos(Utc.ymd(2004, 3, 17), Utc.ymd(2014, 1, 1)));
+    }
+    #[test]
+    fn test_ajusta_limites() {
+        assert_eq!(0.04, ajusta_limites(0.01362, Some(0.04), Some(0.12)));
+        assert_eq!(0.01362, ajusta_limites(0.01362, None, Some(0.12)));
+        assert_eq!(0.12, ajusta_limites(0.15393, None, Some(0.12)));
+        assert_eq!(0.15393, ajusta_limites(0.15393, None, None));
     }
     #[test]
     fn test_redondea_dos_decimales() {
-        let x: f64 = 1324.7856;
-        assert_eq!(1324.79, redondea_dos_decimales(x));
-        let x:f64 = 1324.7816;
-        assert_eq!(1324.78, redondea_dos_decimales(x));
-        let x:f64 = 1324.785;
-        assert_eq!(1324.79, redondea_dos_decimales(x));
+        let x: Importe = dec!(1324.7856);
+        assert_eq!(dec!(1324.79), redondea_dos_decimales(x));
+        let x: Importe = dec!(1324.7816);
+        assert_eq!(dec!(1324.78), redondea_dos_decimales(x));
+        let x: Importe = dec!(1324.785);
+        assert_eq!(dec!(1324.79), redondea_dos_decimales(x));
     }
 
 }
\ No newline at end of file
@@ -0,0 +1,202 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+use super::interes_legal::*;
+use super::base_calculo::BaseCalculo;
+
+/// Línea de la liquidación del impago: una cuota vencida y no pagada
+pub struct LineaImpago {
+    /// Fecha de vencimiento de la cuota
+    pub fecha: Date<Utc>,
+    /// Tipo de interés ordinario aplicado en la cuota
+    pub tipo_interes: f64,
+    /// Principal vencido de la cuota
+    pub capital: Importe,
+    /// Intereses ordinarios de la cuota
+    pub intereses_ordinarios: Importe,
+    /// Días desde el vencimiento hasta la fecha de la liquidación
+    pub dias_demora: i64,
+    /// Intereses de demora devengados por el principal vencido
+    pub intereses_demora: Importe,
+}
+impl LineaImpago {
+    pub fn to_csv_string(&self) -> String {
+        format!("{}/{}/{}; {}; {}; {}; {}; {}", 
+            self.fecha.day(), self.fecha.month(), self.fecha.year(),
+            self.tipo_interes.to_string().replace(".", ","),
+            self.capital.to_string().replace(".", ","),
+            self.intereses_ordinarios.to_string().replace(".", ","),
+            self.dias_demora,
+            self.intereses_demora.to_string().replace(".", ","))
+    }
+}
+
+/// Liquidación de las cuotas impagadas a una fecha, con el principal, 
+/// los intereses ordinarios y los intereses de demora por separado
+pub struct LiquidacionImpago {
+    /// Fecha a la que se calcula la liquidación
+    pub fecha: Date<Utc>,
+    pub lineas: Vec<LineaImpago>,
+    /// Gastos repercutidos pendientes de pago
+    pub gastos: Importe,
+}
+
+impl LiquidacionImpago {
+    pub fn new(fecha: Date<Utc>) -> Self {
+        LiquidacionImpago { fecha, lineas: Vec::<LineaImpago>::new(), gastos: Importe::ZERO }
+    }
+    pub fn push(&mut self, linea: LineaImpago) {
+        self.lineas.push(linea);
+    }
+    pub fn total_capital(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.capital).sum())
+    }
+    pub fn total_intereses_ordinarios(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.intereses_ordinarios).sum())
+    }
+    pub fn total_intereses_demora(&self) -> Importe {
+        redondea_dos_decimales(self.lineas.iter().map(|x| x.intereses_demora).sum())
+    }
+    /// Importe total adeudado en la fecha de la liquidación
+    pub fn total(&self) -> Importe {
+        redondea_dos_decimales(self.total_capital() + self.total_intereses_ordinarios() 
+            + self.total_intereses_demora() + self.gastos)
+    }
+    pub fn disp(&self) {
+        for linea in self.lineas.iter() {
+            println!("{}", linea.to_csv_string());
+        }
+        println!("Principal: {:.2} Intereses ordinarios: {:.2} Intereses de demora: {:.2} Gastos: {:.2} Total: {:.2}",
+            self.total_capital(), self.total_intereses_ordinarios(), 
+            self.total_intereses_demora(), self.gastos, self.total());
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        writeln!(&file, "Vencimiento; i; Principal; Intereses; Dias_demora; Intereses_demora")?;
+        for linea in self.lineas.iter() {
+            writeln!(&file, "{}", linea.to_csv_string())?;
+        }
+        writeln!(&file, "Total; ; {}; {}; ; {}", 
+            self.total_capital().to_string().replace(".", ","),
+            self.total_intereses_ordinarios().to_string().replace(".", ","),
+            self.total_intereses_demora().to_string().replace(".", ","))?;
+        if !self.gastos.is_zero() {
+            writeln!(&file, "Gastos; {}", self.gastos.to_string().replace(".", ","))?;
+        }
+        writeln!(&file, "Total adeudado; {}", self.total().to_string().replace(".", ","))?;
+        Ok(())
+    }
+}
+
+/// Tipo de interés de demora aplicable en una fecha:
+/// - Escrituras desde la Ley 5/2019: interés ordinario más tres puntos, sea
+///   cual sea el pactado (art. 25 Ley 5/2019, que no admite pacto en contrario)
+/// - Desde la Ley 1/2013: el pactado, como máximo tres veces el interés
+///   legal del dinero
+/// - Antes de la Ley 1/2013, el tipo pactado sin límite
+///
+/// Si no se pactó interés de demora se aplica el límite legal o, en su
+/// defecto, el interés legal del dinero (art. 1108 CC)
+pub fn tipo_demora_aplicable(il: &InteresLegal, fecha_escritura: Date<Utc>, 
+        interes_demora: Option<f64>, tipo_ordinario: f64, fecha: Date<Utc>) -> f64 {
+    if fecha_escritura >= fecha_ley_5_2019() {
+        return redondea_cinco_decimales(tipo_ordinario + 0.03);
+    }
+    let limite: Option<f64> = if fecha >= fecha_ley_1_2013() {
+        Some(3.0 * il.interes_legal(fecha))
+    } else {
+        None
+    };
+    let tipo = match (interes_demora, limite) {
+        (Some(pactado), Some(limite)) => pactado.min(limite),
+        (Some(pactado), None) => pactado,
+        (None, Some(limite)) => limite,
+        (None, None) => il.interes_legal(fecha),
+    };
+    redondea_cinco_decimales(tipo)
+}
+
+/// Intereses de demora devengados por un principal vencido entre dos fechas, 
+/// calculados día a día con la base de cálculo del contrato. `tipo_ordinario`
+/// da el tipo remuneratorio vigente cada día, del que depende el límite de
+/// la Ley 5/2019. No se capitalizan
+#[allow(clippy::too_many_arguments)]
+pub fn intereses_demora(il: &InteresLegal, base: BaseCalculo, redondeo: Redondeo, 
+        fecha_escritura: Date<Utc>, interes_demora: Option<f64>, tipo_ordinario: impl Fn(Date<Utc>) -> f64, 
+        principal: Importe, desde: Date<Utc>, hasta: Date<Utc>) -> Importe {
+    let mut factor = Decimal::ZERO;
+    let mut fecha = desde;
+    while fecha < hasta {
+        factor += tipo(tipo_demora_aplicable(il, fecha_escritura, interes_demora, 
+            tipo_ordinario(fecha), fecha)) * base.fraccion_anual(fecha, fecha.succ());
+        fecha = fecha.succ();
+    }
+    redondeo.importe(principal * factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_tipo_demora_aplicable() {
+        let il = InteresLegal::new();
+        // Anterior a la Ley 1/2013: el pactado
+        assert_eq!(0.19, tipo_demora_aplicable(&il, Utc.ymd(2004, 3, 17), Some(0.19), 
+            0.04, Utc.ymd(2012, 1, 1)));
+        // Ley 1/2013: tres veces el interés legal
+        assert_eq!(0.09, tipo_demora_aplicable(&il, Utc.ymd(2004, 3, 17), Some(0.19), 
+            0.04, Utc.ymd(2018, 1, 1)));
+        assert_eq!(0.08, tipo_demora_aplicable(&il, Utc.ymd(2004, 3, 17), Some(0.08), 
+            0.04, Utc.ymd(2018, 1, 1)));
+        // Ley 5/2019: ordinario más tres puntos
+        assert_eq!(0.045, tipo_demora_aplicable(&il, Utc.ymd(2020, 3, 17), Some(0.19), 
+            0.015, Utc.ymd(2022, 1, 1)));
+        assert_eq!(0.045, tipo_demora_aplicable(&il, Utc.ymd(2020, 3, 17), None, 
+            0.015, Utc.ymd(2022, 1, 1)));
+        // El pactado inferior no se aplica: siempre ordinario más tres puntos
+        assert_eq!(0.045, tipo_demora_aplicable(&il, Utc.ymd(2020, 3, 17), Some(0.02), 
+            0.015, Utc.ymd(2022, 1, 1)));
+        // Sin pacto ni límite: interés legal
+        assert_eq!(0.04, tipo_demora_aplicable(&il, Utc.ymd(2004, 3, 17), None, 
+            0.04, Utc.ymd(2012, 1, 1)));
+    }
+    #[test]
+    fn test_intereses_demora() {
+        let il = InteresLegal::new();
+        let r = Redondeo::MitadArriba;
+        // 365 días de 2018 al 9% (3 x 3%)
+        assert_eq!(dec!(90), intereses_demora(&il, BaseCalculo::Real365, r, Utc.ymd(2004, 3, 17), 
+            Some(0.19), |_| 0.04, dec!(1000), Utc.ymd(2018, 1, 1), Utc.ymd(2019, 1, 1)));
+        assert_eq!(dec!(0), intereses_demora(&il, BaseCalculo::Real365, r, Utc.ymd(2004, 3, 17), 
+            Some(0.19), |_| 0.04, dec!(1000), Utc.ymd(2018, 1, 1), Utc.ymd(2018, 1, 1)));
+        // 365 días sobre base 360: 1000 x 9% x 365 / 360
+        assert_eq!(dec!(91.25), intereses_demora(&il, BaseCalculo::Real360, r, Utc.ymd(2004, 3, 17), 
+            Some(0.19), |_| 0.04, dec!(1000), Utc.ymd(2018, 1, 1), Utc.ymd(2019, 1, 1)));
+        // Un mes en 30/360: 1000 x 9% / 12
+        assert_eq!(dec!(7.5), intereses_demora(&il, BaseCalculo::Treinta360, r, Utc.ymd(2004, 3, 17), 
+            Some(0.19), |_| 0.04, dec!(1000), Utc.ymd(2018, 1, 31), Utc.ymd(2018, 2, 28)));
+        // Ley 5/2019: el límite sigue al tipo ordinario de cada día. 
+        // 181 días al 4,5% y 184 al 5,5% sobre 365
+        let tipo_ordinario = |fecha: Date<Utc>| if fecha < Utc.ymd(2022, 7, 1) { 0.015 } else { 0.025 };
+        assert_eq!(redondea_dos_decimales(dec!(1000) * (dec!(0.045) * dec!(181) + dec!(0.055) * dec!(184)) 
+            / dec!(365)), intereses_demora(&il, BaseCalculo::Real365, r, Utc.ymd(2020, 3, 17), Some(0.19), 
+            tipo_ordinario, dec!(1000), Utc.ymd(2022, 1, 1), Utc.ymd(2023, 1, 1)));
+    }
+    #[test]
+    fn test_totales() {
+        let mut liq = LiquidacionImpago::new(Utc.ymd(2018, 8, 5));
+        liq.push(LineaImpago { fecha: Utc.ymd(2018, 6, 17), tipo_interes: 0.04, capital: dec!(300),
+            intereses_ordinarios: dec!(150.5), dias_demora: 49, intereses_demora: dec!(3.62) });
+        liq.push(LineaImpago { fecha: Utc.ymd(2018, 7, 17), tipo_interes: 0.04, capital: dec!(301),
+            intereses_ordinarios: dec!(149.5), dias_demora: 19, intereses_demora: dec!(1.41) });
+        assert_eq!(dec!(601), liq.total_capital());
+        assert_eq!(dec!(300), liq.total_intereses_ordinarios());
+        assert_eq!(dec!(5.03), liq.total_intereses_demora());
+        assert_eq!(dec!(906.03), liq.total());
+    }
+}
@@ -1,6 +1,28 @@
 pub mod lib;
+pub mod importe;
+pub mod base_calculo;
+pub mod calendario;
+pub mod sistema_amortizacion;
+pub mod carencia;
+pub mod tramo_interes;
+pub mod redondeo_tipo;
+pub mod error;
 pub mod hipoteca;
 pub mod cuota;
 pub mod tabla_amort;
 pub mod euribor_data;
 pub mod novacion;
+pub mod amortizacion_anticipada;
+pub mod interes_legal;
+pub mod liquidacion_impago;
+pub mod indice_referencia;
+pub mod comparacion_tablas;
+pub mod sustitucion_indice;
+pub mod conciliacion;
+pub mod pagos;
+pub mod cancelacion;
+pub mod buenas_practicas;
+pub mod congelacion_cuota;
+pub mod vencimiento_anticipado;
+pub mod ejecucion;
+pub mod subasta;
@@ -1,10 +1,61 @@
+use chrono::prelude::*;
+use super::importe::Importe;
 
+/// Novación de la hipoteca: modificación de las condiciones del
+/// préstamo a partir de una fecha. Los campos a `None` mantienen
+/// las condiciones vigentes antes de la novación.
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct Novacion {
-
+    /// Fecha desde la que surte efecto la novación. Se aplica
+    /// a la primera cuota que vence en esa fecha o después
+    pub fecha: Date<Utc>,
+    /// Nuevo tipo de interés nominal anual, aplicable hasta
+    /// la siguiente revisión
+    pub tipo_interes_anual: Option<f64>,
+    /// Nuevo incremento (diferencial) sobre el euribor
+    pub incremento_euribor: Option<f64>,
+    /// Nuevo plazo: meses restantes a partir de la novación,
+    /// incluyendo la primera cuota afectada
+    pub meses_restantes: Option<i32>,
+    /// Nuevo tipo mínimo
+    pub i_min: Option<f64>,
+    /// Nuevo tipo máximo
+    pub i_max: Option<f64>,
+    /// Ampliación de capital, que se suma al capital pendiente
+    pub ampliacion_capital: Importe,
 }
 
 impl Novacion {
-    fn new() -> Self {
-        Novacion {  }
+    /// Crea una novación sin cambios en las condiciones, para
+    /// ir rellenando los campos que se modifiquen
+    pub fn new(fecha: Date<Utc>) -> Self {
+        Novacion { fecha, tipo_interes_anual: None, incremento_euribor: None,
+            meses_restantes: None, i_min: None, i_max: None, ampliacion_capital: Importe::ZERO }
+    }
+    /// Indica si la novación cambia alguna de las condiciones
+    /// del tipo de interés variable (diferencial, mínimo o máximo)
+    pub fn cambia_condiciones_revision(&self) -> bool {
+        self.incremento_euribor.is_some() || self.i_min.is_some() || self.i_max.is_some()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+    #[test]
+    fn test_new() {
+        let n = Novacion::new(Utc.ymd(2010, 5, 17));
+        assert_eq!(Utc.ymd(2010, 5, 17), n.fecha);
+        assert_eq!(None, n.tipo_interes_anual);
+        assert_eq!(None, n.meses_restantes);
+        assert_eq!(dec!(0), n.ampliacion_capital);
+        assert!(!n.cambia_condiciones_revision());
+    }
+    #[test]
+    fn test_cambia_condiciones_revision() {
+        let mut n = Novacion::new(Utc.ymd(2010, 5, 17));
+        n.i_min = Some(0.0);
+        assert!(n.cambia_condiciones_revision());
+    }
+}
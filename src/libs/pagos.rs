@@ -0,0 +1,381 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::ops::Deref;
+use umya_spreadsheet::Worksheet;
+use super::lib::*;
+use super::importe::*;
+use super::conciliacion::{parse_fecha_recibo, parse_importe};
+
+/// Concepto de la deuda al que se imputan los pagos
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ConceptoImputacion {
+    /// Gastos repercutidos por la entidad (reclamación, costas...)
+    Gastos,
+    /// Intereses de demora devengados por el principal vencido
+    InteresesDemora,
+    /// Intereses ordinarios de las cuotas vencidas
+    InteresesOrdinarios,
+    /// Principal de las cuotas vencidas
+    Capital,
+}
+
+impl ConceptoImputacion {
+    /// Orden de imputación de los pagos del art. 1173 CC: primero los
+    /// gastos, después los intereses y por último el capital
+    pub fn orden_legal() -> [ConceptoImputacion; 4] {
+        [ConceptoImputacion::Gastos, ConceptoImputacion::InteresesDemora,
+            ConceptoImputacion::InteresesOrdinarios, ConceptoImputacion::Capital]
+    }
+    /// Código del concepto usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            ConceptoImputacion::Gastos => "GASTOS",
+            ConceptoImputacion::InteresesDemora => "DEMORA",
+            ConceptoImputacion::InteresesOrdinarios => "INTERESES",
+            ConceptoImputacion::Capital => "CAPITAL",
+        }
+    }
+    /// Obtiene el concepto a partir de su código
+    pub fn from_codigo(codigo: &str) -> Option<Self> {
+        Self::orden_legal().into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Nombre del concepto, tal como se escribe en los informes
+    pub fn nombre(&self) -> &'static str {
+        match self {
+            ConceptoImputacion::Gastos => "Gastos",
+            ConceptoImputacion::InteresesDemora => "Intereses de demora",
+            ConceptoImputacion::InteresesOrdinarios => "Intereses ordinarios",
+            ConceptoImputacion::Capital => "Capital",
+        }
+    }
+}
+
+/// Pago realizado por el deudor después del impago, total o parcial
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pago {
+    pub fecha: Date<Utc>,
+    pub importe: Importe,
+}
+
+/// Gasto repercutido al deudor por la entidad
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gasto {
+    pub fecha: Date<Utc>,
+    pub importe: Importe,
+    /// Descripción del gasto
+    pub concepto: String,
+}
+
+/// Movimiento de una fila de la hoja de pagos y gastos
+enum Movimiento {
+    Pago(Pago),
+    Gasto(Gasto),
+}
+
+impl Movimiento {
+    /// Interpreta los campos de una fila: tipo (PAGO o GASTO), fecha,
+    /// importe y, en los gastos, su descripción
+    fn from_campos(campos: &[String]) -> Option<Self> {
+        if campos.len() < 3 {
+            return None;
+        }
+        let fecha = parse_fecha_recibo(&campos[1])?;
+        let importe = parse_importe(&campos[2])?;
+        if campos[0].eq_ignore_ascii_case("PAGO") {
+            Some(Movimiento::Pago(Pago { fecha, importe }))
+        } else if campos[0].eq_ignore_ascii_case("GASTO") {
+            let concepto = campos.get(3).cloned().unwrap_or_default();
+            Some(Movimiento::Gasto(Gasto { fecha, importe, concepto }))
+        } else {
+            None
+        }
+    }
+}
+
+/// Interpreta las filas de los pagos del deudor y los gastos repercutidos
+/// después del impago. Como en el histórico de recibos, las filas anteriores
+/// al primer movimiento se toman como cabeceras y se ignoran, igual que las
+/// vacías; cualquier otra fila que no sea un movimiento es un error que
+/// indica su número de línea
+pub fn pagos_y_gastos_de_filas(filas: impl Iterator<Item = Vec<String>>) -> io::Result<(Vec<Pago>, Vec<Gasto>)> {
+    let mut pagos = Vec::new();
+    let mut gastos = Vec::new();
+    for (n, campos) in filas.enumerate() {
+        if campos.iter().all(|x| x.is_empty()) {
+            continue;
+        }
+        match Movimiento::from_campos(&campos) {
+            Some(Movimiento::Pago(pago)) => pagos.push(pago),
+            Some(Movimiento::Gasto(gasto)) => gastos.push(gasto),
+            None if pagos.is_empty() && gastos.is_empty() => continue,
+            None => return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                "la línea {} no es un pago o un gasto (PAGO o GASTO, fecha, importe y concepto): {}", 
+                n + 1, campos.join("; ")))),
+        }
+    }
+    Ok((pagos, gastos))
+}
+
+/// Carga los pagos y gastos posteriores al impago de una hoja de cálculo,
+/// con el tipo, la fecha, el importe y el concepto en las columnas A a D
+pub fn carga_pagos_y_gastos(worksheet: &Worksheet) -> io::Result<(Vec<Pago>, Vec<Gasto>)> {
+    let filas = (1..=worksheet.get_highest_row()).map(|fila| (1..=4)
+        .map(|col| worksheet.get_cell_value_by_column_and_row(&col, &fila).get_value().deref().trim().to_owned())
+        .collect());
+    pagos_y_gastos_de_filas(filas)
+}
+
+/// Parte de un pago imputada a una deuda
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Imputacion {
+    /// Fecha en que se imputa: la del pago o, para lo pagado de más que
+    /// quedó sin imputar, la de la posición deudora
+    pub fecha: Date<Utc>,
+    /// Concepto al que se imputa
+    pub concepto: ConceptoImputacion,
+    /// Vencimiento de la cuota o fecha del gasto al que se imputa
+    pub fecha_deuda: Date<Utc>,
+    pub importe: Importe,
+}
+
+/// Importes pendientes de una cuota vencida
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DeudaCuota {
+    /// Fecha de vencimiento de la cuota
+    pub fecha: Date<Utc>,
+    /// Tipo de interés ordinario aplicado en la cuota
+    pub tipo_interes: f64,
+    /// Principal pendiente
+    pub capital: Importe,
+    /// Intereses ordinarios pendientes
+    pub intereses_ordinarios: Importe,
+    /// Intereses de demora devengados y pendientes
+    pub intereses_demora: Importe,
+    /// Fecha hasta la que se han calculado los intereses de demora
+    devengado_hasta: Date<Utc>,
+}
+
+impl DeudaCuota {
+    pub fn new(fecha: Date<Utc>, tipo_interes: f64, capital: Importe, intereses_ordinarios: Importe) -> Self {
+        DeudaCuota { fecha, tipo_interes, capital, intereses_ordinarios,
+            intereses_demora: Importe::ZERO, devengado_hasta: fecha }
+    }
+    /// Importe total pendiente de la cuota
+    pub fn total(&self) -> Importe {
+        redondea_dos_decimales(self.capital + self.intereses_ordinarios + self.intereses_demora)
+    }
+}
+
+/// Importe pendiente de un gasto
+#[derive(Clone, PartialEq, Debug)]
+pub struct DeudaGasto {
+    pub gasto: Gasto,
+    pub pendiente: Importe,
+}
+
+/// Posición deudora a una fecha: lo que queda por pagar de cada cuota
+/// vencida y de cada gasto después de imputar los pagos realizados
+pub struct PosicionDeudora {
+    /// Fecha de la posición
+    pub fecha: Date<Utc>,
+    pub cuotas: Vec<DeudaCuota>,
+    pub gastos: Vec<DeudaGasto>,
+    pub imputaciones: Vec<Imputacion>,
+    /// Importe pagado que excede de lo vencido y no se ha podido imputar
+    pub pagado_sin_imputar: Importe,
+}
+
+impl PosicionDeudora {
+    /// Calcula la posición deudora imputando los pagos hasta la fecha por
+    /// orden de fecha. En cada pago se devengan primero los intereses de
+    /// demora del principal pendiente y el pago se imputa a lo vencido en
+    /// el orden de conceptos indicado y, dentro de cada concepto, a la deuda
+    /// más antigua. Lo pagado de más se imputa en el pago siguiente o en la
+    /// fecha de la posición. `demora` calcula los intereses de demora de un
+    /// principal entre dos fechas
+    pub fn calcula<F>(fecha: Date<Utc>, cuotas: Vec<DeudaCuota>, gastos: &[Gasto], pagos: &[Pago],
+            orden: &[ConceptoImputacion], demora: F) -> Self
+            where F: Fn(Importe, Date<Utc>, Date<Utc>) -> Importe {
+        let mut cuotas: Vec<DeudaCuota> = cuotas.into_iter().filter(|x| x.fecha <= fecha).collect();
+        cuotas.sort_by_key(|x| x.fecha);
+        let mut gastos: Vec<DeudaGasto> = gastos.iter().filter(|x| x.fecha <= fecha)
+            .map(|x| DeudaGasto { gasto: x.clone(), pendiente: x.importe }).collect();
+        gastos.sort_by_key(|x| x.gasto.fecha);
+        let mut pagos: Vec<&Pago> = pagos.iter().filter(|x| x.fecha <= fecha).collect();
+        pagos.sort_by_key(|x| x.fecha);
+        let mut posicion = PosicionDeudora { fecha, cuotas, gastos,
+            imputaciones: Vec::new(), pagado_sin_imputar: Importe::ZERO };
+        for pago in pagos {
+            posicion.devenga_demora(pago.fecha, &demora);
+            posicion.pagado_sin_imputar += pago.importe;
+            posicion.imputa(pago.fecha, orden);
+        }
+        posicion.devenga_demora(fecha, &demora);
+        if !posicion.pagado_sin_imputar.is_zero() {
+            posicion.imputa(fecha, orden);
+        }
+        posicion
+    }
+    /// Añade los intereses de demora del principal pendiente de cada
+    /// cuota vencida hasta la fecha indicada
+    fn devenga_demora<F>(&mut self, hasta: Date<Utc>, demora: &F)
+            where F: Fn(Importe, Date<Utc>, Date<Utc>) -> Importe {
+        for cuota in self.cuotas.iter_mut().filter(|x| x.devengado_hasta < hasta) {
+            cuota.intereses_demora += demora(cuota.capital, cuota.devengado_hasta, hasta);
+            cuota.devengado_hasta = hasta;
+        }
+    }
+    /// Imputa lo pagado y aún sin imputar a las deudas vencidas en la fecha
+    fn imputa(&mut self, fecha: Date<Utc>, orden: &[ConceptoImputacion]) {
+        for concepto in orden.iter() {
+            let pendientes: Vec<(Date<Utc>, &mut Importe)> = match concepto {
+                ConceptoImputacion::Gastos => self.gastos.iter_mut()
+                    .filter(|x| x.gasto.fecha <= fecha).map(|x| (x.gasto.fecha, &mut x.pendiente)).collect(),
+                ConceptoImputacion::InteresesDemora => self.cuotas.iter_mut()
+                    .filter(|x| x.fecha <= fecha).map(|x| (x.fecha, &mut x.intereses_demora)).collect(),
+                ConceptoImputacion::InteresesOrdinarios => self.cuotas.iter_mut()
+                    .filter(|x| x.fecha <= fecha).map(|x| (x.fecha, &mut x.intereses_ordinarios)).collect(),
+                ConceptoImputacion::Capital => self.cuotas.iter_mut()
+                    .filter(|x| x.fecha <= fecha).map(|x| (x.fecha, &mut x.capital)).collect(),
+            };
+            for (fecha_deuda, pendiente) in pendientes {
+                let importe = self.pagado_sin_imputar.min(*pendiente);
+                if importe <= Importe::ZERO {
+                    continue;
+                }
+                *pendiente = redondea_dos_decimales(*pendiente - importe);
+                self.pagado_sin_imputar = redondea_dos_decimales(self.pagado_sin_imputar - importe);
+                self.imputaciones.push(Imputacion { fecha, concepto: *concepto, fecha_deuda, importe });
+            }
+        }
+    }
+    pub fn total_capital(&self) -> Importe {
+        redondea_dos_decimales(self.cuotas.iter().map(|x| x.capital).sum())
+    }
+    pub fn total_intereses_ordinarios(&self) -> Importe {
+        redondea_dos_decimales(self.cuotas.iter().map(|x| x.intereses_ordinarios).sum())
+    }
+    pub fn total_intereses_demora(&self) -> Importe {
+        redondea_dos_decimales(self.cuotas.iter().map(|x| x.intereses_demora).sum())
+    }
+    pub fn total_gastos(&self) -> Importe {
+        redondea_dos_decimales(self.gastos.iter().map(|x| x.pendiente).sum())
+    }
+    /// Importe imputado de los pagos a un concepto
+    pub fn total_imputado(&self, concepto: ConceptoImputacion) -> Importe {
+        redondea_dos_decimales(self.imputaciones.iter()
+            .filter(|x| x.concepto == concepto).map(|x| x.importe).sum())
+    }
+    /// Importe total adeudado en la fecha de la posición, descontado lo
+    /// pagado de más
+    pub fn total(&self) -> Importe {
+        redondea_dos_decimales(self.total_capital() + self.total_intereses_ordinarios()
+            + self.total_intereses_demora() + self.total_gastos() - self.pagado_sin_imputar)
+    }
+    pub fn disp(&self) {
+        for imputacion in self.imputaciones.iter() {
+            println!("{} {} {} {:.2}", imputacion.fecha, imputacion.concepto.nombre(),
+                imputacion.fecha_deuda, imputacion.importe);
+        }
+        println!("Principal: {:.2} Intereses ordinarios: {:.2} Intereses de demora: {:.2} Gastos: {:.2} Total: {:.2}",
+            self.total_capital(), self.total_intereses_ordinarios(),
+            self.total_intereses_demora(), self.total_gastos(), self.total());
+    }
+    /// Escribe en <nombre>.txt las imputaciones de los pagos y lo que
+    /// queda pendiente de cada cuota y gasto
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let fecha = |f: Date<Utc>| format!("{}/{}/{}", f.day(), f.month(), f.year());
+        writeln!(&file, "Imputaciones")?;
+        writeln!(&file, "Fecha; Concepto; Deuda; Importe")?;
+        for imputacion in self.imputaciones.iter() {
+            writeln!(&file, "{}; {}; {}; {}", fecha(imputacion.fecha), imputacion.concepto.nombre(),
+                fecha(imputacion.fecha_deuda), imputacion.importe.to_string().replace(".", ","))?;
+        }
+        writeln!(&file, "Pendiente a {}", fecha(self.fecha))?;
+        writeln!(&file, "Vencimiento; i; Principal; Intereses; Intereses_demora")?;
+        for cuota in self.cuotas.iter() {
+            writeln!(&file, "{}; {}; {}; {}; {}", fecha(cuota.fecha),
+                cuota.tipo_interes.to_string().replace(".", ","),
+                cuota.capital.to_string().replace(".", ","),
+                cuota.intereses_ordinarios.to_string().replace(".", ","),
+                cuota.intereses_demora.to_string().replace(".", ","))?;
+        }
+        for gasto in self.gastos.iter() {
+            writeln!(&file, "{}; {}; {}", fecha(gasto.gasto.fecha), gasto.gasto.concepto,
+                gasto.pendiente.to_string().replace(".", ","))?;
+        }
+        writeln!(&file, "Total adeudado; {}", self.total().to_string().replace(".", ","))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    /// Demora del 10% anual por días sobre 360, para simplificar las cuentas
+    fn demora(principal: Importe, desde: Date<Utc>, hasta: Date<Utc>) -> Importe {
+        redondea_dos_decimales(principal * dec!(0.1) * Decimal::from((hasta - desde).num_days()) / dec!(360))
+    }
+
+    #[test]
+    fn test_orden_legal() {
+        let cuotas = vec![DeudaCuota::new(Utc.ymd(2020, 1, 1), 0.04, dec!(360), dec!(100)),
+            DeudaCuota::new(Utc.ymd(2020, 2, 1), 0.04, dec!(360), dec!(100))];
+        let gastos = vec![Gasto { fecha: Utc.ymd(2020, 1, 15), importe: dec!(30), concepto: String::from("Reclamación") }];
+        let pagos = vec![Pago { fecha: Utc.ymd(2020, 1, 31), importe: dec!(200) }];
+        let p = PosicionDeudora::calcula(Utc.ymd(2020, 1, 31), cuotas.clone(), &gastos, &pagos,
+            &ConceptoImputacion::orden_legal(), demora);
+        // 30 días de demora sobre 360: 3 euros
+        assert_eq!(dec!(30), p.total_imputado(ConceptoImputacion::Gastos));
+        assert_eq!(dec!(3), p.total_imputado(ConceptoImputacion::InteresesDemora));
+        assert_eq!(dec!(100), p.total_imputado(ConceptoImputacion::InteresesOrdinarios));
+        assert_eq!(dec!(67), p.total_imputado(ConceptoImputacion::Capital));
+        // La segunda cuota aún no ha vencido
+        assert_eq!(1, p.cuotas.len());
+        assert_eq!(dec!(293), p.total());
+        // Con otro orden, el pago va primero al capital
+        let orden = [ConceptoImputacion::Capital, ConceptoImputacion::InteresesOrdinarios];
+        let p = PosicionDeudora::calcula(Utc.ymd(2020, 3, 1), cuotas, &gastos, &pagos, &orden, demora);
+        assert_eq!(dec!(200), p.total_imputado(ConceptoImputacion::Capital));
+        assert_eq!(dec!(160), p.cuotas[0].capital);
+        // Demora de la primera cuota: 3 euros hasta el pago y 160 x 10% x 30 / 360 después
+        assert_eq!(dec!(4.33), p.cuotas[0].intereses_demora);
+        assert_eq!(dec!(30), p.total_gastos());
+    }
+    #[test]
+    fn test_pagado_de_mas() {
+        let cuotas = vec![DeudaCuota::new(Utc.ymd(2020, 1, 1), 0.04, dec!(300), dec!(100))];
+        let pagos = vec![Pago { fecha: Utc.ymd(2020, 1, 1), importe: dec!(500) }];
+        let p = PosicionDeudora::calcula(Utc.ymd(2020, 1, 1), cuotas, &[], &pagos,
+            &ConceptoImputacion::orden_legal(), demora);
+        assert_eq!(dec!(100), p.pagado_sin_imputar);
+        assert_eq!(dec!(-100), p.total());
+    }
+    #[test]
+    fn test_pagos_y_gastos_de_filas() {
+        let filas = |texto: &str| texto.lines()
+            .map(|linea| linea.split(';').map(|x| x.trim().to_owned()).collect::<Vec<String>>())
+            .collect::<Vec<_>>();
+        let (pagos, gastos) = pagos_y_gastos_de_filas(filas(
+            "Tipo; Fecha; Importe; Concepto\n\nPAGO; 31/01/2020; 200,00\ngasto; 15/01/2020; 30; Reclamación")
+            .into_iter()).unwrap();
+        assert_eq!(vec![Pago { fecha: Utc.ymd(2020, 1, 31), importe: dec!(200) }], pagos);
+        assert_eq!(vec![Gasto { fecha: Utc.ymd(2020, 1, 15), importe: dec!(30), 
+            concepto: String::from("Reclamación") }], gastos);
+        let error = pagos_y_gastos_de_filas(filas("PAGO; 31/01/2020; 200\nCOMISION; 1/02/2020; 10")
+            .into_iter()).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(error.to_string().starts_with("la línea 2 "));
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(ConceptoImputacion::InteresesDemora), ConceptoImputacion::from_codigo("demora"));
+        assert_eq!(None, ConceptoImputacion::from_codigo("COMISIONES"));
+    }
+}
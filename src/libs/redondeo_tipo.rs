@@ -0,0 +1,125 @@
+use rust_decimal::prelude::*;
+use super::importe::*;
+use super::lib::redondea_cinco_decimales;
+use super::comparacion_tablas::ComparacionTablas;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Cláusula de redondeo del tipo de interés revisado (índice más
+/// diferencial), por ejemplo al octavo o al cuarto de punto. El paso
+/// se indica en tanto por uno: 0,00125 es un octavo de punto. Los
+/// tribunales han anulado la cláusula que solo redondea al alza
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum RedondeoTipo {
+    /// Solo se redondea a cinco decimales
+    #[default]
+    SinRedondeo,
+    /// Al múltiplo del paso más próximo; las mitades hacia arriba
+    Cercano(f64),
+    /// Al múltiplo del paso inmediatamente superior
+    Arriba(f64),
+    /// Al múltiplo del paso inmediatamente inferior
+    Abajo(f64),
+}
+
+impl RedondeoTipo {
+    /// Código del redondeo usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            RedondeoTipo::SinRedondeo => "NINGUNO",
+            RedondeoTipo::Cercano(_) => "CERCANO",
+            RedondeoTipo::Arriba(_) => "ARRIBA",
+            RedondeoTipo::Abajo(_) => "ABAJO",
+        }
+    }
+    /// Obtiene el redondeo a partir de su código y del paso en tanto por uno
+    pub fn from_codigo(codigo: &str, paso: f64) -> Option<Self> {
+        [RedondeoTipo::SinRedondeo, RedondeoTipo::Cercano(paso), RedondeoTipo::Arriba(paso),
+            RedondeoTipo::Abajo(paso)]
+            .into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Nombre del redondeo, tal como se escribe en los informes
+    pub fn nombre(&self) -> String {
+        match self {
+            RedondeoTipo::SinRedondeo => String::from("sin redondeo"),
+            RedondeoTipo::Cercano(paso) => format!("al {} más próximo", RedondeoTipo::puntos(*paso)),
+            RedondeoTipo::Arriba(paso) => format!("al {} superior", RedondeoTipo::puntos(*paso)),
+            RedondeoTipo::Abajo(paso) => format!("al {} inferior", RedondeoTipo::puntos(*paso)),
+        }
+    }
+    /// Paso expresado en puntos porcentuales
+    fn puntos(paso: f64) -> String {
+        format!("múltiplo de {} puntos", redondea_cinco_decimales(paso * 100.0))
+    }
+    /// Redondea un tipo de interés según la cláusula. Se opera con
+    /// decimales exactos para que un tipo que ya es múltiplo del paso
+    /// no cambie por errores de coma flotante
+    pub fn aplica(&self, tipo_interes: f64) -> f64 {
+        let (paso, estrategia) = match self {
+            RedondeoTipo::SinRedondeo => return redondea_cinco_decimales(tipo_interes),
+            RedondeoTipo::Cercano(paso) => (*paso, RoundingStrategy::MidpointAwayFromZero),
+            RedondeoTipo::Arriba(paso) => (*paso, RoundingStrategy::ToPositiveInfinity),
+            RedondeoTipo::Abajo(paso) => (*paso, RoundingStrategy::ToNegativeInfinity),
+        };
+        let paso = tipo(paso).round_dp(5);
+        if paso <= Decimal::ZERO {
+            return redondea_cinco_decimales(tipo_interes);
+        }
+        let pasos = (tipo(tipo_interes).round_dp(5) / paso).round_dp_with_strategy(0, estrategia);
+        redondea_cinco_decimales((pasos * paso).to_f64().unwrap_or(tipo_interes))
+    }
+}
+
+/// Coste de la cláusula de redondeo: cuotas cobradas con el redondeo
+/// pactado frente a las que resultan con otro redondeo (normalmente
+/// sin redondeo o al más próximo)
+pub struct CosteRedondeo {
+    /// Redondeo pactado en la escritura
+    pub redondeo_pactado: RedondeoTipo,
+    /// Redondeo con el que se recalculan las cuotas
+    pub redondeo_alternativo: RedondeoTipo,
+    /// Tabla de amortización con el redondeo alternativo
+    pub tabla: TablaAmortizacion,
+    /// Diferencias entre las cuotas con el redondeo pactado y el alternativo
+    pub comparacion: ComparacionTablas,
+}
+
+impl CosteRedondeo {
+    /// Total cobrado de más por aplicar el redondeo pactado
+    pub fn total_cobrado_de_mas(&self) -> Importe {
+        self.comparacion.total_diferencias()
+    }
+    pub fn disp(&self) {
+        println!("Redondeo pactado {} frente a {}",
+            self.redondeo_pactado.nombre(), self.redondeo_alternativo.nombre());
+        self.comparacion.disp();
+    }
+    /// Escribe la tabla con el redondeo alternativo en <nombre>_sin_redondeo.txt
+    /// y las diferencias por cuota en <nombre>_coste_redondeo.txt
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        self.tabla.print(&(String::from(nombre) + "_sin_redondeo"))?;
+        self.comparacion.print(&(String::from(nombre) + "_coste_redondeo"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_aplica() {
+        assert_eq!(0.01337, RedondeoTipo::SinRedondeo.aplica(0.013372));
+        assert_eq!(0.015, RedondeoTipo::Arriba(0.00125).aplica(0.01376));
+        assert_eq!(0.01375, RedondeoTipo::Arriba(0.00125).aplica(0.01375));
+        assert_eq!(0.01375, RedondeoTipo::Abajo(0.00125).aplica(0.01499));
+        assert_eq!(0.015, RedondeoTipo::Cercano(0.0025).aplica(0.01376));
+        assert_eq!(0.0125, RedondeoTipo::Cercano(0.0025).aplica(0.01374));
+        // Tipos negativos antes de aplicar el suelo
+        assert_eq!(-0.0025, RedondeoTipo::Arriba(0.0025).aplica(-0.0049));
+    }
+    #[test]
+    fn test_from_codigo() {
+        assert_eq!(Some(RedondeoTipo::Arriba(0.00125)), RedondeoTipo::from_codigo("arriba", 0.00125));
+        assert_eq!(Some(RedondeoTipo::SinRedondeo), RedondeoTipo::from_codigo("NINGUNO", 0.0));
+        assert_eq!(None, RedondeoTipo::from_codigo("OCTAVO", 0.00125));
+    }
+}
@@ -0,0 +1,158 @@
+use rust_decimal::prelude::*;
+use super::importe::*;
+use super::lib::meses_para_amortizar;
+
+/// Sistema de amortización del préstamo: cómo se reparte cada cuota
+/// entre capital e intereses a lo largo del plazo
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum SistemaAmortizacion {
+    /// Cuotas constantes entre revisiones (sistema francés)
+    #[default]
+    Frances,
+    /// Cuota de capital constante; los intereses se suman a ella y
+    /// la cuota total decrece (sistema alemán)
+    Aleman,
+    /// Solo se pagan intereses y el capital se devuelve íntegro en
+    /// la última cuota (sistema americano)
+    Americano,
+    /// Cuota creciente: la cuota aumenta cada año, contado desde la
+    /// primera cuota, el porcentaje indicado en tanto por uno
+    Progresivo(f64),
+}
+
+impl SistemaAmortizacion {
+    /// Código del sistema usado en la hoja de cálculo
+    pub fn codigo(&self) -> &'static str {
+        match self {
+            SistemaAmortizacion::Frances => "FRANCES",
+            SistemaAmortizacion::Aleman => "ALEMAN",
+            SistemaAmortizacion::Americano => "AMERICANO",
+            SistemaAmortizacion::Progresivo(_) => "PROGRESIVO",
+        }
+    }
+    /// Obtiene el sistema a partir de su código. El sistema progresivo
+    /// necesita el porcentaje de crecimiento anual de la cuota
+    pub fn from_codigo(codigo: &str, crecimiento_anual: f64) -> Option<Self> {
+        [SistemaAmortizacion::Frances, SistemaAmortizacion::Aleman, SistemaAmortizacion::Americano,
+            SistemaAmortizacion::Progresivo(crecimiento_anual)]
+            .into_iter().find(|x| x.codigo().eq_ignore_ascii_case(codigo.trim()))
+    }
+    /// Importe de referencia de las cuotas para amortizar un capital en
+    /// los meses indicados a partir de la cuota `n` del préstamo (la
+    /// primera es la 0). Se calcula al inicio y en cada revisión: es la
+    /// cuota constante en el sistema francés y la cuota de capital en el
+    /// alemán. En el progresivo es la cuota que correspondería al primer
+    /// año del préstamo, de la que se obtienen las de cada año
+    pub fn cuota_base(&self, redondeo: Redondeo, capital_pendiente: Importe,
+            tipo_interes_anual: f64, meses: i32, n: i32) -> Importe {
+        match self {
+            SistemaAmortizacion::Frances => redondeo.cuota_mensual(capital_pendiente, tipo_interes_anual, meses),
+            SistemaAmortizacion::Aleman if meses > 0 => redondeo.importe(capital_pendiente / Decimal::from(meses)),
+            SistemaAmortizacion::Aleman => capital_pendiente,
+            SistemaAmortizacion::Americano => Importe::ZERO,
+            SistemaAmortizacion::Progresivo(crecimiento) => {
+                // Valor actual de las cuotas que resultan de una cuota base de 1 euro
+                let descuento = Decimal::ONE / (Decimal::ONE + tipo(tipo_interes_anual) / Decimal::from(12));
+                let valor_actual: Decimal = (0..meses)
+                    .map(|j| factor_crecimiento(*crecimiento, n + j) * descuento.powi(j as i64 + 1))
+                    .sum();
+                if valor_actual.is_zero() {
+                    return capital_pendiente;
+                }
+                (capital_pendiente / valor_actual).round_dp(10)
+            },
+        }
+    }
+    /// Capital que amortiza la cuota `n` del préstamo con la cuota de
+    /// referencia y los intereses del periodo. La última cuota amortiza
+    /// todo el capital pendiente en los sistemas alemán y americano
+    pub fn cuota_capital(&self, redondeo: Redondeo, cuota_base: Importe, n: i32,
+            intereses: Importe, capital_pendiente: Importe, meses_restantes: i32) -> Importe {
+        match self {
+            SistemaAmortizacion::Frances => redondeo.importe(cuota_base - intereses),
+            SistemaAmortizacion::Aleman if meses_restantes <= 1 => capital_pendiente,
+            SistemaAmortizacion::Aleman => cuota_base.min(capital_pendiente),
+            SistemaAmortizacion::Americano if meses_restantes <= 1 => capital_pendiente,
+            SistemaAmortizacion::Americano => Importe::ZERO,
+            SistemaAmortizacion::Progresivo(crecimiento) =>
+                redondeo.importe(self.cuota_progresiva(redondeo, cuota_base, n, *crecimiento) - intereses),
+        }
+    }
+    /// Cuota del año de la cuota `n` en el sistema progresivo
+    fn cuota_progresiva(&self, redondeo: Redondeo, cuota_base: Importe, n: i32, crecimiento: f64) -> Importe {
+        redondeo.importe(cuota_base * factor_crecimiento(crecimiento, n))
+    }
+    /// Número de cuotas necesarias para amortizar el capital pendiente
+    /// manteniendo la cuota de referencia, a partir de la cuota `n`.
+    /// En el sistema americano no se puede reducir el plazo y se
+    /// mantienen los meses restantes
+    pub fn meses_para_amortizar(&self, redondeo: Redondeo, capital_pendiente: Importe,
+            tipo_interes_anual: f64, cuota_base: Importe, n: i32, meses_restantes: i32) -> i32 {
+        match self {
+            SistemaAmortizacion::Frances => meses_para_amortizar(capital_pendiente, tipo_interes_anual, cuota_base),
+            SistemaAmortizacion::Aleman if cuota_base > Importe::ZERO =>
+                (capital_pendiente / cuota_base).ceil().to_i32().unwrap_or(meses_restantes),
+            SistemaAmortizacion::Aleman | SistemaAmortizacion::Americano => meses_restantes,
+            SistemaAmortizacion::Progresivo(crecimiento) => {
+                // Se simulan las cuotas hasta amortizar todo el capital
+                let mut capital = capital_pendiente;
+                let mut meses = 0;
+                while capital > Importe::ZERO && meses < 1200 {
+                    let intereses = redondeo.intereses_mes(capital, tipo_interes_anual);
+                    capital -= self.cuota_progresiva(redondeo, cuota_base, n + meses, *crecimiento) - intereses;
+                    meses += 1;
+                }
+                meses
+            },
+        }
+    }
+}
+
+/// Factor por el que se multiplica la cuota base en el año de la
+/// cuota `n`: (1 + crecimiento) elevado al número de años completos
+fn factor_crecimiento(crecimiento: f64, n: i32) -> Decimal {
+    (Decimal::ONE + tipo(crecimiento)).powi((n / 12) as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_cuota_base() {
+        let r = Redondeo::MitadArriba;
+        assert_eq!(dec!(444.12), SistemaAmortizacion::Frances.cuota_base(r, dec!(84140), 0.04, 300, 0));
+        assert_eq!(dec!(280.47), SistemaAmortizacion::Aleman.cuota_base(r, dec!(84140), 0.04, 300, 0));
+        assert_eq!(dec!(0), SistemaAmortizacion::Americano.cuota_base(r, dec!(84140), 0.04, 300, 0));
+        // Sin crecimiento, el sistema progresivo coincide con el francés
+        let base = SistemaAmortizacion::Progresivo(0.0).cuota_base(r, dec!(84140), 0.04, 300, 0);
+        assert_eq!(dec!(444.12), r.importe(base));
+        let base = SistemaAmortizacion::Progresivo(0.02).cuota_base(r, dec!(84140), 0.04, 300, 0);
+        assert!(base < dec!(444.12));
+    }
+    #[test]
+    fn test_cuota_capital() {
+        let r = Redondeo::MitadArriba;
+        let s = SistemaAmortizacion::Aleman;
+        assert_eq!(dec!(280.47), s.cuota_capital(r, dec!(280.47), 0, dec!(280.47), dec!(84140), 300));
+        assert_eq!(dec!(280.50), s.cuota_capital(r, dec!(280.47), 299, dec!(0.93), dec!(280.50), 1));
+        let s = SistemaAmortizacion::Americano;
+        assert_eq!(dec!(0), s.cuota_capital(r, dec!(0), 0, dec!(280.47), dec!(84140), 300));
+        assert_eq!(dec!(84140), s.cuota_capital(r, dec!(0), 299, dec!(280.47), dec!(84140), 1));
+        // La cuota del segundo año es un 2% mayor
+        let s = SistemaAmortizacion::Progresivo(0.02);
+        assert_eq!(dec!(100), s.cuota_capital(r, dec!(400), 11, dec!(300), dec!(84140), 289));
+        assert_eq!(dec!(108), s.cuota_capital(r, dec!(400), 12, dec!(300), dec!(84140), 288));
+    }
+    #[test]
+    fn test_meses_para_amortizar() {
+        let r = Redondeo::MitadArriba;
+        assert_eq!(300, SistemaAmortizacion::Frances.meses_para_amortizar(r, dec!(84140), 0.04, dec!(444.12), 0, 300));
+        assert_eq!(150, SistemaAmortizacion::Aleman.meses_para_amortizar(r, dec!(42070), 0.04, dec!(280.47), 150, 150));
+        assert_eq!(120, SistemaAmortizacion::Americano.meses_para_amortizar(r, dec!(42070), 0.04, dec!(0), 180, 120));
+        let s = SistemaAmortizacion::Progresivo(0.02);
+        let base = s.cuota_base(r, dec!(84140), 0.04, 300, 0);
+        assert!((299..=301).contains(&s.meses_para_amortizar(r, dec!(84140), 0.04, base, 0, 300)));
+    }
+}
@@ -0,0 +1,192 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+use super::interes_legal::InteresLegal;
+
+/// Porcentaje del valor de tasación por el que se adjudica al acreedor la
+/// vivienda habitual en una subasta sin postores (art. 671 LEC)
+pub const ADJUDICACION_VIVIENDA_HABITUAL: f64 = 0.70;
+/// Porcentaje para la vivienda habitual cuando la deuda es inferior al 70%
+pub const ADJUDICACION_VIVIENDA_HABITUAL_DEUDA_MENOR: f64 = 0.60;
+/// Porcentaje para los demás bienes
+pub const ADJUDICACION_OTROS_BIENES: f64 = 0.50;
+/// Años desde la adjudicación en los que la revalorización reduce la deuda
+/// remanente (art. 579.2 LEC)
+pub const AGNOS_REVALORIZACION: i32 = 10;
+/// Meses desde la adjudicación en los que el deudor de la vivienda habitual
+/// se libera pagando `LIBERACION_CINCO_AGNOS` de la deuda (art. 579.2 LEC)
+pub const MESES_LIBERACION_CINCO_AGNOS: i32 = 60;
+/// Porcentaje de la deuda remanente que libera al deudor en los cinco
+/// primeros años
+pub const LIBERACION_CINCO_AGNOS: f64 = 0.65;
+/// Porcentaje de la deuda remanente que libera al deudor en los cinco
+/// años siguientes
+pub const LIBERACION_DIEZ_AGNOS: f64 = 0.80;
+
+/// Datos de la subasta de la finca hipotecada
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Subasta {
+    /// Fecha de aprobación del remate o de la adjudicación
+    pub fecha: Date<Utc>,
+    /// Valor de tasación para subasta
+    pub valor_tasacion: Importe,
+    /// Indica si la finca es la vivienda habitual del deudor
+    pub vivienda_habitual: bool,
+    /// Porcentaje del valor de tasación, en tanto por uno, por el que
+    /// se adjudica la finca
+    pub porcentaje_adjudicacion: f64,
+    /// Importe por el que se adjudica la finca
+    pub importe_adjudicacion: Importe,
+}
+
+impl Subasta {
+    pub fn new(fecha: Date<Utc>, valor_tasacion: Importe, vivienda_habitual: bool,
+            porcentaje_adjudicacion: f64, importe_adjudicacion: Importe) -> Self {
+        Subasta { fecha, valor_tasacion, vivienda_habitual, porcentaje_adjudicacion, importe_adjudicacion }
+    }
+    /// Importe mínimo de la adjudicación con el porcentaje indicado
+    pub fn importe_minimo(&self) -> Importe {
+        redondea_dos_decimales(self.valor_tasacion * tipo(self.porcentaje_adjudicacion))
+    }
+    /// Porcentaje mínimo por el que el acreedor puede adjudicarse la finca
+    /// si no hay postores: el 70% en la vivienda habitual, o el 60% si lo
+    /// debido es menor que el 70%, y el 50% en los demás bienes
+    pub fn porcentaje_minimo_acreedor(&self, deuda: Importe) -> f64 {
+        if !self.vivienda_habitual {
+            ADJUDICACION_OTROS_BIENES
+        } else if deuda < self.valor_tasacion * tipo(ADJUDICACION_VIVIENDA_HABITUAL) {
+            ADJUDICACION_VIVIENDA_HABITUAL_DEUDA_MENOR
+        } else {
+            ADJUDICACION_VIVIENDA_HABITUAL
+        }
+    }
+}
+
+/// Resultado de la subasta: deuda que queda después de la adjudicación y
+/// reducciones del art. 579.2 LEC para la vivienda habitual
+pub struct ResultadoSubasta {
+    pub subasta: Subasta,
+    /// Deuda a la fecha de resolución según la liquidación para la ejecución
+    pub deuda: Importe,
+    /// Costas de la ejecución, dentro de su límite
+    pub costas: Importe,
+}
+
+impl ResultadoSubasta {
+    /// Deuda total reclamada, con las costas
+    pub fn deuda_total(&self) -> Importe {
+        redondea_dos_decimales(self.deuda + self.costas)
+    }
+    /// Indica si la adjudicación alcanza el importe mínimo del porcentaje
+    /// indicado y el porcentaje mínimo de la adjudicación al acreedor
+    pub fn cumple_minimos(&self) -> bool {
+        self.subasta.importe_adjudicacion >= self.subasta.importe_minimo()
+            && self.subasta.porcentaje_adjudicacion >= self.subasta.porcentaje_minimo_acreedor(self.deuda_total())
+    }
+    /// Deuda que queda sin cubrir con la adjudicación
+    pub fn deuda_remanente(&self) -> Importe {
+        redondea_dos_decimales((self.deuda_total() - self.subasta.importe_adjudicacion).max(Importe::ZERO))
+    }
+    /// Sobrante de la adjudicación que se entrega al deudor o a los
+    /// acreedores posteriores
+    pub fn sobrante(&self) -> Importe {
+        redondea_dos_decimales((self.subasta.importe_adjudicacion - self.deuda_total()).max(Importe::ZERO))
+    }
+    /// Reducción de la deuda remanente si el acreedor vende la vivienda
+    /// habitual en los diez años siguientes: la mitad de la plusvalía,
+    /// descontados los costes acreditados
+    pub fn reduccion_revalorizacion(&self, fecha_venta: Date<Utc>, precio_venta: Importe,
+            costes: Importe) -> Importe {
+        if !self.subasta.vivienda_habitual || fecha_venta < self.subasta.fecha
+                || fecha_venta >= add_n_months(self.subasta.fecha, 12 * AGNOS_REVALORIZACION) {
+            return Importe::ZERO;
+        }
+        let plusvalia = (precio_venta - costes - self.subasta.importe_adjudicacion).max(Importe::ZERO);
+        redondea_dos_decimales(plusvalia / Decimal::from(2)).min(self.deuda_remanente())
+    }
+    /// Deuda remanente después de la reducción por la venta
+    pub fn deuda_remanente_tras_venta(&self, fecha_venta: Date<Utc>, precio_venta: Importe,
+            costes: Importe) -> Importe {
+        redondea_dos_decimales(self.deuda_remanente()
+            - self.reduccion_revalorizacion(fecha_venta, precio_venta, costes))
+    }
+    /// Importe con el que el deudor de la vivienda habitual queda liberado
+    /// de la deuda remanente: el 65% en los cinco años siguientes a la
+    /// adjudicación y el 80% en los cinco siguientes, de la deuda más el
+    /// interés legal desde la adjudicación. `None` si ya no es posible
+    pub fn importe_liberacion(&self, il: &InteresLegal, fecha_pago: Date<Utc>) -> Option<Importe> {
+        if !self.subasta.vivienda_habitual || fecha_pago < self.subasta.fecha {
+            return None;
+        }
+        let porcentaje = if fecha_pago < add_n_months(self.subasta.fecha, MESES_LIBERACION_CINCO_AGNOS) {
+            LIBERACION_CINCO_AGNOS
+        } else if fecha_pago < add_n_months(self.subasta.fecha, 12 * AGNOS_REVALORIZACION) {
+            LIBERACION_DIEZ_AGNOS
+        } else {
+            return None;
+        };
+        let deuda = self.deuda_remanente() + il.intereses(self.deuda_remanente(), self.subasta.fecha, fecha_pago);
+        Some(redondea_dos_decimales(deuda * tipo(porcentaje)))
+    }
+    pub fn disp(&self) {
+        println!("Adjudicación por {:.2} ({}% de {:.2}). Deuda: {:.2} Remanente: {:.2} Sobrante: {:.2}{}",
+            self.subasta.importe_adjudicacion, redondea_cinco_decimales(self.subasta.porcentaje_adjudicacion * 100.0),
+            self.subasta.valor_tasacion, self.deuda_total(), self.deuda_remanente(), self.sobrante(),
+            if self.cumple_minimos() { "" } else { " (no alcanza los mínimos legales)" });
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let importe = |i: Importe| i.to_string().replace(".", ",");
+        let s = &self.subasta;
+        writeln!(&file, "Fecha de adjudicación; {}/{}/{}", s.fecha.day(), s.fecha.month(), s.fecha.year())?;
+        writeln!(&file, "Valor de tasación; {}", importe(s.valor_tasacion))?;
+        writeln!(&file, "Vivienda habitual; {}", if s.vivienda_habitual { "Sí" } else { "No" })?;
+        writeln!(&file, "Porcentaje de adjudicación; {}", s.porcentaje_adjudicacion.to_string().replace(".", ","))?;
+        writeln!(&file, "Importe mínimo; {}", importe(s.importe_minimo()))?;
+        writeln!(&file, "Porcentaje mínimo de adjudicación al acreedor; {}",
+            s.porcentaje_minimo_acreedor(self.deuda_total()).to_string().replace(".", ","))?;
+        writeln!(&file, "Importe de adjudicación; {}; {}", importe(s.importe_adjudicacion),
+            if self.cumple_minimos() { "cumple los mínimos" } else { "no alcanza los mínimos legales" })?;
+        writeln!(&file, "Deuda; {}", importe(self.deuda))?;
+        writeln!(&file, "Costas; {}", importe(self.costas))?;
+        writeln!(&file, "Deuda remanente; {}", importe(self.deuda_remanente()))?;
+        writeln!(&file, "Sobrante; {}", importe(self.sobrante()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_porcentaje_minimo_acreedor() {
+        let s = Subasta::new(Utc.ymd(2023, 3, 1), dec!(100000), true, 0.7, dec!(70000));
+        assert_eq!(0.7, s.porcentaje_minimo_acreedor(dec!(80000)));
+        assert_eq!(0.6, s.porcentaje_minimo_acreedor(dec!(65000)));
+        let s = Subasta::new(Utc.ymd(2023, 3, 1), dec!(100000), false, 0.5, dec!(50000));
+        assert_eq!(0.5, s.porcentaje_minimo_acreedor(dec!(80000)));
+    }
+    #[test]
+    fn test_deuda_remanente() {
+        let s = Subasta::new(Utc.ymd(2023, 3, 1), dec!(100000), true, 0.7, dec!(70000));
+        let r = ResultadoSubasta { subasta: s, deuda: dec!(85000), costas: dec!(5000) };
+        assert!(r.cumple_minimos());
+        assert_eq!(dec!(20000), r.deuda_remanente());
+        assert_eq!(dec!(0), r.sobrante());
+        // Venta a los tres años con 20.000 de plusvalía neta
+        assert_eq!(dec!(10000), r.reduccion_revalorizacion(Utc.ymd(2026, 3, 1), dec!(95000), dec!(5000)));
+        assert_eq!(dec!(10000), r.deuda_remanente_tras_venta(Utc.ymd(2026, 3, 1), dec!(95000), dec!(5000)));
+        assert_eq!(dec!(0), r.reduccion_revalorizacion(Utc.ymd(2033, 3, 1), dec!(95000), dec!(5000)));
+        let il = InteresLegal::new();
+        assert_eq!(Some(dec!(13000)), r.importe_liberacion(&il, Utc.ymd(2023, 3, 1)));
+        assert_eq!(None, r.importe_liberacion(&il, Utc.ymd(2033, 3, 1)));
+        let r = ResultadoSubasta { subasta: s, deuda: dec!(60000), costas: dec!(0) };
+        assert_eq!(dec!(10000), r.sobrante());
+        assert!(r.cumple_minimos());
+    }
+}
@@ -0,0 +1,35 @@
+use super::comparacion_tablas::ComparacionTablas;
+use super::importe::Importe;
+use super::indice_referencia::IndiceReferencia;
+use super::tabla_amort::TablaAmortizacion;
+
+/// Reclamación por sustitución del índice de referencia (p. ej. IRPH
+/// por euribor): tabla recalculada con el nuevo índice y comparación
+/// cuota a cuota con la tabla cobrada por la entidad
+pub struct SustitucionIndice {
+    /// Índice con el que se recalcula la hipoteca
+    pub indice: IndiceReferencia,
+    /// Diferencial sumado al nuevo índice en cada revisión
+    pub diferencial: f64,
+    /// Tabla de amortización recalculada con el nuevo índice
+    pub tabla: TablaAmortizacion,
+    /// Diferencias entre las cuotas cobradas y las recalculadas
+    pub comparacion: ComparacionTablas,
+}
+
+impl SustitucionIndice {
+    /// Total cobrado de más con el índice de la escritura
+    pub fn total_cobrado_de_mas(&self) -> Importe {
+        self.comparacion.total_diferencias()
+    }
+    pub fn disp(&self) {
+        println!("Recálculo con {} más {}", self.indice.nombre(), self.diferencial);
+        self.comparacion.disp();
+    }
+    /// Escribe la tabla recalculada en <nombre>_sustitucion.txt y
+    /// las diferencias por cuota en <nombre>_diferencias.txt
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        self.tabla.print(&(String::from(nombre) + "_sustitucion"))?;
+        self.comparacion.print(&(String::from(nombre) + "_diferencias"))
+    }
+}
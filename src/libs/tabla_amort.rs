@@ -1,9 +1,11 @@
-use super::{cuota::Cuota, hipoteca::Hipoteca};
-use chrono::prelude::*;
+use super::cuota::Cuota;
+use super::lib::redondea_dos_decimales;
+use super::importe::Importe;
 use std::fs::File;
-use std::io::{Write, Result};
+use std::io::Write;
 
 
+#[derive(Clone)]
 pub struct TablaAmortizacion {
     pub cuotas: Vec<Cuota>,
 }
@@ -15,9 +17,16 @@ impl TablaAmortizacion {
     pub fn len(&self) -> usize {
         self.cuotas.len()
     }
+    pub fn is_empty(&self) -> bool {
+        self.cuotas.is_empty()
+    }
     pub fn push(&mut self, cuota: Cuota) {
         self.cuotas.push(cuota);
     }
+    /// Suma de las comisiones por amortización anticipada de la tabla
+    pub fn total_comisiones(&self) -> Importe {
+        redondea_dos_decimales(self.cuotas.iter().map(|x| x.comision).sum())
+    }
     pub fn disp(&self) {
         for i in 0..self.len() {
             let cuota = &self.cuotas[i];
@@ -29,19 +38,28 @@ impl TablaAmortizacion {
         //println!("{}", filename);
         let file = File::create(filename)?;
         // Escribir la línea de cabeceras
-        writeln!(&file, "{}; {}; {}; {}; {}; {}; {}; {}", "Fecha","i", "Meses","Pendiente_antes",
-             "Cuota", "Capital", "Intereses", "Pendiente_despues")?;
+        writeln!(&file, "Fecha; i; Meses; Pendiente_antes; Cuota; Capital; Intereses; Pendiente_despues; Comision; Observaciones")?;
         for i in 0..self.len() {
             writeln!(&file, "{}" , self.cuotas[i].to_csv_string())?;
         }
         Ok(())
     }
 }
+impl Default for TablaAmortizacion {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::dec;
+    use super::super::hipoteca::Hipoteca;
+    use super::super::euribor_data::EuriborData;
+    use chrono::prelude::*;
+    use std::io::Result;
 
     #[test]
     fn test_new() {
@@ -56,8 +74,8 @@ mod tests {
         let mut tabla = TablaAmortizacion::new();
         let cuota = Cuota::new(
             Utc.ymd(2004, 4, 17),0.04,
-            300, 84140.0, 444.12, 
-            163.65, 280.47); 
+            300, dec!(84140), dec!(444.12), 
+            dec!(163.65), dec!(280.47)); 
         tabla.push(cuota);
         assert_eq!(1, tabla.len());
     }
@@ -67,8 +85,8 @@ mod tests {
         for _i in 1..10 {
             let cuota = Cuota::new(
                 Utc.ymd(2004, 4, 17),0.04,
-                300, 84140.0, 444.12, 
-                163.65, 280.47);
+                300, dec!(84140), dec!(444.12), 
+                dec!(163.65), dec!(280.47));
             tabla.push(cuota);
         }
         tabla.disp();    
@@ -77,12 +95,13 @@ mod tests {
     fn test_print() -> Result<()> {
         let nombre = String::from("h1");
         let fecha = Utc.ymd(2004,3,17);
-        let mut h1= Hipoteca::new(nombre, fecha, 84140.0, 0.04,
+        let mut h1= Hipoteca::new_con_indices(nombre, fecha, dec!(84140), 0.04,
             300,6,12,0.01, 
             0.04, 0.12, Utc.ymd(2018, 5, 17),
-            Utc.ymd(2022, 8, 5));        
+            Utc.ymd(2022, 8, 5),
+            EuriborData::con_proyeccion()).unwrap();        
         h1.tabla_amort_sin_actualizacion = h1.calcula_tabla_amort_sin_actualizacion();
-        h1.tabla_amort_con_actualizacion_euribor = h1.calcula_tabla_amort_con_actualizacion_euribor();
+        h1.tabla_amort_con_actualizacion_euribor = h1.calcula_tabla_amort_con_actualizacion_euribor().unwrap();
         h1.tabla_amort_sin_actualizacion.print(&h1.nombre_operacion.clone())?;
         let filename = h1.nombre_operacion.clone()+"_euribor";
         h1.tabla_amort_con_actualizacion_euribor.print(&filename)?;  
@@ -0,0 +1,44 @@
+/// Tipo de interés de un tramo
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TipoTramo {
+    /// Tipo fijo nominal anual durante todo el tramo
+    Fijo(f64),
+    /// Tipo variable: índice de referencia más el diferencial indicado
+    Variable(f64),
+}
+
+/// Tramo de tipo de interés de una hipoteca mixta, por ejemplo cinco
+/// años a tipo fijo y después euribor más un diferencial. Los tramos
+/// se aplican uno tras otro desde la primera cuota
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TramoInteres {
+    /// Tipo fijo o diferencial sobre el índice
+    pub tipo: TipoTramo,
+    /// Número de cuotas del tramo. `None` si dura hasta el final del
+    /// préstamo; si el último tramo termina antes, sus condiciones se
+    /// mantienen hasta el final
+    pub meses: Option<i32>,
+    /// Tipo mínimo del tramo variable. Si no se indica se aplica el
+    /// de la hipoteca
+    pub i_min: Option<f64>,
+    /// Tipo máximo del tramo variable. Si no se indica se aplica el
+    /// de la hipoteca
+    pub i_max: Option<f64>,
+    /// Meses entre revisiones del tramo variable. Si no se indica se
+    /// aplica el intervalo de la hipoteca
+    pub intervalo_revisiones: Option<i32>,
+}
+
+impl TramoInteres {
+    /// Tramo a tipo fijo durante los meses indicados
+    pub fn fijo(tipo_interes_anual: f64, meses: i32) -> Self {
+        TramoInteres { tipo: TipoTramo::Fijo(tipo_interes_anual), meses: Some(meses),
+            i_min: None, i_max: None, intervalo_revisiones: None }
+    }
+    /// Tramo a tipo variable con el diferencial indicado, con las
+    /// demás condiciones de revisión de la hipoteca
+    pub fn variable(diferencial: f64, meses: Option<i32>) -> Self {
+        TramoInteres { tipo: TipoTramo::Variable(diferencial), meses,
+            i_min: None, i_max: None, intervalo_revisiones: None }
+    }
+}
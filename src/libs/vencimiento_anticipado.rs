@@ -0,0 +1,124 @@
+use chrono::prelude::*;
+use std::fs::File;
+use std::io::Write;
+use super::lib::*;
+use super::importe::*;
+
+/// Proporción del capital concedido que debe estar impagada si la mora
+/// empieza en la primera mitad del plazo
+pub const UMBRAL_PRIMERA_MITAD: f64 = 0.03;
+/// Cuotas mensuales impagadas equivalentes en la primera mitad del plazo
+pub const CUOTAS_PRIMERA_MITAD: i32 = 12;
+/// Proporción del capital concedido en la segunda mitad del plazo
+pub const UMBRAL_SEGUNDA_MITAD: f64 = 0.07;
+/// Cuotas mensuales impagadas equivalentes en la segunda mitad del plazo
+pub const CUOTAS_SEGUNDA_MITAD: i32 = 15;
+/// Plazo mínimo del requerimiento de pago antes del vencimiento anticipado
+pub const MESES_REQUERIMIENTO: i32 = 1;
+
+/// Situación de la deuda vencida en el vencimiento de una cuota
+pub struct LineaVencimiento {
+    /// Fecha de vencimiento de la cuota
+    pub fecha: Date<Utc>,
+    /// Importe de la cuota
+    pub cuota: Importe,
+    /// Total de las cuotas vencidas y no pagadas, descontados los pagos
+    pub total_impagado: Importe,
+    /// Número de cuotas vencidas que no se han pagado por completo
+    pub cuotas_impagadas: i32,
+}
+impl LineaVencimiento {
+    pub fn to_csv_string(&self) -> String {
+        format!("{}/{}/{}; {}; {}; {}",
+            self.fecha.day(), self.fecha.month(), self.fecha.year(),
+            self.cuota.to_string().replace(".", ","),
+            self.total_impagado.to_string().replace(".", ","),
+            self.cuotas_impagadas)
+    }
+}
+
+/// Comprobación de los requisitos del vencimiento anticipado del art. 24
+/// de la Ley 5/2019: las cuotas vencidas y no pagadas deben llegar al 3%
+/// del capital concedido o a doce cuotas mensuales si la mora empieza en
+/// la primera mitad del plazo, o al 7% o quince cuotas en la segunda
+/// mitad, y debe haberse requerido el pago con un mes de plazo
+pub struct VencimientoAnticipado {
+    /// Vencimiento de la primera cuota impagada
+    pub fecha_inicio_mora: Date<Utc>,
+    /// Indica si la mora empieza en la primera mitad del plazo
+    pub primera_mitad: bool,
+    /// Importe impagado que permite el vencimiento anticipado
+    pub umbral_importe: Importe,
+    /// Número de cuotas impagadas que permite el vencimiento anticipado
+    pub umbral_cuotas: i32,
+    /// Situación de la deuda en cada vencimiento desde la mora hasta
+    /// que se alcanza el umbral
+    pub lineas: Vec<LineaVencimiento>,
+    /// Fecha de vencimiento en la que se alcanza el umbral
+    pub fecha_umbral: Option<Date<Utc>>,
+    /// Fecha en la que la entidad dio por vencido el préstamo
+    pub fecha_resolucion: Date<Utc>,
+}
+
+impl VencimientoAnticipado {
+    /// Primera fecha en que la entidad podía dar por vencido el préstamo:
+    /// un mes después de alcanzar el umbral, por el plazo del requerimiento
+    pub fn fecha_minima_resolucion(&self) -> Option<Date<Utc>> {
+        self.fecha_umbral.map(|f| add_n_months(f, MESES_REQUERIMIENTO))
+    }
+    /// Indica si la entidad dio por vencido el préstamo antes de lo permitido
+    pub fn resolucion_prematura(&self) -> bool {
+        self.fecha_minima_resolucion().is_none_or(|f| self.fecha_resolucion < f)
+    }
+    pub fn disp(&self) {
+        for linea in self.lineas.iter() {
+            println!("{}", linea.to_csv_string());
+        }
+        println!("Mora desde {} en la {} mitad del plazo. Umbral: {:.2} o {} cuotas",
+            self.fecha_inicio_mora, if self.primera_mitad { "primera" } else { "segunda" },
+            self.umbral_importe, self.umbral_cuotas);
+        match self.fecha_minima_resolucion() {
+            Some(fecha) => println!("Vencimiento anticipado posible desde {}; la entidad lo aplicó el {}{}",
+                fecha, self.fecha_resolucion, if self.resolucion_prematura() { " (prematuro)" } else { "" }),
+            None => println!("No se alcanza el umbral; la entidad aplicó el vencimiento anticipado el {} (prematuro)",
+                self.fecha_resolucion),
+        }
+    }
+    pub fn print(&self, nombre: &str) -> std::io::Result<()> {
+        let filename = String::from(nombre) + ".txt";
+        let file = File::create(filename)?;
+        let fecha = |f: Date<Utc>| format!("{}/{}/{}", f.day(), f.month(), f.year());
+        writeln!(&file, "Vencimiento; Cuota; Total_impagado; Cuotas_impagadas")?;
+        for linea in self.lineas.iter() {
+            writeln!(&file, "{}", linea.to_csv_string())?;
+        }
+        writeln!(&file, "Inicio de la mora; {}; {} mitad del plazo", fecha(self.fecha_inicio_mora),
+            if self.primera_mitad { "primera" } else { "segunda" })?;
+        writeln!(&file, "Umbral; {}; {} cuotas", self.umbral_importe.to_string().replace(".", ","),
+            self.umbral_cuotas)?;
+        writeln!(&file, "Vencimiento anticipado posible desde; {}",
+            self.fecha_minima_resolucion().map_or(String::from("no se alcanza"), fecha))?;
+        writeln!(&file, "Vencimiento anticipado de la entidad; {}; {}", fecha(self.fecha_resolucion),
+            if self.resolucion_prematura() { "prematuro" } else { "conforme" })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_decimal_macros::dec;
+
+    #[test]
+    fn test_resolucion_prematura() {
+        let mut v = VencimientoAnticipado { fecha_inicio_mora: Utc.ymd(2020, 1, 17), primera_mitad: true,
+            umbral_importe: dec!(3000), umbral_cuotas: CUOTAS_PRIMERA_MITAD, lineas: Vec::new(),
+            fecha_umbral: Some(Utc.ymd(2020, 8, 17)), fecha_resolucion: Utc.ymd(2020, 9, 16) };
+        assert_eq!(Some(Utc.ymd(2020, 9, 17)), v.fecha_minima_resolucion());
+        assert!(v.resolucion_prematura());
+        v.fecha_resolucion = Utc.ymd(2020, 9, 17);
+        assert!(!v.resolucion_prematura());
+        v.fecha_umbral = None;
+        assert!(v.resolucion_prematura());
+    }
+}
@@ -1,14 +1,23 @@
-mod libs;
-
-
 use chrono::prelude::*;
 use chrono::Utc;
-use libs::hipoteca::*;
-use libs::lib::*;
-use std::io;
-use std::io::*;
-use std::io::Result;
-use colored::*;
+use hipotecas::libs::hipoteca::*;
+use hipotecas::libs::lib::*;
+use hipotecas::libs::importe::{Importe, Redondeo};
+use hipotecas::libs::base_calculo::BaseCalculo;
+use hipotecas::libs::calendario::AjusteDiaHabil;
+use hipotecas::libs::sistema_amortizacion::SistemaAmortizacion;
+use hipotecas::libs::carencia::*;
+use hipotecas::libs::amortizacion_anticipada::*;
+use hipotecas::libs::tramo_interes::*;
+use hipotecas::libs::redondeo_tipo::RedondeoTipo;
+use hipotecas::libs::buenas_practicas::SolicitudBuenasPracticas;
+use hipotecas::libs::ejecucion::ResponsabilidadHipotecaria;
+use hipotecas::libs::subasta::Subasta;
+use hipotecas::libs::conciliacion::carga_recibos;
+use hipotecas::libs::pagos::{ConceptoImputacion, carga_pagos_y_gastos};
+use hipotecas::libs::euribor_data::EuriborData;
+use hipotecas::libs::indice_referencia::*;
+use hipotecas::libs::error::*;
 use std::env::args;
 
 
@@ -16,39 +25,221 @@ use umya_spreadsheet::*;
 use umya_spreadsheet::helper::date::{excel_to_date_time_object, CALENDAR_WINDOWS_1900};
 use std::ops::Deref;
 
-fn main() -> Result<()>{
-    if args().len() != 2 {
-        println!("ERROR DE EJECUCIÓN");
+fn main() {
+    if let Err(e) = ejecuta() {
+        eprintln!("ERROR DE EJECUCIÓN: {}", e);
+        std::process::exit(1);
     }
-    let worksheet_file_name: String = args().last().unwrap();
+}
+
+/// Lee la hoja de cálculo indicada en la línea de órdenes y escribe
+/// las tablas de amortización y la liquidación del impago. Si se indica
+/// también un histórico de recibos de la entidad (CSV o XLSX), escribe
+/// su conciliación con las cuotas calculadas. Los pagos y gastos posteriores
+/// al impago se leen de la hoja "Pagos" del libro. Con la orden `cancelacion`
+/// calcula el importe para cancelar el préstamo en la fecha indicada
+fn ejecuta() -> Result<()> {
+    if args().nth(1).as_deref() == Some("cancelacion") {
+        return ejecuta_cancelacion();
+    }
+    if !(2..=3).contains(&args().len()) {
+        return Err(HipotecaError::entrada_invalida("argumentos",
+            "se debe indicar el nombre de la hoja de cálculo y, opcionalmente, el del histórico de recibos"));
+    }
+    let worksheet_file_name: String = args().nth(1)
+        .ok_or_else(|| HipotecaError::entrada_invalida("argumentos",
+            "se debe indicar el nombre de la hoja de cálculo"))?;
+    let recibos_file_name: Option<String> = args().nth(2);
     println!("{}", worksheet_file_name);
     let working_directory: String = String::from("C:\\ProgramaHipotecas\\"); 
     println!("{}", working_directory);
-    let filepath_cad: String =  working_directory + &worksheet_file_name;
-    println!("{}", filepath_cad);
-    let path = std::path::Path::new(&filepath_cad);
-    let book: Spreadsheet = reader::xlsx::read(path).unwrap();
-    let worksheet: &Worksheet = book.get_sheet(&0).unwrap();
+    let book = read_book(&working_directory, &worksheet_file_name)?;
+    let worksheet: &Worksheet = book.get_sheet(&0)
+        .map_err(|e| HipotecaError::Fichero { ruta: worksheet_file_name.clone(), motivo: e.to_owned() })?;
+
+    let indices = read_indices(&working_directory)?;
+    println!("Datos de índices de referencia disponibles:");
+    println!("{}", indices.informe_cobertura());
     
-    let mut h = read_data_from_excel_file(worksheet);
+    let mut h = read_data_from_excel_file(&book, indices)?;
+    read_pagos_y_gastos(&book, &mut h)?;
     println!("Leídos datos");
-    h.tabla_amort_impago = h.calcula_tabla_impago();
+    h.tabla_amort_impago = h.calcula_tabla_impago()?;
 
     print_csv_files(&h);    
 
+    if let Some(base_entidad) = read_base_calculo(worksheet, "C21")? {
+        let comparacion = h.calcula_comparacion_base_calculo(base_entidad, h.fecha_resolucion)?;
+        let result = comparacion.print(&h.nombre_operacion);
+        if result.is_ok() {
+            println!("El fichero con la comparación de bases de cálculo se escribió en {}", 
+                h.nombre_operacion.clone()+"_base_calculo.txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con la comparación de bases de cálculo");
+            println!("{:?}", result);
+        }
+    }
+
+    if h.redondeo_tipo != RedondeoTipo::SinRedondeo {
+        let coste = h.calcula_coste_redondeo(RedondeoTipo::SinRedondeo, h.fecha_resolucion)?;
+        let result = coste.print(&h.nombre_operacion);
+        if result.is_ok() {
+            println!("El fichero con el coste de la cláusula de redondeo se escribió en {}", 
+                h.nombre_operacion.clone()+"_coste_redondeo.txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con el coste de la cláusula de redondeo");
+            println!("{:?}", result);
+        }
+    }
+
+    // Reestructuración del Código de Buenas Prácticas: fecha, ingresos netos
+    // mensuales, IPREM mensual, precio de adquisición y límite de precio
+    if let Some(fecha_reestructuracion) = read_fecha_opcional(worksheet, "C33")? {
+        let solicitud = SolicitudBuenasPracticas::new(read_importe(worksheet, "C34")?,
+            read_importe(worksheet, "C35")?, read_importe(worksheet, "C36")?,
+            read_importe(worksheet, "C37")?);
+        let reestructuracion = h.simula_buenas_practicas(fecha_reestructuracion, &solicitud)?;
+        reestructuracion.disp();
+        let result = reestructuracion.print(&h.nombre_operacion);
+        if result.is_ok() {
+            println!("El fichero con la reestructuración del Código de Buenas Prácticas se escribió en {}", 
+                h.nombre_operacion.clone()+"_buenas_practicas.txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con la reestructuración del Código de Buenas Prácticas");
+            println!("{:?}", result);
+        }
+    }
+
+    // Medidas del RDL 19/2022: fecha de la congelación de la cuota y meses
+    // de ampliación del plazo
+    if let Some(fecha_congelacion) = read_fecha_opcional(worksheet, "C38")? {
+        let meses_ampliacion = match read_string_opcional(worksheet, "C39") {
+            Some(_) => read_i32(worksheet, "C39")?,
+            None => 0,
+        };
+        let congelacion = h.simula_congelacion_cuota(fecha_congelacion, meses_ampliacion)?;
+        congelacion.disp();
+        let result = congelacion.print(&h.nombre_operacion);
+        if result.is_ok() {
+            println!("El fichero con la congelación de la cuota se escribió en {}", 
+                h.nombre_operacion.clone()+"_congelacion.txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con la congelación de la cuota");
+            println!("{:?}", result);
+        }
+    }
+
+    if let Some(recibos_file_name) = recibos_file_name {
+        let recibos = carga_recibos(&std::path::Path::new(&working_directory).join(recibos_file_name))?;
+        let conciliacion = h.concilia_recibos(&recibos);
+        conciliacion.disp();
+        let filename = h.nombre_operacion.clone() + "_conciliacion";
+        let result = conciliacion.print(&filename);
+        if result.is_ok() {
+            println!("El fichero con la conciliación de recibos se escribió en {}", filename+".txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con la conciliación de recibos");
+            println!("{:?}", result);
+        }
+    }
+
+    let vencimiento = h.comprueba_vencimiento_anticipado()?;
+    vencimiento.disp();
+    let filename = h.nombre_operacion.clone() + "_vencimiento_anticipado";
+    let result = vencimiento.print(&filename);
+    if result.is_ok() {
+        println!("El fichero con la comprobación del vencimiento anticipado se escribió en {}", filename+".txt" );
+    } else {
+        println!("Se produjeron errores al escribir el fichero con la comprobación del vencimiento anticipado");
+        println!("{:?}", result);
+    }
+
+    let liquidacion = h.calcula_liquidacion_impago()?;
+    let filename = h.nombre_operacion.clone() + "_liquidacion";
+    let result = liquidacion.print(&filename);
+    if result.is_ok() {
+        println!("El fichero con la liquidación del impago se escribió en {}", filename+".txt" );
+    } else {
+        println!("Se produjeron errores al escribir el fichero con la liquidación del impago");
+        println!("{:?}", result);
+    }
+
+
+    let ejecucion = h.calcula_liquidacion_ejecucion()?;
+    ejecucion.disp();
+    let filename = h.nombre_operacion.clone() + "_ejecucion";
+    let result = ejecucion.print(&filename);
+    if result.is_ok() {
+        println!("El fichero con la liquidación para la ejecución se escribió en {}", filename+".txt" );
+    } else {
+        println!("Se produjeron errores al escribir el fichero con la liquidación para la ejecución");
+        println!("{:?}", result);
+    }
+
+    // Subasta: fecha de adjudicación, valor de tasación, vivienda habitual
+    // (SI o NO), porcentaje de adjudicación en %, importe adjudicado y costas
+    if let Some(fecha_adjudicacion) = read_fecha_opcional(worksheet, "C44")? {
+        let subasta = Subasta::new(fecha_adjudicacion, read_importe(worksheet, "C45")?,
+            read_string(worksheet, "C46")?.eq_ignore_ascii_case("SI"),
+            redondea_cinco_decimales(read_f64(worksheet, "C47")?/100.0), read_importe(worksheet, "C48")?);
+        let costas = read_string_opcional(worksheet, "C49").map(|_| read_importe(worksheet, "C49"))
+            .transpose()?.unwrap_or(Importe::ZERO);
+        let resultado = h.simula_subasta(subasta, costas)?;
+        resultado.disp();
+        let filename = h.nombre_operacion.clone() + "_subasta";
+        let result = resultado.print(&filename);
+        if result.is_ok() {
+            println!("El fichero con el resultado de la subasta se escribió en {}", filename+".txt" );
+        } else {
+            println!("Se produjeron errores al escribir el fichero con el resultado de la subasta");
+            println!("{:?}", result);
+        }
+    }
 
     
     // let _ = umya_spreadsheet::writer::xlsx::write(&book, path);
-    //wait();
-        
 
     Ok(())
 }
 
-fn date_to_string(date: Date<Utc>) -> String {
-    let fecha = date.day().to_string() + "/" + &date.month().to_string() + &"/" +
-        &date.year().to_string();
-    fecha
+/// Orden `cancelacion <hoja.xlsx> <fecha>`: escribe el importe necesario
+/// para cancelar el préstamo en la fecha indicada (dd/mm/aaaa o aaaa-mm-dd)
+fn ejecuta_cancelacion() -> Result<()> {
+    if args().len() != 4 {
+        return Err(HipotecaError::entrada_invalida("argumentos",
+            "se debe indicar el nombre de la hoja de cálculo y la fecha de cancelación"));
+    }
+    let worksheet_file_name: String = args().nth(2)
+        .ok_or_else(|| HipotecaError::entrada_invalida("argumentos",
+            "se debe indicar el nombre de la hoja de cálculo"))?;
+    let cad_fecha: String = args().nth(3).unwrap_or_default();
+    let fecha = parse_fecha(&cad_fecha).ok_or_else(|| HipotecaError::entrada_invalida("fecha",
+        &format!("\"{}\" no es una fecha", cad_fecha)))?;
+    let working_directory: String = String::from("C:\\ProgramaHipotecas\\"); 
+    let book = read_book(&working_directory, &worksheet_file_name)?;
+    let indices = read_indices(&working_directory)?;
+    let mut h = read_data_from_excel_file(&book, indices)?;
+    read_pagos_y_gastos(&book, &mut h)?;
+    let cancelacion = h.calcula_cancelacion(fecha)?;
+    cancelacion.disp();
+    let filename = h.nombre_operacion.clone() + "_cancelacion";
+    let result = cancelacion.print(&filename);
+    if result.is_ok() {
+        println!("El fichero con la liquidación de cancelación se escribió en {}", filename+".txt" );
+    } else {
+        println!("Se produjeron errores al escribir el fichero con la liquidación de cancelación");
+        println!("{:?}", result);
+    }
+    Ok(())
+}
+
+/// Lee el libro de la hoja de cálculo del directorio de trabajo
+fn read_book(working_directory: &str, worksheet_file_name: &str) -> Result<Spreadsheet> {
+    let filepath_cad: String = String::from(working_directory) + worksheet_file_name;
+    println!("{}", filepath_cad);
+    let path = std::path::Path::new(&filepath_cad);
+    reader::xlsx::read(path)
+        .map_err(|e| HipotecaError::Fichero { ruta: filepath_cad.clone(), motivo: format!("{:?}", e) })
 }
 
 fn print_csv_files(h: &Hipoteca) {
@@ -77,70 +268,379 @@ fn print_csv_files(h: &Hipoteca) {
         println!("{:?}", result);
     }
 }
-fn read_data_from_excel_file(worksheet: &Worksheet) -> Hipoteca {
-    let nombre = read_string(worksheet, "C5");
-    let fecha = read_fecha(worksheet, "C6");    
-    let meses_primera_cuota = read_i32(worksheet, "C7");
-    let capital = read_f64(worksheet, "C8");
-    let tipo = redondea_cinco_decimales(read_f64(worksheet, "C9")/100.0);
-    let meses = read_i32(worksheet, "C10");
-    let meses_primera_revision = read_i32(worksheet, "C11");
-    let intervalo_revisiones = read_i32(worksheet, "C12");
-    let incremento_euribor = redondea_cinco_decimales(read_f64(worksheet, "C13")/100.0);
-    let i_min = redondea_cinco_decimales(read_f64(worksheet, "C14")/100.0);
-    let i_max = redondea_cinco_decimales(read_f64(worksheet, "C15")/100.0);
-    let fecha_impago: Date<Utc> = read_fecha(worksheet, "C16");
-    let fecha_resolucion: Date<Utc> = read_fecha(worksheet, "C17");     
+/// Carga los datos de los índices de referencia del directorio de trabajo:
+/// el fichero indices.json y un fichero CSV por índice con su código
+/// como nombre (EURIBOR12M.csv, IRPH_ENTIDADES.csv...), si existen.
+/// Si alguno no se puede leer devuelve un error con su ruta
+fn read_indices(working_directory: &str) -> Result<EuriborData> {
+    let mut indices = EuriborData::new();
+    let error_fichero = |path: &std::path::Path, e: std::io::Error| HipotecaError::Fichero {
+        ruta: path.display().to_string(), motivo: e.to_string() };
+    let path = std::path::Path::new(working_directory).join("indices.json");
+    if path.exists() {
+        let n = indices.carga_json(&path).map_err(|e| error_fichero(&path, e))?;
+        println!("Leídos {} valores de índices de {}", n, path.display());
+    }
+    for indice in IndiceReferencia::todos() {
+        // Medias mensuales en <CODIGO>.csv y valores de cierre de mes en <CODIGO>_CIERRE.csv
+        for (serie, sufijo) in [(TipoSerie::MediaMensual, String::new()), 
+                (TipoSerie::FinDeMes, String::from("_") + TipoSerie::FinDeMes.codigo())] {
+            let path = std::path::Path::new(working_directory).join(indice.codigo().to_owned() + &sufijo + ".csv");
+            if path.exists() {
+                let n = indices.carga_csv_serie(indice, serie, &path).map_err(|e| error_fichero(&path, e))?;
+                println!("Leídos {} valores de {} ({}) de {}", n, indice.nombre(), 
+                    serie.nombre(), path.display());
+            }
+        }
+    }
+    Ok(indices)
+}
+/// Lee las condiciones del préstamo de la primera hoja del libro y, si
+/// existen, las amortizaciones anticipadas de la hoja "Amortizaciones" y
+/// los tramos de interés de la hoja "Tramos", y calcula las tablas de
+/// amortización
+fn read_data_from_excel_file(book: &Spreadsheet, mut indices: EuriborData) -> Result<Hipoteca> {
+    let worksheet: &Worksheet = book.get_sheet(&0)
+        .map_err(|e| HipotecaError::hoja_calculo("A1", e))?;
+    let nombre = read_string(worksheet, "C5")?;
+    let fecha = read_fecha(worksheet, "C6")?;    
+    let meses_primera_cuota = read_i32(worksheet, "C7")?;
+    let capital = read_importe(worksheet, "C8")?;
+    let tipo = redondea_cinco_decimales(read_f64(worksheet, "C9")?/100.0);
+    let meses = read_i32(worksheet, "C10")?;
+    let meses_primera_revision = read_i32(worksheet, "C11")?;
+    let intervalo_revisiones = read_i32(worksheet, "C12")?;
+    let incremento_euribor = redondea_cinco_decimales(read_f64(worksheet, "C13")?/100.0);
+    let i_min = redondea_cinco_decimales(read_f64(worksheet, "C14")?/100.0);
+    let i_max = redondea_cinco_decimales(read_f64(worksheet, "C15")?/100.0);
+    let fecha_impago: Date<Utc> = read_fecha(worksheet, "C16")?;
+    let fecha_resolucion: Date<Utc> = read_fecha(worksheet, "C17")?;     
+    let interes_demora = read_f64_opcional(worksheet, "C18")?
+        .map(|x| redondea_cinco_decimales(x/100.0));
+    // La fecha de la primera cuota, si se indica, prevalece sobre los meses
+    let fecha_primera_cuota = read_fecha_opcional(worksheet, "C19")?
+        .unwrap_or_else(|| add_n_months(fecha, meses_primera_cuota));
+    // Proyección del tipo tras la resolución sin datos del índice (SI o NO)
+    if let Some(cad) = read_string_opcional(worksheet, "C50") {
+        indices.set_proyeccion(cad.eq_ignore_ascii_case("SI"));
+    }
+    // Índice de referencia (EURIBOR12M, IRPH_ENTIDADES, IRPH_CAJAS, CECA...),
+    // índice sustitutivo y su diferencial en %, si el índice desapareció
+    let indice = read_indice_referencia(worksheet, "C51")?;
+    let indice_sustitutivo = read_indice_referencia(worksheet, "C52")?;
+    let diferencial_sustitutivo = read_f64_opcional(worksheet, "C53")?
+        .map(|x| redondea_cinco_decimales(x/100.0));
+    // Orden de imputación de los pagos, separado por comas (GASTOS, DEMORA,
+    // INTERESES, CAPITAL); en blanco, el del art. 1173 CC
+    let orden_imputacion = read_orden_imputacion(worksheet, "C54")?;
+    // Redondeo de los importes a céntimos (MITAD_ARRIBA, MITAD_PAR o TRUNCAMIENTO)
+    let redondeo = read_redondeo(worksheet, "C55")?;
+    // Comisión por amortización anticipada (SIN_COMISION, VARIABLE_5,
+    // VARIABLE_3 o FIJO)
+    let comision_amortizacion = read_comision_amortizacion(worksheet, "C56")?;
+    let amortizaciones = match book.get_sheet_by_name("Amortizaciones") {
+        Ok(hoja) => read_amortizaciones(hoja).map_err(|e| en_hoja("Amortizaciones", e))?,
+        Err(_) => Vec::new(),
+    };
+    let tramos = match book.get_sheet_by_name("Tramos") {
+        Ok(hoja) => read_tramos(hoja).map_err(|e| en_hoja("Tramos", e))?,
+        Err(_) => Vec::new(),
+    };
     
-    let h = Hipoteca::new(nombre, fecha,
+    // Se leen todas las condiciones antes de calcular las tablas, que se
+    // calculan una sola vez con todas ellas
+    let mut h = Hipoteca::new_sin_tablas(nombre, fecha,
                 capital, tipo, meses, 
                 meses_primera_revision, intervalo_revisiones,
-                incremento_euribor, i_min, i_max, fecha_impago, fecha_resolucion);
+                incremento_euribor, i_min, i_max, fecha_impago, fecha_resolucion, indices)?;
+    h.interes_demora = interes_demora;
+    h.fecha_primera_cuota = fecha_primera_cuota;
+    if let Some(indice) = indice {
+        h.indice = indice;
+    }
+    h.indice_sustitutivo = indice_sustitutivo;
+    if let Some(orden) = orden_imputacion {
+        h.orden_imputacion = orden;
+    }
+    if let Some(redondeo) = redondeo {
+        h.redondeo = redondeo;
+    }
+    if let Some(comision) = comision_amortizacion {
+        h.comision_amortizacion = comision;
+    }
+    h.amortizaciones_anticipadas = amortizaciones;
+    h.amortizaciones_anticipadas.sort_by_key(|a| a.fecha);
+    h.tramos = tramos;
+    if let Some(diferencial) = diferencial_sustitutivo {
+        h.diferencial_sustitutivo = diferencial;
+    }
+    if let Some(base_calculo) = read_base_calculo(worksheet, "C20")? {
+        h.base_calculo = base_calculo;
+    }
+    if let Some(ajuste) = read_ajuste_dia_habil(worksheet, "C22")? {
+        h.ajuste_dia_habil = ajuste;
+    }
+    if let Some(referencia) = read_referencia_revision(worksheet, "C30", "C31", "C32")? {
+        h.referencia_revision = referencia;
+    }
+    if let Some(redondeo_tipo) = read_redondeo_tipo(worksheet, "C28", "C29")? {
+        h.redondeo_tipo = redondeo_tipo;
+    }
+    if let Some(sistema) = read_sistema_amortizacion(worksheet, "C23", "C24")? {
+        h.sistema_amortizacion = sistema;
+    }
+    // Periodo de carencia: fecha de inicio, número de cuotas y tipo
+    if let Some(fecha_carencia) = read_fecha_opcional(worksheet, "C25")? {
+        let meses_carencia = read_i32(worksheet, "C26")?;
+        let tipo_carencia = read_tipo_carencia(worksheet, "C27")?;
+        h.carencias.push(Carencia::new(fecha_carencia, meses_carencia, tipo_carencia));
+    }
+    // Responsabilidad hipotecaria por capital, intereses ordinarios,
+    // intereses de demora y costas
+    if read_string_opcional(worksheet, "C40").is_some() {
+        h.responsabilidad_hipotecaria = Some(ResponsabilidadHipotecaria::new(read_importe(worksheet, "C40")?,
+            read_importe(worksheet, "C41")?, read_importe(worksheet, "C42")?, read_importe(worksheet, "C43")?));
+    }
+    h.recalcula_tablas()?;
     println!("{} {}", h.fecha_impago, h.fecha_resolucion);
-    h
+    Ok(h)
+}
+/// Lee el texto de una celda; si la celda no existe devuelve un error con su coordenada
+fn read_string(worksheet: &Worksheet, coordinate: &str) -> Result<String> {
+    let cell = worksheet.get_cell(coordinate)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, "la celda está vacía"))?;
+    Ok(cell.get_value().deref().trim().to_owned())
+}
+fn read_i32(worksheet: &Worksheet, coordinate: &str) -> Result<i32> {
+    let cad = read_string(worksheet, coordinate)?;
+    cad.parse().map_err(|_| HipotecaError::hoja_calculo(coordinate, 
+        &format!("\"{}\" no es un número entero", cad)))
+}
+fn read_f64(worksheet: &Worksheet, coordinate: &str) -> Result<f64> {
+    let cad = read_string(worksheet, coordinate)?;
+    cad.parse().ok().filter(|x: &f64| x.is_finite()).ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+        &format!("\"{}\" no es un número", cad)))
+}
+/// Lee un importe en euros sin pasar por `f64`
+fn read_importe(worksheet: &Worksheet, coordinate: &str) -> Result<Importe> {
+    let cad = read_string(worksheet, coordinate)?;
+    cad.parse().map_err(|_| HipotecaError::hoja_calculo(coordinate, 
+        &format!("\"{}\" no es un importe", cad)))
+}
+/// Lee un valor numérico que puede dejarse en blanco. Si la celda tiene
+/// un valor que no es un número devuelve un error con su coordenada
+fn read_f64_opcional(worksheet: &Worksheet, coordinate: &str) -> Result<Option<f64>> {
+    read_string_opcional(worksheet, coordinate).map(|_| read_f64(worksheet, coordinate)).transpose()
+}
+/// Lee un texto que puede dejarse en blanco
+fn read_string_opcional(worksheet: &Worksheet, coordinate: &str) -> Option<String> {
+    let cad = worksheet.get_cell(coordinate)?.get_value().deref().trim().to_owned();
+    (!cad.is_empty()).then_some(cad)
+}
+/// Lee una base de cálculo (30/360, ACT/365, ACT/360 o ACT/ACT) que puede dejarse en blanco
+fn read_base_calculo(worksheet: &Worksheet, coordinate: &str) -> Result<Option<BaseCalculo>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| BaseCalculo::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es una base de cálculo (30/360, ACT/365, ACT/360 o ACT/ACT)", cad))))
+        .transpose()
+}
+/// Lee el código de un índice de referencia (EURIBOR12M, EURIBOR6M,
+/// IRPH_ENTIDADES, IRPH_CAJAS, IRPH_BANCOS o CECA), que puede dejarse en blanco
+fn read_indice_referencia(worksheet: &Worksheet, coordinate: &str) -> Result<Option<IndiceReferencia>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| IndiceReferencia::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un índice de referencia ({})", cad, 
+                IndiceReferencia::todos().map(|i| i.codigo()).join(", ")))))
+        .transpose()
+}
+/// Lee la forma de redondear los importes a céntimos (MITAD_ARRIBA,
+/// MITAD_PAR o TRUNCAMIENTO), que puede dejarse en blanco
+fn read_redondeo(worksheet: &Worksheet, coordinate: &str) -> Result<Option<Redondeo>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| Redondeo::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un redondeo (MITAD_ARRIBA, MITAD_PAR o TRUNCAMIENTO)", cad))))
+        .transpose()
+}
+/// Lee la comisión por amortización anticipada pactada (SIN_COMISION,
+/// VARIABLE_5, VARIABLE_3 o FIJO), que puede dejarse en blanco
+fn read_comision_amortizacion(worksheet: &Worksheet, coordinate: &str) -> Result<Option<ComisionAmortizacion>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| ComisionAmortizacion::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es una comisión de amortización (SIN_COMISION, VARIABLE_5, VARIABLE_3 o FIJO)", cad))))
+        .transpose()
+}
+/// Lee las amortizaciones anticipadas de una hoja con una cabecera en la
+/// primera fila y, en las siguientes, la fecha, el capital amortizado y la
+/// modalidad (REDUCIR_CUOTA o REDUCIR_PLAZO). Se omiten las filas sin fecha
+fn read_amortizaciones(worksheet: &Worksheet) -> Result<Vec<AmortizacionAnticipada>> {
+    let mut amortizaciones = Vec::new();
+    for fila in filas_con_datos(worksheet) {
+        let celda_modalidad = format!("C{}", fila);
+        let cad = read_string(worksheet, &celda_modalidad)?;
+        let modalidad = ModalidadAmortizacion::from_codigo(&cad).ok_or_else(|| 
+            HipotecaError::hoja_calculo(&celda_modalidad, 
+                &format!("\"{}\" no es una modalidad de amortización (REDUCIR_CUOTA o REDUCIR_PLAZO)", cad)))?;
+        amortizaciones.push(AmortizacionAnticipada::new(read_fecha(worksheet, &format!("A{}", fila))?,
+            read_importe(worksheet, &format!("B{}", fila))?, modalidad));
+    }
+    Ok(amortizaciones)
+}
+/// Añade el nombre de la hoja a la celda de un error de lectura de una
+/// hoja que no es la principal (Amortizaciones!A2)
+fn en_hoja(hoja: &str, e: HipotecaError) -> HipotecaError {
+    match e {
+        HipotecaError::HojaCalculo { celda, motivo } => 
+            HipotecaError::HojaCalculo { celda: format!("{}!{}", hoja, celda), motivo },
+        e => e,
+    }
+}
+/// Lee los tramos de interés de una hoja con una cabecera en la primera
+/// fila y, en las siguientes, el tipo de tramo (FIJO o VARIABLE), el tipo
+/// fijo o el diferencial en %, las cuotas del tramo (en blanco, hasta el
+/// final del préstamo), los tipos mínimo y máximo en % y el intervalo de
+/// revisiones, que pueden dejarse en blanco para aplicar los de la hipoteca
+fn read_tramos(worksheet: &Worksheet) -> Result<Vec<TramoInteres>> {
+    let mut tramos = Vec::new();
+    for fila in filas_con_datos(worksheet) {
+        let celda = |columna: &str| format!("{}{}", columna, fila);
+        let i32_opcional = |coordinate: &str| read_string_opcional(worksheet, coordinate)
+            .map(|_| read_i32(worksheet, coordinate)).transpose();
+        let tipo = redondea_cinco_decimales(read_f64(worksheet, &celda("B"))?/100.0);
+        let cad = read_string(worksheet, &celda("A"))?;
+        let tipo = if cad.eq_ignore_ascii_case("FIJO") {
+            TipoTramo::Fijo(tipo)
+        } else if cad.eq_ignore_ascii_case("VARIABLE") {
+            TipoTramo::Variable(tipo)
+        } else {
+            return Err(HipotecaError::hoja_calculo(&celda("A"), 
+                &format!("\"{}\" no es un tipo de tramo (FIJO o VARIABLE)", cad)));
+        };
+        tramos.push(TramoInteres { tipo, meses: i32_opcional(&celda("C"))?,
+            i_min: read_f64_opcional(worksheet, &celda("D"))?.map(|x| redondea_cinco_decimales(x/100.0)),
+            i_max: read_f64_opcional(worksheet, &celda("E"))?.map(|x| redondea_cinco_decimales(x/100.0)),
+            intervalo_revisiones: i32_opcional(&celda("F"))? });
+    }
+    Ok(tramos)
+}
+/// Filas, a partir de la segunda, con la columna A rellena
+fn filas_con_datos(worksheet: &Worksheet) -> Vec<u32> {
+    (2..=worksheet.get_highest_row())
+        .filter(|fila| read_string_opcional(worksheet, &format!("A{}", fila)).is_some())
+        .collect()
+}
+/// Lee el orden de imputación de los pagos, con los códigos de los conceptos
+/// (GASTOS, DEMORA, INTERESES o CAPITAL) separados por comas, que puede
+/// dejarse en blanco
+fn read_orden_imputacion(worksheet: &Worksheet, coordinate: &str) -> Result<Option<Vec<ConceptoImputacion>>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| cad.split([',', ';'])
+        .map(|codigo| ConceptoImputacion::from_codigo(codigo).ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un concepto de imputación (GASTOS, DEMORA, INTERESES o CAPITAL)", codigo.trim()))))
+        .collect())
+        .transpose()
 }
-fn read_string(worksheet: &Worksheet, coordinate: &str) -> String {
-    worksheet.get_cell(coordinate).unwrap().get_value().deref().to_owned()
+/// Añade a la hipoteca los pagos del deudor y los gastos repercutidos después
+/// del impago de la hoja "Pagos" del libro, si existe
+fn read_pagos_y_gastos(book: &Spreadsheet, h: &mut Hipoteca) -> Result<()> {
+    let Ok(worksheet) = book.get_sheet_by_name("Pagos") else {
+        return Ok(());
+    };
+    let (pagos, gastos) = carga_pagos_y_gastos(worksheet)
+        .map_err(|e| HipotecaError::hoja_calculo("Pagos", &e.to_string()))?;
+    println!("Leídos {} pagos y {} gastos de la hoja Pagos", pagos.len(), gastos.len());
+    for pago in pagos {
+        h.add_pago(pago)?;
+    }
+    for gasto in gastos {
+        h.add_gasto(gasto)?;
+    }
+    Ok(())
+}
+/// Lee el ajuste de los vencimientos en días inhábiles (NINGUNO, SIGUIENTE
+/// o SIGUIENTE_MODIFICADO) que puede dejarse en blanco
+fn read_ajuste_dia_habil(worksheet: &Worksheet, coordinate: &str) -> Result<Option<AjusteDiaHabil>> {
+    read_string_opcional(worksheet, coordinate).map(|cad| AjusteDiaHabil::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un ajuste de días inhábiles (NINGUNO, SIGUIENTE o SIGUIENTE_MODIFICADO)", cad))))
+        .transpose()
+}
+/// Lee el sistema de amortización (FRANCES, ALEMAN, AMERICANO o PROGRESIVO),
+/// que puede dejarse en blanco, y el crecimiento anual en % de la cuota
+/// del sistema progresivo
+fn read_sistema_amortizacion(worksheet: &Worksheet, coordinate: &str,
+        coordinate_crecimiento: &str) -> Result<Option<SistemaAmortizacion>> {
+    let crecimiento = read_f64_opcional(worksheet, coordinate_crecimiento)?.unwrap_or(0.0) / 100.0;
+    read_string_opcional(worksheet, coordinate).map(|cad| SistemaAmortizacion::from_codigo(&cad, 
+            redondea_cinco_decimales(crecimiento))
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un sistema de amortización (FRANCES, ALEMAN, AMERICANO o PROGRESIVO)", cad))))
+        .transpose()
+}
+/// Lee la cláusula de redondeo del tipo revisado (NINGUNO, CERCANO, ARRIBA
+/// o ABAJO), que puede dejarse en blanco, y el paso del redondeo en puntos
+/// porcentuales (0,125 para el octavo de punto)
+fn read_redondeo_tipo(worksheet: &Worksheet, coordinate: &str,
+        coordinate_paso: &str) -> Result<Option<RedondeoTipo>> {
+    let paso = read_f64_opcional(worksheet, coordinate_paso)?.unwrap_or(0.0) / 100.0;
+    read_string_opcional(worksheet, coordinate).map(|cad| RedondeoTipo::from_codigo(&cad, 
+            redondea_cinco_decimales(paso))
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+            &format!("\"{}\" no es un redondeo del tipo (NINGUNO, CERCANO, ARRIBA o ABAJO)", cad))))
+        .transpose()
+}
+/// Lee la regla de referencia de las revisiones: meses de desfase (1 para el
+/// mes anterior), serie (MEDIA o CIERRE; en blanco MEDIA si está cargada y,
+/// si no, CIERRE) y meses que se promedian (en blanco 1). Si no se indica
+/// el desfase se usa la regla por defecto
+fn read_referencia_revision(worksheet: &Worksheet, coordinate_desfase: &str, coordinate_serie: &str,
+        coordinate_media: &str) -> Result<Option<ReferenciaRevision>> {
+    if read_string_opcional(worksheet, coordinate_desfase).is_none() {
+        return Ok(None);
+    }
+    let meses_desfase = read_i32(worksheet, coordinate_desfase)?;
+    let serie = read_string_opcional(worksheet, coordinate_serie).map(|cad| TipoSerie::from_codigo(&cad)
+        .ok_or_else(|| HipotecaError::hoja_calculo(coordinate_serie, 
+            &format!("\"{}\" no es una serie del índice (MEDIA o CIERRE)", cad))))
+        .transpose()?;
+    let meses_media = match read_string_opcional(worksheet, coordinate_media) {
+        Some(_) => read_i32(worksheet, coordinate_media)?,
+        None => 1,
+    };
+    Ok(Some(ReferenciaRevision::new(meses_desfase, serie, meses_media)?))
 }
-fn read_i32(worksheet: &Worksheet, coordinate: &str) -> i32 {
-    let cad = worksheet.get_cell(coordinate).unwrap().get_value().deref().to_owned();
-    let cap: i32 = cad.parse().unwrap();
-    cap    
+/// Lee el tipo de carencia (CAPITAL o TOTAL)
+fn read_tipo_carencia(worksheet: &Worksheet, coordinate: &str) -> Result<TipoCarencia> {
+    let cad = read_string(worksheet, coordinate)?;
+    TipoCarencia::from_codigo(&cad).ok_or_else(|| HipotecaError::hoja_calculo(coordinate, 
+        &format!("\"{}\" no es un tipo de carencia (CAPITAL o TOTAL)", cad)))
 }
-fn read_f64(worksheet: &Worksheet, coordinate: &str) -> f64 {
-    let cad = worksheet.get_cell(coordinate).unwrap().get_value().deref().to_owned();
-    let cap: f64 = cad.parse().unwrap();
-    cap    
+/// Lee una fecha que puede dejarse en blanco. Si la celda tiene un
+/// valor que no es una fecha devuelve un error con su coordenada
+fn read_fecha_opcional(worksheet: &Worksheet, coordinate: &str) -> Result<Option<Date<Utc>>> {
+    read_string_opcional(worksheet, coordinate).map(|_| read_fecha(worksheet, coordinate)).transpose()
 }
-fn read_fecha(worksheet: &Worksheet, coordinate: &str) -> Date<Utc> {
-    let cell = worksheet.get_cell(coordinate).unwrap();
-    let fecha_excel = cell.get_value().deref().to_owned();
-    let fecha_f64 = fecha_excel.parse().unwrap();
+fn read_fecha(worksheet: &Worksheet, coordinate: &str) -> Result<Date<Utc>> {
+    let fecha_excel = read_string(worksheet, coordinate)?;
+    let fecha_f64 = fecha_excel.parse().map_err(|_| HipotecaError::hoja_calculo(coordinate, 
+        &format!("\"{}\" no es una fecha", fecha_excel)))?;
     let fecha = excel_to_date_time_object(&fecha_f64,
          Some(CALENDAR_WINDOWS_1900.to_owned()));
-    let fecha_2 = Utc.ymd(fecha.year(), fecha.month(), fecha.day());
-    fecha_2
-}
-fn wait() {
-    let mut nombre = String::new();
-    println!("Nombre: ");
-    let result = io::stdin().read_line(&mut nombre);
+    Ok(Utc.ymd(fecha.year(), fecha.month(), fecha.day()))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rust_decimal_macros::dec;
     #[test]
     fn test_read_data_from_excel_file() {
-        let path = std::path::Path::new("assets\\Libro11.xlsx");
-        let book: Spreadsheet = reader::xlsx::read(path).unwrap();
-        let worksheet: &Worksheet = book.get_sheet(&0).unwrap();
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let book: Spreadsheet = reader::xlsx::read(&path).unwrap();
         
-        let mut h = read_data_from_excel_file(worksheet);
+        let h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
         println!("Leídos datos");
         assert_eq!("Libro11", h.nombre_operacion);
         assert_eq!(Utc.ymd(2004, 2, 17), h.fecha_escritura);
-        assert_eq!(84140.0, h.capital_prestado);
+        assert_eq!(dec!(84140), h.capital_prestado);
         assert_eq!(0.04, h.tipo_interes_anual);
         assert_eq!(300, h.meses);
         assert_eq!(6, h.meses_hasta_primera_revision);
@@ -150,8 +650,179 @@ mod tests {
         assert_eq!(0.12, h.i_max);
         assert_eq!(Utc.ymd(2018, 5, 17), h.fecha_impago);
         assert_eq!(Utc.ymd(2022, 8, 5), h.fecha_resolucion);
+        assert_eq!(None, h.interes_demora);
+        assert_eq!(Utc.ymd(2004, 3, 17), h.fecha_primera_cuota);
+        assert_eq!(BaseCalculo::Treinta360, h.base_calculo);
+        assert_eq!(AjusteDiaHabil::SinAjuste, h.ajuste_dia_habil);
+        assert_eq!(SistemaAmortizacion::Frances, h.sistema_amortizacion);
+        assert!(h.carencias.is_empty());
+        assert_eq!(None, h.responsabilidad_hipotecaria);
+        assert_eq!(RedondeoTipo::SinRedondeo, h.redondeo_tipo);
+        assert_eq!(ReferenciaRevision::default(), h.referencia_revision);
+        assert_eq!(Redondeo::MitadArriba, h.redondeo);
+        assert_eq!(IndiceReferencia::Euribor12Meses, h.indice);
+        assert_eq!(None, h.indice_sustitutivo);
+        assert_eq!(ComisionAmortizacion::SinComision, h.comision_amortizacion);
+        assert!(h.amortizaciones_anticipadas.is_empty());
+        assert!(h.tramos.is_empty());
 
         //h.tabla_amort_impago = h.calcula_tabla_impago();
     
     }
+    #[test]
+    fn test_read_indice_irph() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let mut book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        let worksheet = book.get_sheet_mut(&0).unwrap();
+        worksheet.get_cell_mut("C51").set_value("IRPH_CAJAS");
+        worksheet.get_cell_mut("C52").set_value("irph_entidades");
+        worksheet.get_cell_mut("C53").set_value("0.25");
+        let mut indices = EuriborData::con_proyeccion();
+        for agno in 2003..=2022 {
+            indices.set_valores_agno(IndiceReferencia::IrphCajas, agno, vec![5.0; 12]);
+            indices.set_valores_agno(IndiceReferencia::IrphEntidades, agno, vec![3.0; 12]);
+        }
+        let h = read_data_from_excel_file(&book, indices).unwrap();
+        assert_eq!(IndiceReferencia::IrphCajas, h.indice);
+        assert_eq!(Some(IndiceReferencia::IrphEntidades), h.indice_sustitutivo);
+        assert_eq!(0.0025, h.diferencial_sustitutivo);
+        // IRPH Cajas + 1 antes de su desaparición y después IRPH Entidades + 0,25 + 1
+        let cuotas = &h.tabla_amort_con_actualizacion_euribor.cuotas;
+        let tipo = |fecha: Date<Utc>| cuotas.iter().find(|c| c.fecha == fecha).unwrap().i;
+        assert_eq!(0.06, tipo(Utc.ymd(2012, 9, 17)));
+        assert_eq!(0.0425, tipo(Utc.ymd(2014, 9, 17)));
+
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C51").set_value("LIBOR");
+        let indices = EuriborData::con_proyeccion();
+        match read_data_from_excel_file(&book, indices) {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C51", celda),
+            _ => panic!("Se esperaba un error en la celda C51"),
+        }
+    }
+    #[test]
+    fn test_read_pagos_y_gastos() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let mut book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C54").set_value("capital, intereses");
+        let pagos = book.new_sheet("Pagos").unwrap();
+        for (celda, valor) in [("A1", "Tipo"), ("B1", "Fecha"), ("C1", "Importe"), ("D1", "Concepto"),
+                ("A2", "PAGO"), ("B2", "17/07/2018"), ("C2", "500"),
+                ("A3", "GASTO"), ("B3", "01/07/2018"), ("C3", "35,50"), ("D3", "Reclamación")] {
+            pagos.get_cell_mut(celda).set_value(valor);
+        }
+        let mut h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(vec![ConceptoImputacion::Capital, ConceptoImputacion::InteresesOrdinarios], h.orden_imputacion);
+        read_pagos_y_gastos(&book, &mut h).unwrap();
+        assert_eq!(1, h.pagos.len());
+        assert_eq!(dec!(500), h.pagos[0].importe);
+        assert_eq!(1, h.gastos.len());
+        assert_eq!(dec!(35.50), h.gastos[0].importe);
+        let posicion = h.calcula_posicion_deudora(Utc.ymd(2018, 8, 1)).unwrap();
+        assert_eq!(dec!(500), posicion.total_imputado(ConceptoImputacion::Capital)
+            + posicion.total_imputado(ConceptoImputacion::InteresesOrdinarios));
+        assert_eq!(dec!(35.50), posicion.total_gastos());
+
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C54").set_value("capital, comisiones");
+        match read_data_from_excel_file(&book, EuriborData::con_proyeccion()) {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C54", celda),
+            _ => panic!("Se esperaba un error en la celda C54"),
+        }
+    }
+    #[test]
+    fn test_read_redondeo() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let mut book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        let intereses = |h: &Hipoteca| h.tabla_amort_sin_actualizacion.cuotas.iter()
+            .map(|c| c.cuota_interes).collect::<Vec<Importe>>();
+        let h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
+        let intereses_mitad_arriba = intereses(&h);
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C55").set_value("truncamiento");
+        let h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(Redondeo::Truncamiento, h.redondeo);
+        assert_ne!(intereses_mitad_arriba, intereses(&h));
+        let worksheet = book.get_sheet_mut(&0).unwrap();
+        worksheet.get_cell_mut("C55").set_value("HACIA_ABAJO");
+        match read_redondeo(worksheet, "C55") {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C55", celda),
+            _ => panic!("Se esperaba un error en la celda C55"),
+        }
+    }
+    #[test]
+    fn test_read_amortizaciones() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let mut book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C56").set_value("variable_5");
+        let hoja = book.new_sheet("Amortizaciones").unwrap();
+        // 17/03/2010 y 17/03/2012 como números de serie de Excel
+        for (celda, valor) in [("A1", "Fecha"), ("B1", "Importe"), ("C1", "Modalidad"),
+                ("A2", "40254"), ("B2", "5000"), ("C2", "REDUCIR_CUOTA"),
+                ("A3", "40985"), ("B3", "3000"), ("C3", "reducir_plazo")] {
+            hoja.get_cell_mut(celda).set_value(valor);
+        }
+        let h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(ComisionAmortizacion::VariableCincoAgnos, h.comision_amortizacion);
+        assert_eq!(vec![
+            AmortizacionAnticipada::new(Utc.ymd(2010, 3, 17), dec!(5000), ModalidadAmortizacion::ReducirCuota),
+            AmortizacionAnticipada::new(Utc.ymd(2012, 3, 17), dec!(3000), ModalidadAmortizacion::ReducirPlazo)],
+            h.amortizaciones_anticipadas);
+        assert!(h.tabla_amort_con_actualizacion_euribor.cuotas.iter().any(|c| c.amortizacion_anticipada));
+
+        book.get_sheet_by_name_mut("Amortizaciones").unwrap().get_cell_mut("C3").set_value("TOTAL");
+        match read_data_from_excel_file(&book, EuriborData::con_proyeccion()) {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("Amortizaciones!C3", celda),
+            _ => panic!("Se esperaba un error en la celda C3 de la hoja Amortizaciones"),
+        }
+    }
+    #[test]
+    fn test_read_tramos() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let mut book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        // Resolución el 31/12/2021, último mes con datos del tramo variable
+        book.get_sheet_mut(&0).unwrap().get_cell_mut("C17").set_value("44561");
+        let hoja = book.new_sheet("Tramos").unwrap();
+        for (celda, valor) in [("A1", "Tipo"), ("B1", "Tipo o diferencial"), ("C1", "Cuotas"),
+                ("A2", "FIJO"), ("B2", "3"), ("C2", "60"),
+                ("A3", "variable"), ("B3", "0.75"), ("D3", "2"), ("E3", "10")] {
+            hoja.get_cell_mut(celda).set_value(valor);
+        }
+        let h = read_data_from_excel_file(&book, EuriborData::con_proyeccion()).unwrap();
+        assert_eq!(vec![TramoInteres::fijo(0.03, 60), 
+            TramoInteres { i_min: Some(0.02), i_max: Some(0.1), ..TramoInteres::variable(0.0075, None) }],
+            h.tramos);
+        assert_eq!(0.03, h.tabla_amort_con_actualizacion_euribor.cuotas[59].i);
+
+        // Un tramo a continuación de otro que dura hasta el final no es válido
+        let hoja = book.get_sheet_by_name_mut("Tramos").unwrap();
+        for (celda, valor) in [("A4", "FIJO"), ("B4", "2"), ("C4", "12")] {
+            hoja.get_cell_mut(celda).set_value(valor);
+        }
+        assert_eq!(Some(HipotecaError::entrada_invalida("tramo", "el tramo anterior dura hasta el final del préstamo")),
+            read_data_from_excel_file(&book, EuriborData::con_proyeccion()).err());
+        book.get_sheet_by_name_mut("Tramos").unwrap().get_cell_mut("A4").set_value("MIXTO");
+        match read_data_from_excel_file(&book, EuriborData::con_proyeccion()) {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("Tramos!A4", celda),
+            _ => panic!("Se esperaba un error en la celda A4 de la hoja Tramos"),
+        }
+    }
+    #[test]
+    fn test_read_celda_no_valida() {
+        let path = std::path::Path::new("assets").join("Libro11.xlsx");
+        let book: Spreadsheet = reader::xlsx::read(&path).unwrap();
+        let worksheet: &Worksheet = book.get_sheet(&0).unwrap();
+        match read_i32(worksheet, "C5") {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C5", celda),
+            _ => panic!("Se esperaba un error en la celda C5"),
+        }
+        // Las celdas opcionales solo se omiten si están vacías
+        assert!(matches!(read_f64_opcional(worksheet, "C18"), Ok(None)));
+        assert!(matches!(read_fecha_opcional(worksheet, "C33"), Ok(None)));
+        match read_f64_opcional(worksheet, "C5") {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C5", celda),
+            _ => panic!("Se esperaba un error en la celda C5"),
+        }
+        match read_fecha_opcional(worksheet, "C5") {
+            Err(HipotecaError::HojaCalculo { celda, .. }) => assert_eq!("C5", celda),
+            _ => panic!("Se esperaba un error en la celda C5"),
+        }
+    }
 }